@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+
+pub const DEFAULT_ALIAS_PATH: &str = "analysis/smurfs.json";
+
+// Alternate account names mapped to the name a player's stats are kept under
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AliasRegistry {
+    aliases: BTreeMap<String, String>,
+}
+
+impl AliasRegistry {
+    pub fn load(input_path: &str) -> Option<Self> {
+        let input_file = File::open(input_path).ok()?;
+        serde_json::from_reader(input_file).ok()
+    }
+
+    // The main name for `name`, or `name` itself if it isn't an alias
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, String::as_str)
+    }
+}
@@ -1,4 +1,9 @@
-use crate::events_reader::{Event, Team};
+use crate::events_reader::{Event, PlayerEvent, Team};
+use crate::log_reader::MatchLog;
+use crate::participation::Participation;
+use crate::win_probability::WinProbabilityModel;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct RelevantEvent {
@@ -8,6 +13,220 @@ pub struct RelevantEvent {
     pub team: Team,
 }
 
+const DEFAULT_MIN_ROSTER_COVERAGE: f64 = 0.95;
+const RANKED_TIME_LIMIT_SECONDS: usize = 8 * 60;
+
+// Once `after` ticks have passed, a lead of at least `min_lead` caps is
+// considered decided
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageTimeRule {
+    pub after: usize,
+    pub min_lead: usize,
+}
+
+// How to tell when a game is decided. Stats the leading team piles up after
+// that point (caps, hold, returns) are counted separately as garbage time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GarbageTime {
+    Rules(Vec<GarbageTimeRule>),
+    // Decided once the leading team's chance of winning reaches `threshold`
+    WinProbability { model: Arc<WinProbabilityModel>, threshold: f64 },
+    Never,
+}
+
+impl GarbageTime {
+    // The ranked rules: a lead of 4 after 5:30, 3 after 6:00 or 2 after 6:30
+    pub fn ranked() -> Self {
+        Self::for_time_limit(8.0)
+    }
+
+    // The ranked rules, with the same fractions of the game for other time limits
+    pub fn for_time_limit(time_limit: f32) -> Self {
+        let limit_seconds = (time_limit * 60.0).round() as usize;
+        let after = |ranked_seconds: usize| ranked_seconds * limit_seconds * 60 / RANKED_TIME_LIMIT_SECONDS;
+        Self::Rules(vec![
+            GarbageTimeRule { after: after(330), min_lead: 4 },
+            GarbageTimeRule { after: after(360), min_lead: 3 },
+            GarbageTimeRule { after: after(390), min_lead: 2 },
+        ])
+    }
+
+    pub fn is_garbage_time(&self, time: usize, lead: isize) -> bool {
+        self.start_time(lead).is_some_and(|start| time > start)
+    }
+
+    // Time after which a lead of this size is garbage time, if ever
+    pub fn start_time(&self, lead: isize) -> Option<usize> {
+        let lead = lead.unsigned_abs();
+        match self {
+            Self::Rules(rules) => rules.iter()
+                .filter(|rule| lead >= rule.min_lead)
+                .map(|rule| rule.after)
+                .min(),
+            Self::WinProbability { model, threshold } => {
+                if lead == 0 {
+                    return None;
+                }
+                (0..=model.time_limit)
+                    .step_by(model.time_bucket)
+                    .find(|&time| model.leader_win_probability(time, lead as isize) >= *threshold)
+            }
+            Self::Never => None,
+        }
+    }
+
+    // How much of [start, end) a team holding `lead` spent in garbage time.
+    // Only the leading team's time counts.
+    pub fn overlap(&self, lead: isize, start: usize, end: usize) -> usize {
+        if lead <= 0 {
+            return 0;
+        }
+        match self.start_time(lead) {
+            Some(garbage_start) => end.saturating_sub(start.max(garbage_start)),
+            None => 0,
+        }
+    }
+}
+
+impl Default for GarbageTime {
+    fn default() -> Self {
+        Self::ranked()
+    }
+}
+
+// Which kinds of groups a match format accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupFilter {
+    // Public games only (`group` is an empty string)
+    Public,
+    // Private group games only (`group` is non-empty)
+    Private,
+    Any,
+}
+
+// Describes the kind of match the processing pipeline should accept.
+#[derive(Debug, Clone)]
+pub struct MatchFormat {
+    pub team_size: usize,
+    // Required time limit in minutes, or `None` to accept any
+    pub time_limit: Option<f32>,
+    pub groups: GroupFilter,
+    pub official_only: bool,
+    // Minimum player-time each team needs, as a fraction of `team_size` full
+    // matches, for a game with substitutes or early quits to still count
+    pub min_roster_coverage: f64,
+    pub garbage_time: GarbageTime,
+    // Used to credit players with win probability added, if set
+    pub win_probability: Option<Arc<WinProbabilityModel>>,
+}
+
+impl MatchFormat {
+    // Standard 4v4, 8 minute public ranked games
+    pub fn ranked() -> Self {
+        Self {
+            team_size: 4,
+            time_limit: Some(8.0),
+            groups: GroupFilter::Public,
+            official_only: true,
+            min_roster_coverage: DEFAULT_MIN_ROSTER_COVERAGE,
+            garbage_time: GarbageTime::ranked(),
+            win_probability: None,
+        }
+    }
+
+    pub fn new(team_size: usize, time_limit: Option<f32>, groups: GroupFilter) -> Self {
+        Self {
+            team_size,
+            time_limit,
+            groups,
+            official_only: true,
+            min_roster_coverage: DEFAULT_MIN_ROSTER_COVERAGE,
+            garbage_time: time_limit.map_or_else(GarbageTime::ranked, GarbageTime::for_time_limit),
+            win_probability: None,
+        }
+    }
+
+    pub fn with_garbage_time(mut self, garbage_time: GarbageTime) -> Self {
+        self.garbage_time = garbage_time;
+        self
+    }
+
+    pub fn with_win_probability(mut self, model: Arc<WinProbabilityModel>) -> Self {
+        self.win_probability = Some(model);
+        self
+    }
+
+    pub fn accepts(&self, match_log: &MatchLog) -> bool {
+        if self.official_only && !match_log.official {
+            return false;
+        }
+        if match_log.players.len() < 2 * self.team_size {
+            return false;
+        }
+        if self.time_limit.is_some_and(|time_limit| match_log.time_limit != time_limit) {
+            return false;
+        }
+        let is_public = match_log.group.as_deref() == Some("");
+        match self.groups {
+            GroupFilter::Public => is_public,
+            GroupFilter::Private => !is_public,
+            GroupFilter::Any => true,
+        }
+    }
+
+    // Slot labels for one team, e.g. `r1..r4`
+    pub fn slot_names(&self, team: Team) -> Vec<String> {
+        let prefix = match team {
+            Team::Red => "r",
+            Team::Blue => "b",
+            Team::None => "s",
+        };
+        (1..=self.team_size).map(|i| format!("{}{}", prefix, i)).collect()
+    }
+
+    pub fn has_full_rosters(&self, participation: &Participation) -> bool {
+        let required = self.team_size as f64 * self.min_roster_coverage;
+        participation.team_coverage(Team::Red) >= required
+            && participation.team_coverage(Team::Blue) >= required
+    }
+}
+
+impl Default for MatchFormat {
+    fn default() -> Self {
+        Self::ranked()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RosterChangeKind {
+    Join,
+    Quit,
+    Switch,
+}
+
+// A player entering, leaving or switching teams mid-match. For switches,
+// `team` is the team the player left.
+#[derive(Debug, Clone)]
+pub struct RosterChange {
+    pub time: usize,
+    pub player_index: usize,
+    pub team: Team,
+    pub kind: RosterChangeKind,
+}
+
+impl RosterChange {
+    // The roster change a player's event makes, if it's a join, quit or switch
+    pub fn from_event(player_index: usize, event: &PlayerEvent) -> Option<Self> {
+        let kind = match event.event_type {
+            Event::Join => RosterChangeKind::Join,
+            Event::Quit => RosterChangeKind::Quit,
+            Event::Switch => RosterChangeKind::Switch,
+            _ => return None,
+        };
+        Some(Self { time: event.time, player_index, team: event.team, kind })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MatchResult<S> {
     pub match_id: String,
@@ -16,29 +235,43 @@ pub struct MatchResult<S> {
     pub duration: usize,
     pub cap_diff: isize,
     pub garbage_time_cap_diff: isize,
+    // Final score, as the match log reports it
+    pub red_caps: usize,
+    pub blue_caps: usize,
     pub red_team: Vec<usize>,
     pub blue_team: Vec<usize>,
+    pub roster_changes: Vec<RosterChange>,
+    pub participation: Participation,
     pub player_stats: Vec<S>,
 }
 
+// Score and flag state carried from one event to the next while a match is processed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchState {
+    // Red caps minus blue caps so far
+    pub cap_diff: isize,
+    pub garbage_time_cap_diff: isize,
+    // Each team's current flag carrier, and when they grabbed
+    pub red_fc: Option<usize>,
+    pub blue_fc: Option<usize>,
+    pub red_grab_time: Option<usize>,
+    pub blue_grab_time: Option<usize>,
+}
+
 pub trait StatConfig {
     type Stats: Default + Clone;
-    
+
     const RELEVANT_EVENTS: &'static [Event];
     const STAT_FIELDS: &'static [&'static str];
-    
+
     fn process_event(
         event: &RelevantEvent,
-        cap_diff: &mut isize,
-        garbage_time_cap_diff: &mut isize,
+        state: &mut MatchState,
+        garbage_time: &GarbageTime,
         match_duration: usize,
-        red_fc: &mut Option<usize>,
-        blue_fc: &mut Option<usize>,
-        red_grab_time: &mut Option<usize>,
-        blue_grab_time: &mut Option<usize>,
         all_player_stats: &mut [Self::Stats],
     );
-    
+
     fn post_process_stats(
         _all_events: &[RelevantEvent],
         _all_player_stats: &mut [Self::Stats],
@@ -47,27 +280,45 @@ pub trait StatConfig {
     ) {
         // Default implementation does nothing
     }
-    
+
+    fn record_wpa(_stats: &mut Self::Stats, _wpa: f64) {
+        // Default implementation does nothing
+    }
+
+    // Every stat as a number, in STAT_FIELDS order
+    fn to_values(stats: &Self::Stats) -> Vec<f64>;
+
     fn to_csv_values(stats: &Self::Stats) -> Vec<String>;
-    
-    fn generate_csv_header() -> String {
-        let mut header_parts = vec!["match_id", "map_id", "timestamp", "duration", "cap_diff", "garbage_time_cap_diff"];
-        header_parts.extend(["r1", "r2", "r3", "r4", "b1", "b2", "b3", "b4"]);
-        
-        let mut stat_parts = Vec::new();
-        for player in ["r1", "r2", "r3", "r4", "b1", "b2", "b3", "b4"] {
+
+    fn generate_csv_header(format: &MatchFormat) -> String {
+        let mut header_parts = vec!["match_id", "map_id", "timestamp", "duration", "cap_diff", "garbage_time_cap_diff"]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        let slots = [format.slot_names(Team::Red), format.slot_names(Team::Blue)].concat();
+        header_parts.extend(slots.iter().cloned());
+
+        for player in slots.iter() {
             for field in Self::STAT_FIELDS {
-                stat_parts.push(format!("{}_{}", player, field));
+                header_parts.push(format!("{}_{}", player, field));
             }
         }
-        
-        header_parts.extend(stat_parts.iter().map(|s| s.as_str()));
+
+        // Time on team for each slot, then any substitutes beyond the slots
+        for player in slots.iter() {
+            header_parts.push(format!("{}_time", player));
+        }
+        header_parts.push("red_subs".to_string());
+        header_parts.push("blue_subs".to_string());
+
         header_parts.join(",")
     }
 }
 
 impl<S> MatchResult<S> {
-    pub fn to_csv_row<C: StatConfig<Stats = S>>(&self, player_names: &[String]) -> String {
+    // Rosters are ordered by time on team, so the first `team_size` players of
+    // each team fill the slots and anyone after that was a substitute.
+    pub fn to_csv_row<C: StatConfig<Stats = S>>(&self, player_names: &[String], format: &MatchFormat) -> String {
         let mut cells = vec![
             self.match_id.clone(),
             self.map_id.to_string(),
@@ -76,19 +327,138 @@ impl<S> MatchResult<S> {
             self.cap_diff.to_string(),
             self.garbage_time_cap_diff.to_string(),
         ];
-        
+
+        let slots = |roster: &[usize]| -> Vec<Option<usize>> {
+            (0..format.team_size).map(|i| roster.get(i).copied()).collect()
+        };
+        let current_players: Vec<Option<usize>> = [slots(&self.red_team), slots(&self.blue_team)].concat();
+
         // Add player names in red team then blue team order
-        let current_players: Vec<usize> = [self.red_team.clone(), self.blue_team.clone()].concat();
-        current_players.iter().for_each(|&player_idx| {
-            cells.push(format!("\"{}\"", player_names[player_idx]));
+        current_players.iter().for_each(|player| {
+            cells.push(match player {
+                Some(player_idx) => format!("\"{}\"", player_names[*player_idx]),
+                None => String::new(),
+            });
         });
-        
+
         // Add player stats
-        current_players.iter().for_each(|&player_idx| {
-            let stat_values = C::to_csv_values(&self.player_stats[player_idx]);
-            cells.extend(stat_values);
+        current_players.iter().for_each(|player| {
+            match player {
+                Some(player_idx) => cells.extend(C::to_csv_values(&self.player_stats[*player_idx])),
+                None => cells.extend(C::STAT_FIELDS.iter().map(|_| String::new())),
+            }
         });
-        
+
+        // Add time on team
+        for (team, roster) in [(Team::Red, &self.red_team), (Team::Blue, &self.blue_team)] {
+            for player in slots(roster) {
+                cells.push(match player {
+                    Some(player_idx) => self.participation.time_on_team(player_idx, team).to_string(),
+                    None => String::new(),
+                });
+            }
+        }
+
+        // Add substitutes as name:time pairs
+        for (team, roster) in [(Team::Red, &self.red_team), (Team::Blue, &self.blue_team)] {
+            let subs: Vec<String> = roster.iter()
+                .skip(format.team_size)
+                .map(|&player_idx| format!("{}:{}", player_names[player_idx], self.participation.time_on_team(player_idx, team)))
+                .collect();
+            cells.push(format!("\"{}\"", subs.join(";")));
+        }
+
         cells.join(",")
     }
-}
\ No newline at end of file
+
+    // Fraction of the match the player spent on their team
+    pub fn share(&self, player_idx: usize) -> f64 {
+        let team = if self.red_team.contains(&player_idx) { Team::Red } else { Team::Blue };
+        self.participation.share(player_idx, team)
+    }
+
+    pub fn minutes_played(&self, player_idx: usize) -> f64 {
+        self.participation.minutes_played(player_idx)
+    }
+
+    // Every stat for the player divided by their minutes played
+    pub fn per_minute_values<C: StatConfig<Stats = S>>(&self, player_idx: usize) -> Vec<f64> {
+        C::to_values(&self.player_stats[player_idx]).into_iter()
+            .map(|value| self.participation.per_minute(player_idx, value))
+            .collect()
+    }
+
+    // Same layout as s1_matches.json: one object per match with a flat player list,
+    // red team first, plus any roster changes that happened during the match.
+    // Each player carries their time on team and share of the match so
+    // substitutes can be given fractional credit.
+    pub fn to_json<C: StatConfig<Stats = S>>(&self, player_names: &[String]) -> Value {
+        let mut players = Vec::new();
+        for (team, roster) in [(Team::Red, &self.red_team), (Team::Blue, &self.blue_team)] {
+            for &player_idx in roster.iter() {
+                let per_minute: Map<String, Value> = C::STAT_FIELDS.iter()
+                    .zip(self.per_minute_values::<C>(player_idx))
+                    .map(|(field, value)| (field.to_string(), Value::from(value)))
+                    .collect();
+                players.push(json!({
+                    "name": player_names[player_idx],
+                    "team": team_label(team),
+                    "time_on_team": self.participation.time_on_team(player_idx, team),
+                    "share": self.participation.share(player_idx, team),
+                    "stats": Self::stats_to_json::<C>(&self.player_stats[player_idx]),
+                    "per_minute": per_minute,
+                }));
+            }
+        }
+
+        let roster_changes: Vec<Value> = self.roster_changes.iter()
+            .map(|change| json!({
+                "time": change.time,
+                "name": player_names[change.player_index],
+                "team": team_label(change.team),
+                "kind": match change.kind {
+                    RosterChangeKind::Join => "join",
+                    RosterChangeKind::Quit => "quit",
+                    RosterChangeKind::Switch => "switch",
+                },
+            }))
+            .collect();
+
+        json!({
+            "match_id": self.match_id,
+            "map_id": self.map_id,
+            "timestamp": self.timestamp,
+            "duration": self.duration,
+            "cap_diff": self.cap_diff,
+            "garbage_time_cap_diff": self.garbage_time_cap_diff,
+            "players": players,
+            "roster_changes": roster_changes,
+        })
+    }
+
+    fn stats_to_json<C: StatConfig<Stats = S>>(stats: &S) -> Value {
+        let mut map = Map::new();
+        for (field, value) in C::STAT_FIELDS.iter().zip(C::to_csv_values(stats)) {
+            map.insert(field.to_string(), csv_value_to_json(&value));
+        }
+        Value::Object(map)
+    }
+}
+
+fn team_label(team: Team) -> &'static str {
+    match team {
+        Team::Red => "red",
+        Team::Blue => "blue",
+        Team::None => "none",
+    }
+}
+
+fn csv_value_to_json(value: &str) -> Value {
+    if let Ok(n) = value.parse::<i64>() {
+        Value::from(n)
+    } else if let Ok(x) = value.parse::<f64>() {
+        Value::from(x)
+    } else {
+        Value::from(value)
+    }
+}
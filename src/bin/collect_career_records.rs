@@ -0,0 +1,10 @@
+use ranked_stats::career_records::collect_career_records;
+use ranked_stats::log_reader::MatchIterator;
+
+fn main() {
+    println!("Collecting career records...");
+    println!("Processing match files 394 through 413...\n");
+
+    let match_iterator = MatchIterator::new(394, 414);
+    collect_career_records(match_iterator);
+}
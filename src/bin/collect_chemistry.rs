@@ -0,0 +1,10 @@
+use ranked_stats::chemistry::collect_chemistry;
+use ranked_stats::log_reader::MatchIterator;
+
+fn main() {
+    println!("Collecting teammate chemistry...");
+    println!("Processing match files 394 through 413...\n");
+
+    let match_iterator = MatchIterator::new(394, 414);
+    collect_chemistry(match_iterator);
+}
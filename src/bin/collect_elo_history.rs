@@ -0,0 +1,10 @@
+use ranked_stats::elo::collect_elo_history;
+use ranked_stats::log_reader::MatchIterator;
+
+fn main() {
+    println!("Running Elo ratings with history...");
+    println!("Processing match files 394 through 413...\n");
+
+    let match_iterator = MatchIterator::new(394, 414);
+    collect_elo_history(match_iterator);
+}
@@ -0,0 +1,18 @@
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::maps::{collect_by_map, collect_map_summary, write_map_reports, MapRegistry, DEFAULT_MAPS_PATH};
+use ranked_stats::records::{CapRunsAndComebacksCollector, CombinedGameRecordsCollector, RecordsCollector, TeamRecordsCollector};
+
+fn main() {
+    println!("Collecting per-map records...");
+    println!("Processing match files 394 through 413...\n");
+
+    collect_map_summary(MatchIterator::new(394, 414));
+
+    // Every record board, split by map
+    let maps = MapRegistry::load(DEFAULT_MAPS_PATH).unwrap_or_default();
+    write_map_reports(&collect_by_map(MatchIterator::new(394, 414), &maps, RecordsCollector::new));
+    write_map_reports(&collect_by_map(MatchIterator::new(394, 414), &maps, TeamRecordsCollector::new));
+    write_map_reports(&collect_by_map(MatchIterator::new(394, 414), &maps, CombinedGameRecordsCollector::new));
+    write_map_reports(&collect_by_map(MatchIterator::new(394, 414), &maps, CapRunsAndComebacksCollector::new));
+    println!("Per-map records written to analysis/");
+}
@@ -0,0 +1,45 @@
+use ranked_stats::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use ranked_stats::career_records::CareerRecordsCollector;
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::records::{
+    CapRunsAndComebacksCollector, CombinedGameRecordsCollector, RecordCollector, RecordsCollector, TeamRecordsCollector,
+};
+use ranked_stats::seasons::{collect_by_period, load_seasons, parse_date, write_period_reports, Period, Segmentation, DEFAULT_SEASONS_PATH};
+
+const START_INDEX: usize = 394;
+const END_INDEX: usize = 417;
+
+fn run<C: RecordCollector>(segmentation: &Segmentation, new_collector: impl Fn() -> C) {
+    let match_iterator = MatchIterator::new(START_INDEX, END_INDEX);
+    write_period_reports(&collect_by_period(match_iterator, segmentation, new_collector));
+}
+
+// Usage: collect-period-records seasons | months | window <YYYY-MM-DD> <YYYY-MM-DD>
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let segmentation = match args.get(1).map(String::as_str) {
+        Some("seasons") => Segmentation::Periods(
+            load_seasons(DEFAULT_SEASONS_PATH).unwrap_or_else(|| panic!("Could not open {}", DEFAULT_SEASONS_PATH)),
+        ),
+        Some("months") => Segmentation::Monthly,
+        Some("window") if args.len() == 4 => {
+            let start = parse_date(&args[2]).expect("Invalid start date");
+            let end = parse_date(&args[3]).expect("Invalid end date");
+            Segmentation::Periods(vec![Period::dates(&format!("{} to {}", args[2], args[3]), start, end)])
+        }
+        _ => {
+            eprintln!("Usage: collect-period-records seasons | months | window <YYYY-MM-DD> <YYYY-MM-DD>");
+            std::process::exit(1);
+        }
+    };
+
+    println!("Collecting records per period...");
+    println!("Processing match files {} through {}...\n", START_INDEX, END_INDEX - 1);
+
+    let aliases = AliasRegistry::load(DEFAULT_ALIAS_PATH).unwrap_or_default();
+    run(&segmentation, RecordsCollector::new);
+    run(&segmentation, TeamRecordsCollector::new);
+    run(&segmentation, CombinedGameRecordsCollector::new);
+    run(&segmentation, CapRunsAndComebacksCollector::new);
+    run(&segmentation, || CareerRecordsCollector::new().with_aliases(aliases.clone()));
+}
@@ -1,10 +1,26 @@
-use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::log_reader::{file_range_from_args, MatchIterator};
 use ranked_stats::records::collect_all_records;
+use ranked_stats::win_probability::WinProbabilityModel;
+use std::sync::Arc;
 
+const START_INDEX: usize = 394;
+const END_INDEX: usize = 414;
+
+// Usage: collect-player-records [--files <start> <end>] [--wpa-model <model.json>]
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (start_index, end_index) = file_range_from_args(&args, START_INDEX, END_INDEX).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+    let win_probability = WinProbabilityModel::from_args(&args, start_index, end_index).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+
     println!("Collecting player records...");
-    println!("Processing match files 394 through 413...\n");
+    println!("Processing match files {} through {}...\n", start_index, end_index - 1);
 
-    let match_iterator = MatchIterator::new(394, 414);
-    collect_all_records(match_iterator);
+    let match_iterator = MatchIterator::new(start_index, end_index);
+    collect_all_records(match_iterator, win_probability.map(Arc::new));
 }
@@ -0,0 +1,10 @@
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::plus_minus::collect_plus_minus;
+
+fn main() {
+    println!("Collecting plus/minus and adjusted plus/minus...");
+    println!("Processing match files 394 through 413...\n");
+
+    let match_iterator = MatchIterator::new(394, 414);
+    collect_plus_minus(match_iterator);
+}
@@ -1,23 +1,36 @@
-use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::log_reader::{file_range_from_args, MatchIterator};
 use ranked_stats::records::{collect_all_records, collect_team_records, collect_combined_game_records};
+use ranked_stats::win_probability::WinProbabilityModel;
+use std::sync::Arc;
 
+// Usage: collect-records [--files <start> <end>] [--wpa-model <model.json>]
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (start_index, end_index) = file_range_from_args(&args, 394, 416).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+    let win_probability = WinProbabilityModel::from_args(&args, start_index, end_index).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+
     println!("Collecting all-time TagPro ranked records...");
-    println!("Processing match files 394 through 413...\n");
+    println!("Processing match files {} through {}...\n", start_index, end_index - 1);
 
     // Process all ranked match logs for player records
-    let match_iterator = MatchIterator::new(394, 416);
-    collect_all_records(match_iterator);
+    let match_iterator = MatchIterator::new(start_index, end_index);
+    collect_all_records(match_iterator, win_probability.map(Arc::new));
 
     println!();
 
     // Process all ranked match logs for team records
-    let match_iterator2 = MatchIterator::new(394, 416);
+    let match_iterator2 = MatchIterator::new(start_index, end_index);
     collect_team_records(match_iterator2);
 
     println!();
 
     // Process all ranked match logs for combined game records
-    let match_iterator3 = MatchIterator::new(394, 416);
+    let match_iterator3 = MatchIterator::new(start_index, end_index);
     collect_combined_game_records(match_iterator3);
 }
@@ -0,0 +1,10 @@
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::streaks::collect_streak_records;
+
+fn main() {
+    println!("Collecting streak records...");
+    println!("Processing match files 394 through 413...\n");
+
+    let match_iterator = MatchIterator::new(394, 414);
+    collect_streak_records(match_iterator);
+}
@@ -0,0 +1,10 @@
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::rating_systems::compare_rating_systems;
+
+fn main() {
+    println!("Comparing rating systems...");
+    println!("Processing match files 394 through 413...\n");
+
+    let match_iterator = MatchIterator::new(394, 414);
+    compare_rating_systems(match_iterator);
+}
@@ -0,0 +1,31 @@
+use ranked_stats::leaderboard::RecordRow;
+use ranked_stats::record_diff::{diff_records, write_record_diff};
+use ranked_stats::records::load_record_rows;
+
+fn read_rows(path: &str) -> Vec<RecordRow> {
+    match load_record_rows(path) {
+        Ok(Some(rows)) => rows,
+        Ok(None) => {
+            eprintln!("Could not read {}", path);
+            std::process::exit(1);
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Compare two structured reports (or saved collector states) and write a changelog
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 4 {
+        eprintln!("Usage: diff-records <old.json> <new.json> <changes.txt>");
+        std::process::exit(1);
+    }
+
+    let old = read_rows(&args[1]);
+    let new = read_rows(&args[2]);
+    write_record_diff(&diff_records(&old, &new), &args[2], &args[3]);
+    println!("Record changes written to {}", args[3]);
+}
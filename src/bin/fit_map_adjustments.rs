@@ -0,0 +1,10 @@
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::map_adjustments::fit_map_adjustments;
+
+fn main() {
+    println!("Fitting per-map side advantage...");
+    println!("Processing match files 394 through 413...\n");
+
+    let match_iterator = MatchIterator::new(394, 414);
+    fit_map_adjustments(match_iterator);
+}
@@ -0,0 +1,30 @@
+use ranked_stats::analysis_types::MatchFormat;
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::win_probability::{WinProbabilityModel, DEFAULT_MODEL_PATH};
+
+const START_INDEX: usize = 394;
+const END_INDEX: usize = 414;
+
+// Usage: fit-win-probability [<start file> <end file>]
+// Fit on different match files than the ones the model will score.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (start_index, end_index) = match args.len() {
+        1 => (START_INDEX, END_INDEX),
+        3 => (args[1].parse().expect("Invalid start file"), args[2].parse().expect("Invalid end file")),
+        _ => {
+            eprintln!("Usage: fit-win-probability [<start file> <end file>]");
+            std::process::exit(1);
+        }
+    };
+
+    println!("Fitting win probability model...");
+    println!("Processing match files {} through {}...\n", start_index, end_index - 1);
+
+    let match_iterator = MatchIterator::new(start_index, end_index);
+    let mut model = WinProbabilityModel::fit(match_iterator, &MatchFormat::ranked());
+    model.fitted_files = Some((start_index, end_index));
+    model.save(DEFAULT_MODEL_PATH);
+    model.write_table_csv("analysis/win_probability.csv");
+    println!("Win probability model fitted! Output written to analysis/win_probability.json and analysis/win_probability.csv");
+}
@@ -0,0 +1,32 @@
+use ranked_stats::head_to_head::load_head_to_head;
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::seasons::slug;
+
+const START_INDEX: usize = 394;
+const END_INDEX: usize = 414;
+
+// Usage: head-to-head [<player> <player>]
+// With no players, reports the most-played rivalries
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 1 && args.len() != 3 {
+        eprintln!("Usage: head-to-head [<player> <player>]");
+        std::process::exit(1);
+    }
+
+    println!("Indexing ranked matches for head to head...");
+    println!("Processing match files {} through {}...\n", START_INDEX, END_INDEX - 1);
+    let index = load_head_to_head(MatchIterator::new(START_INDEX, END_INDEX));
+
+    if args.len() == 3 {
+        let output_path = format!("analysis/head_to_head_{}_vs_{}.txt", slug(&args[1]), slug(&args[2]));
+        if index.generate_query_report(&args[1], &args[2], &output_path) {
+            println!("Head to head written to {}", output_path);
+        } else {
+            println!("{} and {} never played in the same game", args[1], args[2]);
+        }
+    } else {
+        index.generate_report("analysis/rivalries.txt");
+        println!("Rivalries collected! Output written to analysis/rivalries.txt");
+    }
+}
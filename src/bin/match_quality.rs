@@ -0,0 +1,10 @@
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::match_quality::collect_match_quality;
+
+fn main() {
+    println!("Grading ranked match quality...");
+    println!("Processing match files 394 through 413...\n");
+
+    let match_iterator = MatchIterator::new(394, 414);
+    collect_match_quality(match_iterator);
+}
@@ -0,0 +1,56 @@
+use ranked_stats::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use ranked_stats::career_records::CareerRecordsCollector;
+use ranked_stats::elo::load_elo_ratings;
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::maps::{MapRegistry, DEFAULT_MAPS_PATH};
+use ranked_stats::profiles::{build_profile, ProfileBuilder};
+use ranked_stats::records::{
+    load_record_rows, CapRunsAndComebacksCollector, CombinedGameRecordsCollector, RecordCollector, RecordsCollector,
+    TeamRecordsCollector,
+};
+use ranked_stats::seasons::slug;
+use ranked_stats::streaks::StreakRecordsCollector;
+
+const START_INDEX: usize = 394;
+const END_INDEX: usize = 414;
+
+// Usage: player-profile <player>
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Usage: player-profile <player>");
+        std::process::exit(1);
+    }
+
+    println!("Building profile for {}...", args[1]);
+    println!("Processing match files {} through {}...\n", START_INDEX, END_INDEX - 1);
+
+    let mut builder = ProfileBuilder::new(&args[1])
+        .with_aliases(AliasRegistry::load(DEFAULT_ALIAS_PATH).unwrap_or_default())
+        .with_maps(MapRegistry::load(DEFAULT_MAPS_PATH).unwrap_or_default());
+
+    // Records held come from whichever record reports have already been written
+    for report_name in [
+        RecordsCollector::REPORT_NAME,
+        TeamRecordsCollector::REPORT_NAME,
+        CombinedGameRecordsCollector::REPORT_NAME,
+        CapRunsAndComebacksCollector::REPORT_NAME,
+        CareerRecordsCollector::REPORT_NAME,
+        StreakRecordsCollector::REPORT_NAME,
+    ] {
+        match load_record_rows(&format!("analysis/{}.json", report_name)) {
+            Ok(Some(rows)) => builder = builder.with_records(&rows),
+            Ok(None) => {}
+            Err(error) => eprintln!("Skipping {}: {}", report_name, error),
+        }
+    }
+
+    let ratings = load_elo_ratings(MatchIterator::new(START_INDEX, END_INDEX));
+    builder = builder.with_rating_history(ratings.history_for(&args[1]));
+
+    let profile = build_profile(MatchIterator::new(START_INDEX, END_INDEX), builder);
+    let base_path = format!("analysis/profile_{}", slug(&profile.name));
+    profile.write_json(&format!("{}.json", base_path));
+    profile.write_markdown(&format!("{}.md", base_path));
+    println!("Profile written to {}.{{json,md}}", base_path);
+}
@@ -0,0 +1,10 @@
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::matchmaker::simulate_matchmaker;
+
+fn main() {
+    println!("Simulating matchmaker strategies...");
+    println!("Processing match files 394 through 413...\n");
+
+    let match_iterator = MatchIterator::new(394, 414);
+    simulate_matchmaker(match_iterator);
+}
@@ -0,0 +1,29 @@
+use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::win_probability::{WinProbabilityModel, DEFAULT_MODEL_PATH};
+
+const START_INDEX: usize = 394;
+const END_INDEX: usize = 417;
+
+// Usage: win-probability-curve <match id> [<model.json>]
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("Usage: win-probability-curve <match id> [<model.json>]");
+        std::process::exit(1);
+    }
+    let match_id = &args[1];
+    let model_path = args.get(2).map_or(DEFAULT_MODEL_PATH, String::as_str);
+    let model = WinProbabilityModel::load(model_path).unwrap_or_else(|| {
+        eprintln!("Could not read win probability model from {}; fit one with fit-win-probability", model_path);
+        std::process::exit(1);
+    });
+
+    let Some((_, match_log)) = MatchIterator::new(START_INDEX, END_INDEX).find(|(id, _)| id == match_id) else {
+        eprintln!("Match {} not found in match files {} through {}", match_id, START_INDEX, END_INDEX - 1);
+        std::process::exit(1);
+    };
+
+    let output_path = format!("analysis/win_probability_curve_{}.csv", match_id);
+    model.write_curve_csv(&match_log, &output_path);
+    println!("Win probability curve written to {}", output_path);
+}
@@ -0,0 +1,316 @@
+use crate::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use crate::leaderboard::{record_rows, write_record_outputs, Cutoff, Leaderboard, LeaderboardEntry, Order, RecordRow};
+use crate::log_reader::{MatchIterator, MatchLog};
+use crate::records::{is_record_match, write_changes, CollectorState, PlayerGameStats, PlayerStatLines, RecordCollector};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+
+const MINIMUM_CAREER_GAMES: usize = 20;
+const CAREER_RECORDS_PER_BOARD: usize = 10;
+const RATE_SCALE: f64 = 100.0; // averages are stored in hundredths so they can key a leaderboard
+const CAREER_RECORDS_STATE_PATH: &str = "analysis/career_records_state.json";
+
+// A player's totals over every game they played
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CareerTotals {
+    pub games: usize,
+    // Games and wins credited by share of each match, so a substitute's
+    // partial game counts as that fraction of one
+    pub credited_games: f64,
+    pub wins: f64,
+    pub caps: usize,
+    pub returns: usize,
+    pub tags: usize,
+    pub pops: usize,
+    pub grabs: usize,
+    pub pups: usize,
+    pub quick_returns: usize,
+
+    // Time-based stats (in ticks)
+    pub hold: usize,
+    pub prevent: usize,
+    pub time_played: usize,
+}
+
+impl CareerTotals {
+    pub fn add_game(&mut self, stats: &PlayerGameStats, game_credit: f64, win_credit: f64) {
+        self.games += 1;
+        self.credited_games += game_credit;
+        self.wins += win_credit;
+        self.caps += stats.caps;
+        self.returns += stats.returns;
+        self.tags += stats.tags;
+        self.pops += stats.pops;
+        self.grabs += stats.grabs;
+        self.pups += stats.pups;
+        self.quick_returns += stats.quick_returns;
+        self.hold += stats.hold;
+        self.prevent += stats.prevent;
+        self.time_played += stats.time_played;
+    }
+
+    pub fn minutes_played(&self) -> f64 {
+        self.time_played as f64 / 3600.0
+    }
+}
+
+// How a career stat is reported. Everything but totals needs a minimum number of games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CareerStatKind {
+    Total,
+    PerGame,
+    PerMinute,
+    Percentage,
+}
+
+struct CareerStatDefinition {
+    title: &'static str,
+    kind: CareerStatKind,
+    value: fn(&CareerTotals) -> f64,
+}
+
+const CAREER_STATS: &[CareerStatDefinition] = &[
+    // Totals
+    CareerStatDefinition { title: "Games Played", kind: CareerStatKind::Total, value: |totals| totals.games as f64 },
+    CareerStatDefinition { title: "Wins", kind: CareerStatKind::Total, value: |totals| totals.wins.round() },
+    CareerStatDefinition { title: "Minutes Played", kind: CareerStatKind::Total, value: |totals| totals.minutes_played().floor() },
+    CareerStatDefinition { title: "Captures", kind: CareerStatKind::Total, value: |totals| totals.caps as f64 },
+    CareerStatDefinition { title: "Returns", kind: CareerStatKind::Total, value: |totals| totals.returns as f64 },
+    CareerStatDefinition { title: "Tags", kind: CareerStatKind::Total, value: |totals| totals.tags as f64 },
+    CareerStatDefinition { title: "Pops", kind: CareerStatKind::Total, value: |totals| totals.pops as f64 },
+    CareerStatDefinition { title: "Grabs", kind: CareerStatKind::Total, value: |totals| totals.grabs as f64 },
+    CareerStatDefinition { title: "Hold (seconds)", kind: CareerStatKind::Total, value: |totals| (totals.hold / 60) as f64 },
+    CareerStatDefinition { title: "Prevent (seconds)", kind: CareerStatKind::Total, value: |totals| (totals.prevent / 60) as f64 },
+    CareerStatDefinition { title: "Powerups", kind: CareerStatKind::Total, value: |totals| totals.pups as f64 },
+    CareerStatDefinition { title: "Quick Returns", kind: CareerStatKind::Total, value: |totals| totals.quick_returns as f64 },
+
+    // Averages
+    CareerStatDefinition { title: "Win Rate (%)", kind: CareerStatKind::Percentage, value: |totals| totals.wins },
+    CareerStatDefinition { title: "Captures per Game", kind: CareerStatKind::PerGame, value: |totals| totals.caps as f64 },
+    CareerStatDefinition { title: "Returns per Game", kind: CareerStatKind::PerGame, value: |totals| totals.returns as f64 },
+    CareerStatDefinition { title: "Tags per Game", kind: CareerStatKind::PerGame, value: |totals| totals.tags as f64 },
+    CareerStatDefinition { title: "Powerups per Game", kind: CareerStatKind::PerGame, value: |totals| totals.pups as f64 },
+    CareerStatDefinition { title: "Captures per Minute", kind: CareerStatKind::PerMinute, value: |totals| totals.caps as f64 },
+    CareerStatDefinition { title: "Returns per Minute", kind: CareerStatKind::PerMinute, value: |totals| totals.returns as f64 },
+    CareerStatDefinition { title: "Hold per Minute (seconds)", kind: CareerStatKind::PerMinute, value: |totals| totals.hold as f64 / 60.0 },
+    CareerStatDefinition { title: "Prevent per Minute (seconds)", kind: CareerStatKind::PerMinute, value: |totals| totals.prevent as f64 / 60.0 },
+];
+
+impl CareerStatDefinition {
+    // Leaderboard key for a career, or None if the player doesn't qualify
+    fn key(&self, totals: &CareerTotals, minimum_games: usize) -> Option<usize> {
+        let value = (self.value)(totals);
+        let average = match self.kind {
+            CareerStatKind::Total => return Some(value as usize),
+            _ if totals.games < minimum_games => return None,
+            CareerStatKind::PerGame if totals.credited_games > 0.0 => value / totals.credited_games,
+            CareerStatKind::PerGame => return None,
+            CareerStatKind::PerMinute if totals.time_played > 0 => value / totals.minutes_played(),
+            CareerStatKind::PerMinute => return None,
+            CareerStatKind::Percentage if totals.credited_games > 0.0 => 100.0 * value / totals.credited_games,
+            CareerStatKind::Percentage => return None,
+        };
+        Some((average * RATE_SCALE).round() as usize)
+    }
+
+    fn scale(&self) -> f64 {
+        if self.kind == CareerStatKind::Total { 1.0 } else { RATE_SCALE }
+    }
+}
+
+// A career on a board. Careers span many matches, so there's no match id.
+#[derive(Debug, Clone)]
+pub struct CareerEntry {
+    players: Vec<String>,
+    games: usize,
+}
+
+impl LeaderboardEntry for CareerEntry {
+    fn match_id(&self) -> &str {
+        ""
+    }
+
+    fn players(&self) -> &[String] {
+        &self.players
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CareerRecordsCollector {
+    ingested: BTreeSet<String>,
+    careers: BTreeMap<String, CareerTotals>,
+    minimum_games: usize,
+    // Only matches with start <= date < end count
+    date_range: Option<(usize, usize)>,
+    #[serde(skip)]
+    aliases: AliasRegistry,
+}
+
+impl CollectorState for CareerRecordsCollector {}
+
+impl RecordCollector for CareerRecordsCollector {
+    const REPORT_NAME: &'static str = "career_records";
+    const TITLE: &'static str = "Career Ranked TagPro Records";
+
+    fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        Self::process_match(self, match_id, match_log);
+    }
+
+    fn generate_report(&self, output_path: &str) {
+        Self::generate_report(self, output_path);
+    }
+
+    fn record_rows(&self) -> Vec<RecordRow> {
+        Self::record_rows(self)
+    }
+}
+
+impl Default for CareerRecordsCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CareerRecordsCollector {
+    pub fn new() -> Self {
+        Self {
+            ingested: BTreeSet::new(),
+            careers: BTreeMap::new(),
+            minimum_games: MINIMUM_CAREER_GAMES,
+            date_range: None,
+            aliases: AliasRegistry::default(),
+        }
+    }
+
+    // Minimum games for per-game, per-minute, and win rate boards
+    pub fn with_minimum_games(mut self, games: usize) -> Self {
+        self.minimum_games = games;
+        self
+    }
+
+    // Count only matches played from `start` up to (not including) `end`, as unix timestamps
+    pub fn with_date_range(mut self, start: usize, end: usize) -> Self {
+        self.date_range = Some((start, end));
+        self
+    }
+
+    // Combine alternate accounts under their main names
+    pub fn with_aliases(mut self, aliases: AliasRegistry) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn careers(&self) -> &BTreeMap<String, CareerTotals> {
+        &self.careers
+    }
+
+    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        if !self.ingested.insert(match_id) {
+            return;
+        }
+
+        // Filter matches
+        if !is_record_match(match_log) {
+            return;
+        }
+        if let Some((start, end)) = self.date_range && (match_log.date < start || match_log.date >= end) {
+            return;
+        }
+
+        let lines = PlayerStatLines::from_match_log(match_log);
+        for (player_idx, player) in match_log.players.iter().enumerate() {
+            if lines.participation.time_played(player_idx) == 0 {
+                continue;
+            }
+            let player_name = self.aliases.resolve(&player.name).to_string();
+            self.careers.entry(player_name).or_default()
+                .add_game(&lines.full[player_idx], lines.game_credit(player_idx), lines.win_credit(player_idx));
+        }
+    }
+
+    // One board per entry in CAREER_STATS
+    fn leaderboards(&self) -> Vec<Leaderboard<usize, CareerEntry>> {
+        CAREER_STATS.iter()
+            .map(|definition| {
+                let mut board = Leaderboard::new(Order::Highest, Cutoff::Ties(CAREER_RECORDS_PER_BOARD)).skipping_zero();
+                for (player_name, totals) in &self.careers {
+                    if let Some(key) = definition.key(totals, self.minimum_games) {
+                        board.insert(key, CareerEntry { players: vec![player_name.clone()], games: totals.games });
+                    }
+                }
+                board
+            })
+            .collect()
+    }
+
+    pub fn generate_report(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+
+        writeln!(file, "=== CAREER RANKED TAGPRO RECORDS ===\n").unwrap();
+        writeln!(file, "(Averages and win rate require at least {} games)\n", self.minimum_games).unwrap();
+
+        for (definition, board) in CAREER_STATS.iter().zip(self.leaderboards()) {
+            writeln!(file, "### {}", definition.title).unwrap();
+            let rows = board.top();
+            if rows.is_empty() {
+                writeln!(file, "No records found.\n").unwrap();
+                continue;
+            }
+            let records = record_rows(definition.title, "career", &rows, |key| key as f64 / definition.scale());
+            for (record, row) in records.iter().zip(rows.iter()) {
+                let value = if definition.kind == CareerStatKind::Total { format!("{}", record.value) } else { format!("{:.2}", record.value) };
+                writeln!(file, "  {}. {} - {} ({} games)", record.rank, record.players.join(", "), value, row.entry.games).unwrap();
+            }
+            writeln!(file).unwrap();
+        }
+    }
+
+    // Every board as structured rows, in report order
+    pub fn record_rows(&self) -> Vec<RecordRow> {
+        CAREER_STATS.iter()
+            .zip(self.leaderboards())
+            .flat_map(|(definition, board)| record_rows(definition.title, "career", &board.top(), |key| key as f64 / definition.scale()))
+            .collect()
+    }
+}
+
+pub fn collect_career_records(match_iterator: MatchIterator) {
+    let saved = match CareerRecordsCollector::load_state(CAREER_RECORDS_STATE_PATH) {
+        Ok(saved) => saved,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+    let previous = saved.as_ref().map(CareerRecordsCollector::record_rows);
+    let mut collector = saved.unwrap_or_else(CareerRecordsCollector::new);
+    if let Some(aliases) = AliasRegistry::load(DEFAULT_ALIAS_PATH) {
+        collector = collector.with_aliases(aliases);
+    }
+
+    for (match_id, match_log) in match_iterator {
+        collector.process_match(match_id, &match_log);
+    }
+
+    collector.save_state(CAREER_RECORDS_STATE_PATH);
+    collector.generate_report("analysis/career_records.txt");
+    let rows = collector.record_rows();
+    write_record_outputs(&rows, CareerRecordsCollector::TITLE, "analysis/career_records");
+    write_changes(previous, &rows, CareerRecordsCollector::TITLE, "analysis/career_records_changes.txt");
+    println!("Career records collected! Output written to analysis/career_records.{{txt,json,csv,md}}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_game_averages_use_credited_games() {
+        // Ten appearances as a substitute, each a tenth of the match
+        let totals = CareerTotals { games: 10, credited_games: 1.0, caps: 2, ..Default::default() };
+        let captures_per_game = CAREER_STATS.iter().find(|definition| definition.title == "Captures per Game").unwrap();
+        assert_eq!(captures_per_game.key(&totals, 10), Some((2.0 * RATE_SCALE) as usize));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use crate::elo::{diff_mapping, EloRatings};
+use crate::events_reader::Team;
+use crate::log_reader::{MatchIterator, MatchLog};
+use crate::maps::{MapRegistry, DEFAULT_MAPS_PATH};
+use crate::participation::RosterSpot;
+use crate::records::{is_record_match, PlayerStatLines};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+
+const MINIMUM_GROUP_GAMES: usize = 10;
+const GROUPS_PER_BOARD: usize = 15;
+const PARTNERS_PER_PLAYER: usize = 3;
+
+// One team's side of a game
+struct TeamGame {
+    // This team's caps minus the opponents'
+    cap_diff: isize,
+    // Expected score the rating model gave this team going into the game
+    expected_score: f64,
+}
+
+// How a duo or trio did in the games they shared a team
+#[derive(Debug, Clone, Default)]
+pub struct GroupTotals {
+    pub games: usize,
+    pub wins: usize,
+    pub cap_diff: isize,
+    // Each game's cap margin as an Elo score, and what the ratings going into
+    // the game expected it to be
+    pub score: f64,
+    pub expected_score: f64,
+}
+
+impl GroupTotals {
+    pub fn win_rate(&self) -> f64 {
+        100.0 * self.wins as f64 / self.games as f64
+    }
+
+    pub fn cap_diff_per_game(&self) -> f64 {
+        self.cap_diff as f64 / self.games as f64
+    }
+
+    // Score per game above (or below) what the ratings expected
+    pub fn performance_per_game(&self) -> f64 {
+        (self.score - self.expected_score) / self.games as f64
+    }
+}
+
+// Every pair and trio of players who shared a team, compared against what
+// the Elo model expected of them. Each game is graded with the ratings going
+// into it, so a duo's own results can't shape the expectation they're judged by.
+pub struct ChemistryCollector {
+    ingested: BTreeSet<String>,
+    games: Vec<TeamGame>,
+    // Sorted player names mapped to indices into `games`
+    groups: BTreeMap<Vec<String>, Vec<usize>>,
+    ratings: EloRatings,
+    minimum_games: usize,
+    aliases: AliasRegistry,
+}
+
+impl Default for ChemistryCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChemistryCollector {
+    pub fn new() -> Self {
+        Self {
+            ingested: BTreeSet::new(),
+            games: Vec::new(),
+            groups: BTreeMap::new(),
+            ratings: EloRatings::new().with_prior_map_adjustments(),
+            minimum_games: MINIMUM_GROUP_GAMES,
+            aliases: AliasRegistry::default(),
+        }
+    }
+
+    // Only CTF maps are rated. Maps missing from the registry are rated too.
+    pub fn with_maps(mut self, maps: MapRegistry) -> Self {
+        self.ratings = self.ratings.with_maps(maps);
+        self
+    }
+
+    // Minimum games together for a duo or trio to be ranked
+    pub fn with_minimum_games(mut self, games: usize) -> Self {
+        self.minimum_games = games;
+        self
+    }
+
+    // Combine alternate accounts under their main names
+    pub fn with_aliases(mut self, aliases: AliasRegistry) -> Self {
+        self.ratings = self.ratings.with_aliases(aliases.clone());
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        if !self.ingested.insert(match_id.clone()) {
+            return;
+        }
+
+        // Grade the match against the ratings going into it, then rate it
+        if is_record_match(match_log) {
+            self.add_team_games(match_log);
+        }
+        self.ratings.process_match(match_id, match_log);
+    }
+
+    fn add_team_games(&mut self, match_log: &MatchLog) {
+        let lines = PlayerStatLines::from_match_log(match_log);
+        let participation = &lines.participation;
+        let side = |team: Team| -> Vec<RosterSpot> {
+            participation.roster(team).into_iter()
+                .map(|player_idx| RosterSpot {
+                    name: self.aliases.resolve(&match_log.players[player_idx].name).to_string(),
+                    share: participation.share(player_idx, team),
+                })
+                .collect()
+        };
+        let (red, blue) = (side(Team::Red), side(Team::Blue));
+        // The expected score is for a whole match, however long it ran, and
+        // saturates like the cap margins it's compared against
+        let max_score = diff_mapping(isize::MAX);
+        let red_expected = self.ratings.expected_score(&red, &blue, match_log.map_id).clamp(-max_score, max_score);
+
+        for (players, cap_diff, expected_score) in [(red, lines.cap_diff, red_expected), (blue, -lines.cap_diff, -red_expected)] {
+            let mut names: Vec<String> = players.into_iter().map(|spot| spot.name).collect();
+            names.sort();
+            names.dedup();
+
+            let game_index = self.games.len();
+            for (i, first) in names.iter().enumerate() {
+                for (j, second) in names.iter().enumerate().skip(i + 1) {
+                    self.groups.entry(vec![first.clone(), second.clone()]).or_default().push(game_index);
+                    for third in names.iter().skip(j + 1) {
+                        self.groups.entry(vec![first.clone(), second.clone(), third.clone()]).or_default().push(game_index);
+                    }
+                }
+            }
+            self.games.push(TeamGame { cap_diff, expected_score });
+        }
+    }
+
+    // Totals for every group, keyed like `groups`
+    pub fn group_totals(&self) -> BTreeMap<Vec<String>, GroupTotals> {
+        self.groups.iter()
+            .map(|(players, game_indices)| {
+                let mut totals = GroupTotals::default();
+                for &game_index in game_indices {
+                    let game = &self.games[game_index];
+                    totals.games += 1;
+                    totals.wins += (game.cap_diff > 0) as usize;
+                    totals.cap_diff += game.cap_diff;
+                    totals.score += diff_mapping(game.cap_diff);
+                    totals.expected_score += game.expected_score;
+                }
+                (players.clone(), totals)
+            })
+            .collect()
+    }
+
+    // Qualifying groups of `size` players, best performance against expectation first
+    fn ranked<'a>(&self, totals: &'a BTreeMap<Vec<String>, GroupTotals>, size: usize) -> Vec<(&'a Vec<String>, &'a GroupTotals)> {
+        let mut ranked: Vec<(&Vec<String>, &GroupTotals)> = totals.iter()
+            .filter(|(players, totals)| players.len() == size && totals.games >= self.minimum_games)
+            .collect();
+        ranked.sort_by(|a, b| b.1.performance_per_game().total_cmp(&a.1.performance_per_game()).then_with(|| a.0.cmp(b.0)));
+        ranked
+    }
+
+    fn write_group(file: &mut File, rank: usize, players: &[String], totals: &GroupTotals) {
+        writeln!(file, "  {}. {} - {:+.2} vs expected ({} games, {:.1}% wins, {:+.2} caps per game)",
+            rank, players.join(", "), totals.performance_per_game(), totals.games, totals.win_rate(), totals.cap_diff_per_game()).unwrap();
+    }
+
+    pub fn generate_report(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        let totals = self.group_totals();
+
+        writeln!(file, "=== RANKED TAGPRO TEAMMATE CHEMISTRY ===\n").unwrap();
+        writeln!(file, "(Elo score per game above what the pre-match ratings expected, at least {} games together)\n", self.minimum_games).unwrap();
+
+        for (size, label) in [(2, "DUOS"), (3, "TRIOS")] {
+            let ranked = self.ranked(&totals, size);
+            // The worst board only takes groups the best board didn't
+            let worst_count = ranked.len().saturating_sub(GROUPS_PER_BOARD).min(GROUPS_PER_BOARD);
+            for (title, rows) in [
+                (format!("## BEST {}", label), ranked.iter().take(GROUPS_PER_BOARD).collect::<Vec<_>>()),
+                (format!("## WORST {}", label), ranked.iter().rev().take(worst_count).collect::<Vec<_>>()),
+            ] {
+                writeln!(file, "{}", title).unwrap();
+                if rows.is_empty() {
+                    writeln!(file, "No records found.\n").unwrap();
+                    continue;
+                }
+                for (rank, (players, group)) in rows.iter().enumerate() {
+                    Self::write_group(&mut file, rank + 1, players, group);
+                }
+                writeln!(file).unwrap();
+            }
+        }
+
+        // Each player's best qualifying partners
+        writeln!(file, "## BEST PARTNERS\n").unwrap();
+        let mut partners: BTreeMap<&str, Vec<(&Vec<String>, &GroupTotals)>> = BTreeMap::new();
+        for (players, group) in self.ranked(&totals, 2) {
+            for player in players {
+                partners.entry(player).or_default().push((players, group));
+            }
+        }
+        for (player, duos) in partners {
+            writeln!(file, "### {}", player).unwrap();
+            for (rank, (players, group)) in duos.iter().take(PARTNERS_PER_PLAYER).enumerate() {
+                let partner: Vec<String> = players.iter().filter(|name| *name != player).cloned().collect();
+                Self::write_group(&mut file, rank + 1, &partner, group);
+            }
+            writeln!(file).unwrap();
+        }
+    }
+
+    // Every duo and trio, including those under the minimum
+    pub fn write_csv(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        writeln!(file, "players,size,games,wins,win_rate,cap_diff,score,expected_score,performance_per_game").unwrap();
+        for (players, totals) in self.group_totals() {
+            let names: Vec<String> = players.iter().map(|name| name.escape_default().to_string()).collect();
+            writeln!(file, "\"{}\",{},{},{},{:.2},{},{:.4},{:.4},{:.4}", names.join("; "), players.len(), totals.games, totals.wins,
+                totals.win_rate(), totals.cap_diff, totals.score, totals.expected_score, totals.performance_per_game()).unwrap();
+        }
+    }
+}
+
+// Measures each duo and trio against the Elo ratings going into their games
+pub fn collect_chemistry(match_iterator: MatchIterator) {
+    let aliases = AliasRegistry::load(DEFAULT_ALIAS_PATH).unwrap_or_default();
+    let maps = MapRegistry::load(DEFAULT_MAPS_PATH).unwrap_or_default();
+    let mut collector = ChemistryCollector::new().with_aliases(aliases).with_maps(maps);
+
+    for (match_id, match_log) in match_iterator {
+        collector.process_match(match_id, &match_log);
+    }
+
+    collector.generate_report("analysis/chemistry.txt");
+    collector.write_csv("analysis/chemistry.csv");
+    println!("Chemistry collected! Output written to analysis/chemistry.{{txt,csv}}");
+}
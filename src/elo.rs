@@ -0,0 +1,431 @@
+use crate::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use crate::analysis_types::{MatchResult, StatConfig};
+use crate::event_processor::process_ranked_match;
+use crate::events_reader::Team;
+use crate::log_reader::{MatchIterator, MatchLog};
+use crate::map_adjustments::{MapAdjustmentFit, MapAdjustments};
+use crate::maps::{MapRegistry, DEFAULT_MAPS_PATH};
+use crate::participation::RosterSpot;
+use crate::profiles::RatingPoint;
+use crate::ranked_analysis::{RankedPlayerStats, RankedStatConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+
+// Model constants, as tuned in analysis/mmr_stats.ipynb
+const STARTING_VARIANCE: f64 = 1.2;
+const VARIANCE_TO_ADD_BACK: f64 = 0.002;
+const BASE_VARIANCE: f64 = 4.5;
+const RED_ADVANTAGE: f64 = 0.1;
+const NEW_PLAYER_ELO: f64 = -1.1;
+const NEWNESS_THRESHOLD: f64 = 0.05;
+const NEW_PLAYER_ELO_BOOST: f64 = 0.1;
+const DIFF_WEIGHT_INITIAL: f64 = 0.65;
+const DIFF_WEIGHT_CONVERGED: f64 = 0.6;
+const GARBAGE_TIME_DISCOUNT: f64 = 0.35;
+const RELATIVE_ELO_CORRECTION: f64 = 0.05;
+// Expected score for each cap margin, 0 through 5. Negative margins mirror these.
+const DIFF_MAPPING: [f64; 6] = [0.0, 0.2, 0.9, 2.1, 3.1, 4.2];
+// Players whose variance is at most this anchor the daily normalization
+const KNOWN_PLAYER_VARIANCE: f64 = 0.2;
+const MINIMUM_NORMALIZATION_PLAYERS: usize = 10;
+// Players need a variance this low to appear on the leaderboard
+const LEADERBOARD_VARIANCE: f64 = 0.21;
+const BIGGEST_MOVES_IN_REPORT: usize = 25;
+
+// Stat weights by RankedStatConfig::STAT_FIELDS name, blended from the
+// initial to the converged set as a player's variance shrinks. Stats not
+// listed weigh nothing.
+const PLAYER_STAT_WEIGHTS_INITIAL: &[(&str, f64)] = &[
+    ("caps", 0.8), ("garbage_time_caps", -0.3), ("hold", 1.7 / 3600.0), ("ndps", -0.5),
+    ("returns", 0.35), ("nrts", 0.3), ("pups", 0.3), ("handoffs", 0.2),
+];
+const TEAM_STAT_WEIGHTS_INITIAL: &[(&str, f64)] = &[
+    ("garbage_time_caps", -0.3), ("hold", 0.8 / 3600.0), ("returns", 0.04),
+];
+const PLAYER_STAT_WEIGHTS_CONVERGED: &[(&str, f64)] = &[
+    ("caps", 0.05),
+];
+const TEAM_STAT_WEIGHTS_CONVERGED: &[(&str, f64)] = &[
+    ("hold", 0.4 / 3600.0), ("returns", 0.02),
+];
+
+// One player's rating change from one match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatingUpdate {
+    pub match_id: String,
+    pub timestamp: usize,
+    pub player: String,
+    // "red" or "blue"
+    pub team: String,
+    // Fraction of the match the player spent on their team
+    pub share: f64,
+    pub pre_rating: f64,
+    pub post_rating: f64,
+    pub pre_variance: f64,
+    pub post_variance: f64,
+    // Cap differential the model expected, from the player's side
+    pub expected_score: f64,
+    pub win_probability: f64,
+    // Actual cap differential, from the player's side
+    pub cap_diff: isize,
+}
+
+impl RatingUpdate {
+    pub fn change(&self) -> f64 {
+        self.post_rating - self.pre_rating
+    }
+
+    pub fn result(&self) -> &'static str {
+        match self.cap_diff {
+            diff if diff > 0 => "win",
+            diff if diff < 0 => "loss",
+            _ => "tie",
+        }
+    }
+}
+
+// Expected score for a cap margin, saturating at 5
+pub fn diff_mapping(cap_diff: isize) -> f64 {
+    let margin = DIFF_MAPPING[cap_diff.unsigned_abs().min(DIFF_MAPPING.len() - 1)];
+    if cap_diff < 0 { -margin } else { margin }
+}
+
+// Line up named weights with RankedStatConfig::STAT_FIELDS
+fn stat_weights(weights: &[(&str, f64)]) -> Vec<f64> {
+    for (field, _) in weights {
+        assert!(RankedStatConfig::STAT_FIELDS.contains(field), "Elo weight for unknown stat {}", field);
+    }
+    RankedStatConfig::STAT_FIELDS.iter()
+        .map(|field| weights.iter().find(|(name, _)| name == field).map_or(0.0, |&(_, weight)| weight))
+        .collect()
+}
+
+// Each slot's stat score: the player's weighted stats over a full match,
+// relative to their team's share-weighted average, plus the team's weighted
+// stat edge. The first `red_slots` slots are red.
+fn judge_stats(lines: &[Vec<f64>], shares: &[f64], red_slots: usize, player_weights: &[(&str, f64)], team_weights: &[(&str, f64)]) -> Vec<f64> {
+    let (player_weights, team_weights) = (stat_weights(player_weights), stat_weights(team_weights));
+    let weigh = |line: &Vec<f64>, weights: &[f64]| line.iter().zip(weights).map(|(value, weight)| value * weight).sum::<f64>();
+    let scores: Vec<f64> = lines.iter().map(|line| weigh(line, &player_weights)).collect();
+    let team_score_diff = lines[..red_slots].iter().map(|line| weigh(line, &team_weights)).sum::<f64>()
+        - lines[red_slots..].iter().map(|line| weigh(line, &team_weights)).sum::<f64>();
+    let red_average = scores[..red_slots].iter().sum::<f64>() / shares[..red_slots].iter().sum::<f64>();
+    let blue_average = scores[red_slots..].iter().sum::<f64>() / shares[red_slots..].iter().sum::<f64>();
+    scores.iter()
+        .zip(shares)
+        .enumerate()
+        .map(|(slot, (score, share))| {
+            if slot < red_slots { score / share - red_average + team_score_diff } else { score / share - blue_average - team_score_diff }
+        })
+        .collect()
+}
+
+// Chance of winning, given the cap differential the model expects
+pub fn win_probability(expected_score: f64) -> f64 {
+    1.0 / (1.0 + (-expected_score / 2.0).exp())
+}
+
+// The notebook's rating model: a per-player rating and variance, updated
+// after every ranked CTF match from the cap margin and each player's stats
+pub struct EloRatings {
+    elo: BTreeMap<String, f64>,
+    variance: BTreeMap<String, f64>,
+    day: i64,
+    history: Vec<RatingUpdate>,
+    aliases: AliasRegistry,
+    maps: MapRegistry,
+    map_adjustments: MapAdjustments,
+    // Refits map_adjustments after every match, when set
+    map_fit: Option<MapAdjustmentFit>,
+}
+
+impl Default for EloRatings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EloRatings {
+    pub fn new() -> Self {
+        Self {
+            elo: BTreeMap::new(),
+            variance: BTreeMap::new(),
+            day: 0,
+            history: Vec::new(),
+            aliases: AliasRegistry::default(),
+            maps: MapRegistry::default(),
+            map_adjustments: MapAdjustments::uniform(RED_ADVANTAGE),
+            map_fit: None,
+        }
+    }
+
+    // Combine alternate accounts under their main names
+    pub fn with_aliases(mut self, aliases: AliasRegistry) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    // Only CTF maps are rated. Maps missing from the registry are rated too.
+    pub fn with_maps(mut self, maps: MapRegistry) -> Self {
+        self.maps = maps;
+        self
+    }
+
+    // Per-map side advantage and scoring factor in place of the flat red advantage.
+    // These should be fitted on other matches than the ones being rated.
+    pub fn with_map_adjustments(mut self, map_adjustments: MapAdjustments) -> Self {
+        self.map_adjustments = map_adjustments;
+        self.map_fit = None;
+        self
+    }
+
+    // Per-map adjustments fitted only from the matches rated so far
+    pub fn with_prior_map_adjustments(mut self) -> Self {
+        self.map_fit = Some(MapAdjustmentFit::new());
+        self
+    }
+
+    pub fn rating(&self, player: &str) -> Option<(f64, f64)> {
+        let player = self.aliases.resolve(player);
+        Some((*self.elo.get(player)?, *self.variance.get(player)?))
+    }
+
+    // The rating a player would take into their next match, new players included
+    pub fn current_rating(&self, player: &str) -> f64 {
+        self.elo.get(self.aliases.resolve(player)).copied().unwrap_or(NEW_PLAYER_ELO)
+    }
+
+    pub fn ratings(&self) -> BTreeMap<String, f64> {
+        self.elo.clone()
+    }
+
+    pub fn history(&self) -> &[RatingUpdate] {
+        &self.history
+    }
+
+    // One player's rating after each of their matches
+    pub fn history_for(&self, player: &str) -> Vec<RatingPoint> {
+        let player = self.aliases.resolve(player);
+        self.history.iter()
+            .filter(|update| update.player == player)
+            .map(|update| RatingPoint { match_id: update.match_id.clone(), date: update.timestamp, rating: update.post_rating })
+            .collect()
+    }
+
+    // Red's expected cap differential on a map, with each player's rating
+    // counted by their share of the match
+    pub fn expected_score(&self, red: &[RosterSpot], blue: &[RosterSpot], map_id: usize) -> f64 {
+        let strength = |team: &[RosterSpot]| team.iter()
+            .map(|spot| spot.share * self.elo.get(&spot.name).copied().unwrap_or(NEW_PLAYER_ELO))
+            .sum::<f64>();
+        self.map_adjustments.expected_score(map_id, strength(red) - strength(blue))
+    }
+
+    fn get_elo(&mut self, player: &str) -> f64 {
+        *self.elo.entry(player.to_string()).or_insert(NEW_PLAYER_ELO)
+    }
+
+    fn get_variance(&mut self, player: &str) -> f64 {
+        *self.variance.entry(player.to_string()).or_insert(STARTING_VARIANCE)
+    }
+
+    // Recenter ratings on the established players, and let settled variances creep back up
+    fn normalize(&mut self) {
+        let known: Vec<f64> = self.elo.iter()
+            .filter(|(player, _)| self.variance[*player] <= KNOWN_PLAYER_VARIANCE)
+            .map(|(_, elo)| *elo)
+            .collect();
+        let average = known.iter().sum::<f64>() / known.len().max(MINIMUM_NORMALIZATION_PLAYERS) as f64;
+        for (player, elo) in self.elo.iter_mut() {
+            *elo -= average;
+            let variance = self.variance.get_mut(player).unwrap();
+            if *variance < STARTING_VARIANCE / 2.0 {
+                *variance = (STARTING_VARIANCE / 2.0).min(*variance + VARIANCE_TO_ADD_BACK);
+            }
+        }
+    }
+
+    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        let is_ctf = self.maps.get(match_log.map_id).is_none_or(|map| map.map_type == "ctf");
+        if !is_ctf {
+            return;
+        }
+        if let Some((result, player_names)) = process_ranked_match::<RankedStatConfig>(match_id, match_log) {
+            self.add_result(&result, &player_names);
+        }
+    }
+
+    pub fn add_result(&mut self, result: &MatchResult<RankedPlayerStats>, player_names: &[String]) {
+        // Ratings are recentered at the start of each new day
+        let day = (result.timestamp as f64 / 86400.0).round_ties_even() as i64;
+        if day != self.day {
+            self.normalize();
+        }
+        self.day = day;
+
+        // Everyone on each roster gets a slot, red then blue, weighted by their share of the match
+        let red = result.participation.shares(Team::Red);
+        let blue = result.participation.shares(Team::Blue);
+        let slots: Vec<(usize, f64)> = red.iter().chain(blue.iter()).copied().collect();
+        let spots: Vec<RosterSpot> = slots.iter()
+            .map(|&(player_idx, share)| RosterSpot { name: self.aliases.resolve(&player_names[player_idx]).to_string(), share })
+            .collect();
+        let shares: Vec<f64> = slots.iter().map(|&(_, share)| share).collect();
+        let lines: Vec<Vec<f64>> = slots.iter()
+            .map(|&(player_idx, _)| RankedStatConfig::to_values(&result.player_stats[player_idx]))
+            .collect();
+
+        let expected_score = self.expected_score(&spots[..red.len()], &spots[red.len()..], result.map_id);
+        let mut total_variance = BASE_VARIANCE;
+        for spot in &spots {
+            self.get_elo(&spot.name);
+            total_variance += spot.share * self.get_variance(&spot.name);
+        }
+        let game_average_elo = spots.iter().map(|spot| spot.share * self.elo[&spot.name]).sum::<f64>() / shares.iter().sum::<f64>();
+
+        let error = diff_mapping(result.cap_diff)
+            - result.garbage_time_cap_diff as f64 * GARBAGE_TIME_DISCOUNT
+            - expected_score.clamp(-DIFF_MAPPING[5], DIFF_MAPPING[5]);
+        let initial = judge_stats(&lines, &shares, red.len(), PLAYER_STAT_WEIGHTS_INITIAL, TEAM_STAT_WEIGHTS_INITIAL);
+        let converged = judge_stats(&lines, &shares, red.len(), PLAYER_STAT_WEIGHTS_CONVERGED, TEAM_STAT_WEIGHTS_CONVERGED);
+
+        for (slot, spot) in spots.iter().enumerate() {
+            let is_red = slot < red.len();
+            let sign = if is_red { 1.0 } else { -1.0 };
+            let pre_rating = self.elo[&spot.name];
+            let pre_variance = self.variance[&spot.name];
+
+            // Substitutes move by their share of what a full match would move them
+            let share_of_variance = pre_variance / total_variance * spot.share;
+            let newness = pre_variance / STARTING_VARIANCE;
+            let error_weight = DIFF_WEIGHT_INITIAL * newness + DIFF_WEIGHT_CONVERGED * (1.0 - newness);
+            let stat_score = initial[slot] * newness + converged[slot] * (1.0 - newness);
+            let update = error_weight * sign * error + stat_score - (pre_rating - game_average_elo) * RELATIVE_ELO_CORRECTION;
+
+            let mut post_rating = pre_rating + update * share_of_variance;
+            let post_variance = pre_variance * (1.0 - share_of_variance);
+            if post_variance > NEWNESS_THRESHOLD {
+                post_rating += NEW_PLAYER_ELO_BOOST * share_of_variance;
+            }
+            self.elo.insert(spot.name.clone(), post_rating);
+            self.variance.insert(spot.name.clone(), post_variance);
+
+            let expected = sign * expected_score;
+            self.history.push(RatingUpdate {
+                match_id: result.match_id.clone(),
+                timestamp: result.timestamp,
+                player: spot.name.clone(),
+                team: if is_red { "red" } else { "blue" }.to_string(),
+                share: spot.share,
+                pre_rating,
+                post_rating,
+                pre_variance,
+                post_variance,
+                expected_score: expected,
+                win_probability: win_probability(expected),
+                cap_diff: if is_red { result.cap_diff } else { -result.cap_diff },
+            });
+        }
+
+        if let Some(map_fit) = &mut self.map_fit {
+            map_fit.add_game(result.map_id, result.red_caps, result.blue_caps);
+            self.map_adjustments = map_fit.adjustments(&self.maps);
+        }
+    }
+
+    pub fn write_history_csv(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        writeln!(file, "match_id,timestamp,player,team,share,pre_rating,post_rating,pre_variance,post_variance,expected_score,win_probability,cap_diff,result").unwrap();
+        for update in &self.history {
+            writeln!(file, "{},{},\"{}\",{},{:.4},{:.4},{:.4},{:.4},{:.4},{:.4},{:.4},{},{}",
+                update.match_id, update.timestamp, update.player.escape_default(), update.team, update.share, update.pre_rating, update.post_rating,
+                update.pre_variance, update.post_variance, update.expected_score, update.win_probability, update.cap_diff, update.result()).unwrap();
+        }
+    }
+
+    pub fn write_history_json(&self, output_path: &str) {
+        let output_file = File::create(output_path).expect("Could not create output file.");
+        serde_json::to_writer(output_file, &self.history).expect("Could not write rating history to file.");
+    }
+
+    // The leaderboard the notebook prints, then the biggest single-match moves
+    pub fn generate_report(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        writeln!(file, "=== RANKED TAGPRO ELO ===\n").unwrap();
+
+        let mut leaderboard: Vec<(&String, f64, f64)> = self.elo.iter()
+            .filter(|(player, _)| self.variance[*player] <= LEADERBOARD_VARIANCE)
+            .map(|(player, elo)| (player, *elo, self.variance[player]))
+            .collect();
+        leaderboard.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        writeln!(file, "### Ratings").unwrap();
+        for (rank, (player, elo, variance)) in leaderboard.iter().enumerate() {
+            writeln!(file, "{:>3}.  {:<12}  {:5.2} ± {:.2}", rank + 1, player, elo, 1.96 * variance.sqrt()).unwrap();
+        }
+        writeln!(file).unwrap();
+
+        let mut moves: Vec<&RatingUpdate> = self.history.iter().collect();
+        moves.sort_by(|a, b| b.change().abs().total_cmp(&a.change().abs()));
+        writeln!(file, "### Biggest Rating Moves").unwrap();
+        for (rank, update) in moves.iter().take(BIGGEST_MOVES_IN_REPORT).enumerate() {
+            writeln!(file, "  {}. Match {}: {} {:+.2} ({:.2} to {:.2}, {} {:+})", rank + 1, update.match_id, update.player,
+                update.change(), update.pre_rating, update.post_rating, update.result(), update.cap_diff).unwrap();
+        }
+        writeln!(file).unwrap();
+    }
+}
+
+pub fn load_elo_ratings(match_iterator: MatchIterator) -> EloRatings {
+    let mut ratings = EloRatings::new()
+        .with_aliases(AliasRegistry::load(DEFAULT_ALIAS_PATH).unwrap_or_default())
+        .with_maps(MapRegistry::load(DEFAULT_MAPS_PATH).unwrap_or_default())
+        .with_prior_map_adjustments();
+    for (match_id, match_log) in match_iterator {
+        ratings.process_match(match_id, &match_log);
+    }
+    ratings
+}
+
+pub fn collect_elo_history(match_iterator: MatchIterator) {
+    let ratings = load_elo_ratings(match_iterator);
+    ratings.generate_report("analysis/elo.txt");
+    ratings.write_history_csv("analysis/elo_history.csv");
+    ratings.write_history_json("analysis/elo_history.json");
+    println!("Elo history collected! Output written to analysis/elo.txt and analysis/elo_history.{{csv,json}}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stat_weights_follow_stat_fields() {
+        for weights in [PLAYER_STAT_WEIGHTS_INITIAL, TEAM_STAT_WEIGHTS_INITIAL, PLAYER_STAT_WEIGHTS_CONVERGED, TEAM_STAT_WEIGHTS_CONVERGED] {
+            let aligned = stat_weights(weights);
+            assert_eq!(aligned.len(), RankedStatConfig::STAT_FIELDS.len());
+            for (field, weight) in weights {
+                let index = RankedStatConfig::STAT_FIELDS.iter().position(|name| name == field).unwrap();
+                assert_eq!(aligned[index], *weight);
+            }
+        }
+    }
+
+    #[test]
+    fn substitutes_are_judged_per_full_match() {
+        let caps = RankedStatConfig::STAT_FIELDS.iter().position(|&field| field == "caps").unwrap();
+        let line = |value: f64| {
+            let mut line = vec![0.0; RankedStatConfig::STAT_FIELDS.len()];
+            line[caps] = value;
+            line
+        };
+        // Two full-time red players and two halves of a third, then four full-time blue players
+        let lines = vec![line(2.0), line(2.0), line(1.0), line(1.0), line(0.0), line(0.0), line(0.0), line(0.0)];
+        let shares = [1.0, 1.0, 0.5, 0.5, 1.0, 1.0, 1.0, 1.0];
+        let scores = judge_stats(&lines, &shares, 4, &[("caps", 1.0)], &[]);
+        for score in &scores[..4] {
+            assert!(score.abs() < 1e-9);
+        }
+    }
+}
@@ -1,18 +1,23 @@
-use crate::analysis_types::{RelevantEvent, MatchResult, StatConfig};
-use crate::events_reader::{Event, EventsReader, Team};
+use crate::analysis_types::{MatchFormat, MatchResult, MatchState, RelevantEvent, RosterChange, StatConfig};
+use crate::events_reader::{EventsReader, Team};
 use crate::log_reader::MatchLog;
+use crate::participation::Participation;
+use crate::win_probability::MatchTimeline;
 use num_traits::FromPrimitive;
 
 pub fn process_ranked_match<C: StatConfig>(
     match_id: String,
     match_log: &MatchLog,
 ) -> Option<(MatchResult<C::Stats>, Vec<String>)> {
-    // Filter matches like the original code
-    if !match_log.official
-        || match_log.players.len() < 8
-        || match_log.group != Some("".to_string())
-        || match_log.time_limit != 8.0
-    {
+    process_match::<C>(match_id, match_log, &MatchFormat::ranked())
+}
+
+pub fn process_match<C: StatConfig>(
+    match_id: String,
+    match_log: &MatchLog,
+    format: &MatchFormat,
+) -> Option<(MatchResult<C::Stats>, Vec<String>)> {
+    if !format.accepts(match_log) {
         return None;
     }
 
@@ -25,26 +30,22 @@ pub fn process_ranked_match<C: StatConfig>(
         player_stats.push(C::Stats::default());
     }
     
-    let mut red_team: Vec<usize> = Vec::new();
-    let mut blue_team: Vec<usize> = Vec::new();
+    let mut initial_teams: Vec<Team> = Vec::new();
+    let mut roster_changes: Vec<RosterChange> = Vec::new();
 
     // Collect events from each player
     for (player_idx, player) in match_log.players.iter().enumerate() {
+        let team = Team::from_usize(player.team).expect("Could not parse Team enum.");
         let player_events = EventsReader::new(player.events.clone())
-            .player_events(
-                Team::from_usize(player.team).expect("Could not parse Team enum."),
-                match_log.duration,
-            );
-        
+            .player_events(team, match_log.duration);
+
         // Track team membership
-        match Team::from_usize(player.team).expect("Could not parse Team enum.") {
-            Team::Red => red_team.push(player_idx),
-            Team::Blue => blue_team.push(player_idx),
-            _ => {}
-        }
+        initial_teams.push(team);
 
-        // Convert player events to relevant events if they match our config
+        // Convert player events to relevant events if they match our config. Roster
+        // changes are always tracked, whether or not the config wants them.
         for event in player_events {
+            roster_changes.extend(RosterChange::from_event(player_idx, &event));
             if C::RELEVANT_EVENTS.contains(&event.event_type) {
                 relevant_events.push(RelevantEvent {
                     time: event.time,
@@ -58,57 +59,38 @@ pub fn process_ranked_match<C: StatConfig>(
 
     // Sort all events by time (your unified timeline approach)
     relevant_events.sort_unstable_by_key(|x| x.time);
+    roster_changes.sort_by_key(|x| x.time);
+
+    // Work out how long everyone spent on each team. Players are assigned to the
+    // team they spent the most time on, longest-serving first.
+    let participation = Participation::from_roster_changes(&initial_teams, &roster_changes, match_log.duration);
+    let red_team = participation.roster(Team::Red);
+    let blue_team = participation.roster(Team::Blue);
 
     // Process events in chronological order
-    let mut cap_diff: isize = 0;
-    let mut garbage_time_cap_diff: isize = 0;
-    let mut red_fc: Option<usize> = None;
-    let mut blue_fc: Option<usize> = None;
-    let mut red_grab_time: Option<usize> = None;
-    let mut blue_grab_time: Option<usize> = None;
+    let mut state = MatchState::default();
     
     for event in relevant_events.iter() {
-        // Handle team changes from Join/Quit events first
-        match event.event_type {
-            Event::Join => {
-                if !red_team.contains(&event.player_index) 
-                    && !blue_team.contains(&event.player_index) 
-                {
-                    match event.team {
-                        Team::Red => red_team.push(event.player_index),
-                        Team::Blue => blue_team.push(event.player_index),
-                        _ => {}
-                    }
-                }
-            }
-            Event::Quit => {
-                match event.team {
-                    Team::Red => red_team.retain(|&x| x != event.player_index),
-                    Team::Blue => blue_team.retain(|&x| x != event.player_index),
-                    _ => {}
-                }
-            }
-            _ => {}
-        }
-        
         // Process the event using the config
-        C::process_event(
-            event,
-            &mut cap_diff,
-            &mut garbage_time_cap_diff,
-            match_log.duration,
-            &mut red_fc,
-            &mut blue_fc,
-            &mut red_grab_time,
-            &mut blue_grab_time,
-            &mut player_stats,
-        );
+        C::process_event(event, &mut state, &format.garbage_time, match_log.duration, &mut player_stats);
     }
 
-    // Only return results for valid 4v4 matches
-    if red_team.len() == 4 && blue_team.len() == 4 {
+    // Only return results where both teams had a (nearly) full roster for the
+    // whole match, counting substitutes' time
+    if red_team.len() >= format.team_size
+        && blue_team.len() >= format.team_size
+        && format.has_full_rosters(&participation)
+    {
         // Post-process stats for complex calculations
         C::post_process_stats(&relevant_events, &mut player_stats, &red_team, &blue_team);
+
+        // Credit win probability added, if there's a model to measure it with
+        if let Some(model) = &format.win_probability {
+            let wpa = model.player_wpa(&MatchTimeline::from_match_log(match_log), player_stats.len());
+            for (stats, player_wpa) in player_stats.iter_mut().zip(wpa) {
+                C::record_wpa(stats, player_wpa);
+            }
+        }
         
         // Extract player names
         let player_names: Vec<String> = match_log.players.iter()
@@ -120,10 +102,14 @@ pub fn process_ranked_match<C: StatConfig>(
             timestamp: match_log.date,
             map_id: match_log.map_id,
             duration: match_log.duration,
-            cap_diff,
-            garbage_time_cap_diff,
+            cap_diff: state.cap_diff,
+            garbage_time_cap_diff: state.garbage_time_cap_diff,
+            red_caps: match_log.teams[0].score,
+            blue_caps: match_log.teams[1].score,
             red_team,
             blue_team,
+            roster_changes,
+            participation,
             player_stats,
         };
         
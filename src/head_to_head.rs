@@ -0,0 +1,268 @@
+use crate::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use crate::analysis_types::MatchResult;
+use crate::event_processor::process_ranked_match;
+use crate::events_reader::Team;
+use crate::log_reader::MatchIterator;
+use crate::ranked_analysis::{RankedPlayerStats, RankedStatConfig};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+
+const RIVALRIES_IN_REPORT: usize = 25;
+
+// The stats a head-to-head compares, for one player in one game
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GameLine {
+    pub caps: usize,
+    pub returns: usize,
+    pub ndps: usize,
+    pub pups: usize,
+    // Ticks
+    pub hold: usize,
+    pub prevent: usize,
+}
+
+impl GameLine {
+    fn from_stats(stats: &RankedPlayerStats) -> Self {
+        Self {
+            caps: stats.caps,
+            returns: stats.returns,
+            ndps: stats.ndps,
+            pups: stats.pups,
+            hold: stats.hold,
+            prevent: stats.prevent,
+        }
+    }
+
+    fn add(&mut self, other: &GameLine) {
+        self.caps += other.caps;
+        self.returns += other.returns;
+        self.ndps += other.ndps;
+        self.pups += other.pups;
+        self.hold += other.hold;
+        self.prevent += other.prevent;
+    }
+}
+
+// One processed match, reduced to rosters, result, and each player's line
+struct HeadToHeadGame {
+    red: Vec<String>,
+    blue: Vec<String>,
+    // Red caps minus blue caps
+    cap_diff: isize,
+    lines: BTreeMap<String, GameLine>,
+}
+
+impl HeadToHeadGame {
+    fn team_of(&self, player: &str) -> Team {
+        if self.red.iter().any(|name| name == player) {
+            Team::Red
+        } else if self.blue.iter().any(|name| name == player) {
+            Team::Blue
+        } else {
+            Team::None
+        }
+    }
+
+    // Cap diff from a team's point of view
+    fn cap_diff_for(&self, team: Team) -> isize {
+        if team == Team::Red { self.cap_diff } else { -self.cap_diff }
+    }
+}
+
+// Games two players shared, from the first player's point of view
+#[derive(Debug, Clone, Default)]
+pub struct SharedGames {
+    pub games: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub ties: usize,
+    // The first player's team's caps minus the other team's
+    pub cap_diff: isize,
+    pub first_totals: GameLine,
+    pub second_totals: GameLine,
+}
+
+impl SharedGames {
+    fn add(&mut self, cap_diff: isize, first: &GameLine, second: &GameLine) {
+        self.games += 1;
+        self.wins += (cap_diff > 0) as usize;
+        self.losses += (cap_diff < 0) as usize;
+        self.ties += (cap_diff == 0) as usize;
+        self.cap_diff += cap_diff;
+        self.first_totals.add(first);
+        self.second_totals.add(second);
+    }
+
+    fn per_game(&self, total: usize) -> f64 {
+        if self.games == 0 { 0.0 } else { total as f64 / self.games as f64 }
+    }
+}
+
+// Everything two players have done against and alongside each other
+#[derive(Debug, Clone)]
+pub struct Rivalry {
+    pub first: String,
+    pub second: String,
+    pub as_opponents: SharedGames,
+    pub as_teammates: SharedGames,
+}
+
+// Every processed ranked match, indexed by player for head-to-head queries
+#[derive(Default)]
+pub struct HeadToHeadIndex {
+    ingested: BTreeSet<String>,
+    games: Vec<HeadToHeadGame>,
+    // Player name mapped to indices into `games`
+    player_games: BTreeMap<String, Vec<usize>>,
+    aliases: AliasRegistry,
+}
+
+impl HeadToHeadIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Combine alternate accounts under their main names
+    pub fn with_aliases(mut self, aliases: AliasRegistry) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn add_result(&mut self, result: &MatchResult<RankedPlayerStats>, player_names: &[String]) {
+        if !self.ingested.insert(result.match_id.clone()) {
+            return;
+        }
+
+        let name = |player_idx: usize| self.aliases.resolve(&player_names[player_idx]).to_string();
+        let red: Vec<String> = result.red_team.iter().map(|&player_idx| name(player_idx)).collect();
+        let blue: Vec<String> = result.blue_team.iter().map(|&player_idx| name(player_idx)).collect();
+        let lines: BTreeMap<String, GameLine> = result.red_team.iter().chain(result.blue_team.iter())
+            .map(|&player_idx| (name(player_idx), GameLine::from_stats(&result.player_stats[player_idx])))
+            .collect();
+
+        let game_index = self.games.len();
+        for player in lines.keys() {
+            self.player_games.entry(player.clone()).or_default().push(game_index);
+        }
+        self.games.push(HeadToHeadGame { red, blue, cap_diff: result.cap_diff, lines });
+    }
+
+    pub fn add_matches(&mut self, match_iterator: MatchIterator) {
+        for (match_id, match_log) in match_iterator {
+            if let Some((result, player_names)) = process_ranked_match::<RankedStatConfig>(match_id, &match_log) {
+                self.add_result(&result, &player_names);
+            }
+        }
+    }
+
+    // Head-to-head and teammate records for two players, or None if they never shared a game
+    pub fn query(&self, first: &str, second: &str) -> Option<Rivalry> {
+        let first = self.aliases.resolve(first);
+        let second = self.aliases.resolve(second);
+        let second_games: BTreeSet<usize> = self.player_games.get(second)?.iter().copied().collect();
+
+        let mut rivalry = Rivalry {
+            first: first.to_string(),
+            second: second.to_string(),
+            as_opponents: SharedGames::default(),
+            as_teammates: SharedGames::default(),
+        };
+        for game_index in self.player_games.get(first)?.iter().filter(|game_index| second_games.contains(game_index)) {
+            let game = &self.games[*game_index];
+            let (first_team, second_team) = (game.team_of(first), game.team_of(second));
+            let shared = if first_team == second_team { &mut rivalry.as_teammates } else { &mut rivalry.as_opponents };
+            shared.add(game.cap_diff_for(first_team), &game.lines[first], &game.lines[second]);
+        }
+
+        if rivalry.as_opponents.games == 0 && rivalry.as_teammates.games == 0 {
+            return None;
+        }
+        Some(rivalry)
+    }
+
+    // The pairs of players who have faced each other most, as (games, first, second)
+    pub fn most_played_rivalries(&self, count: usize) -> Vec<(usize, String, String)> {
+        let mut pairs: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        for game in &self.games {
+            for red in &game.red {
+                for blue in &game.blue {
+                    let pair = if red < blue { (red.as_str(), blue.as_str()) } else { (blue.as_str(), red.as_str()) };
+                    *pairs.entry(pair).or_default() += 1;
+                }
+            }
+        }
+
+        let mut rivalries: Vec<(usize, String, String)> = pairs.into_iter()
+            .map(|((first, second), games)| (games, first.to_string(), second.to_string()))
+            .collect();
+        rivalries.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| (&a.1, &a.2).cmp(&(&b.1, &b.2))));
+        rivalries.truncate(count);
+        rivalries
+    }
+
+    pub fn write_rivalry(file: &mut File, rivalry: &Rivalry) {
+        writeln!(file, "## {} vs {}\n", rivalry.first, rivalry.second).unwrap();
+        for (title, shared, results) in [
+            ("As opponents", &rivalry.as_opponents, format!("{} record", rivalry.first)),
+            ("As teammates", &rivalry.as_teammates, "Record together".to_string()),
+        ] {
+            writeln!(file, "### {} ({} games)", title, shared.games).unwrap();
+            if shared.games == 0 {
+                writeln!(file, "No games found.\n").unwrap();
+                continue;
+            }
+            writeln!(file, "  {}: {}-{}-{}", results, shared.wins, shared.losses, shared.ties).unwrap();
+            writeln!(file, "  Cap differential: {:+} ({:+.2} per game)", shared.cap_diff, shared.cap_diff as f64 / shared.games as f64).unwrap();
+            for (player, totals) in [(&rivalry.first, &shared.first_totals), (&rivalry.second, &shared.second_totals)] {
+                writeln!(file, "  {} per game: {:.2} caps, {:.2} returns, {:.1}s hold, {:.1}s prevent, {:.2} pops, {:.2} powerups",
+                    player, shared.per_game(totals.caps), shared.per_game(totals.returns), shared.per_game(totals.hold) / 60.0,
+                    shared.per_game(totals.prevent) / 60.0, shared.per_game(totals.ndps), shared.per_game(totals.pups)).unwrap();
+            }
+            writeln!(file).unwrap();
+        }
+    }
+
+    // One player pair, written to `output_path`. Returns false if they never shared a game.
+    pub fn generate_query_report(&self, first: &str, second: &str, output_path: &str) -> bool {
+        let Some(rivalry) = self.query(first, second) else {
+            return false;
+        };
+        let mut file = File::create(output_path).expect("Could not create output file");
+        writeln!(file, "=== RANKED TAGPRO HEAD TO HEAD ===\n").unwrap();
+        Self::write_rivalry(&mut file, &rivalry);
+        true
+    }
+
+    // The most-played rivalries, each in full
+    pub fn generate_report(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        writeln!(file, "=== RANKED TAGPRO RIVALRIES ===\n").unwrap();
+
+        let rivalries = self.most_played_rivalries(RIVALRIES_IN_REPORT);
+        if rivalries.is_empty() {
+            writeln!(file, "No records found.\n").unwrap();
+            return;
+        }
+        writeln!(file, "### Most Games as Opponents").unwrap();
+        for (rank, (games, first, second)) in rivalries.iter().enumerate() {
+            writeln!(file, "  {}. {} vs {} - {} games", rank + 1, first, second, games).unwrap();
+        }
+        writeln!(file).unwrap();
+
+        for (_, first, second) in &rivalries {
+            if let Some(rivalry) = self.query(first, second) {
+                Self::write_rivalry(&mut file, &rivalry);
+            }
+        }
+    }
+}
+
+pub fn load_head_to_head(match_iterator: MatchIterator) -> HeadToHeadIndex {
+    let mut index = HeadToHeadIndex::new();
+    if let Some(aliases) = AliasRegistry::load(DEFAULT_ALIAS_PATH) {
+        index = index.with_aliases(aliases);
+    }
+    index.add_matches(match_iterator);
+    index
+}
@@ -0,0 +1,389 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+
+// Which end of a board holds the records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Order {
+    Highest,
+    Lowest,
+}
+
+// How many entries a board reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Cutoff {
+    // Whole tiers of tied values, for as long as the rank is still within N
+    Ties(usize),
+    // Exactly N entries, with ties going to whoever was inserted first
+    Exact(usize),
+}
+
+impl Cutoff {
+    fn size(self) -> usize {
+        match self {
+            Cutoff::Ties(n) | Cutoff::Exact(n) => n,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameResult {
+    Win,
+    Loss,
+}
+
+impl GameResult {
+    pub fn from_win(is_win: bool) -> Self {
+        if is_win { GameResult::Win } else { GameResult::Loss }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GameResult::Win => "Win",
+            GameResult::Loss => "Loss",
+        }
+    }
+}
+
+pub trait LeaderboardEntry {
+    fn match_id(&self) -> &str;
+    fn players(&self) -> &[String];
+}
+
+// The match a record was set in and who set it: one player, a team, or
+// nobody for whole-game records
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordEntry {
+    pub match_id: String,
+    pub players: Vec<String>,
+}
+
+impl RecordEntry {
+    pub fn game(match_id: &str) -> Self {
+        Self { match_id: match_id.to_string(), players: Vec::new() }
+    }
+
+    pub fn player(match_id: &str, player_name: &str) -> Self {
+        Self { match_id: match_id.to_string(), players: vec![player_name.to_string()] }
+    }
+
+    pub fn team(match_id: &str, players: Vec<String>) -> Self {
+        Self { match_id: match_id.to_string(), players }
+    }
+}
+
+impl LeaderboardEntry for RecordEntry {
+    fn match_id(&self) -> &str {
+        &self.match_id
+    }
+
+    fn players(&self) -> &[String] {
+        &self.players
+    }
+}
+
+// One row of a finished board
+#[derive(Debug, Clone)]
+pub struct Ranked<'a, K, E> {
+    pub value: K,
+    pub entry: &'a E,
+    pub result: Option<GameResult>,
+}
+
+impl<K, E: LeaderboardEntry> Ranked<'_, K, E> {
+    pub fn players(&self) -> String {
+        self.entry.players().join(", ")
+    }
+
+    pub fn result_label(&self) -> &'static str {
+        self.result.map_or("", GameResult::label)
+    }
+}
+
+// Values mapped to the entries that reached them. Only entries that could
+// still make the board are kept: once a value falls below the cutoff, new
+// inserts can only push it further down, so it's dropped for good.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "K: Ord + Deserialize<'de>, E: Deserialize<'de>"))]
+pub struct Leaderboard<K, E> {
+    order: Order,
+    cutoff: Cutoff,
+    skip_zero: bool,
+    entries: BTreeMap<K, Vec<E>>,
+    len: usize,
+}
+
+impl<K: Ord + Copy + Default, E> Leaderboard<K, E> {
+    pub fn new(order: Order, cutoff: Cutoff) -> Self {
+        Self { order, cutoff, skip_zero: false, entries: BTreeMap::new(), len: 0 }
+    }
+
+    // Leave zeroes off the board
+    pub fn skipping_zero(mut self) -> Self {
+        self.skip_zero = true;
+        self
+    }
+
+    pub fn order(&self) -> Order {
+        self.order
+    }
+
+    // Entries currently held, including any tied at the cutoff
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: K, entry: E) {
+        if self.skip_zero && value == K::default() {
+            return;
+        }
+        self.entries.entry(value).or_default().push(entry);
+        self.len += 1;
+        self.prune();
+    }
+
+    // Drop the worst values for as long as everything better still fills the board
+    fn prune(&mut self) {
+        let n = self.cutoff.size();
+        loop {
+            let worst = match self.order {
+                Order::Highest => self.entries.first_entry(),
+                Order::Lowest => self.entries.last_entry(),
+            };
+            let Some(mut worst) = worst else {
+                break;
+            };
+            let worst_len = worst.get().len();
+            if self.len - worst_len >= n {
+                worst.remove();
+                self.len -= worst_len;
+                continue;
+            }
+            // Exact boards also drop the latest arrivals in a tie at the cutoff
+            if let Cutoff::Exact(n) = self.cutoff && self.len > n {
+                worst.get_mut().truncate(worst_len - (self.len - n));
+                self.len = n;
+            }
+            break;
+        }
+    }
+
+    pub fn top(&self) -> Vec<Ranked<'_, K, E>> {
+        self.top_with_result(None)
+    }
+
+    fn top_with_result(&self, result: Option<GameResult>) -> Vec<Ranked<'_, K, E>> {
+        let tiers: Box<dyn Iterator<Item = (&K, &Vec<E>)>> = match self.order {
+            Order::Highest => Box::new(self.entries.iter().rev()),
+            Order::Lowest => Box::new(self.entries.iter()),
+        };
+
+        let mut results = Vec::new();
+        for (&value, entries) in tiers {
+            match self.cutoff {
+                Cutoff::Ties(n) => {
+                    // The next rank is one past everyone already on the board
+                    if results.len() >= n {
+                        break;
+                    }
+                    results.extend(entries.iter().map(|entry| Ranked { value, entry, result }));
+                }
+                Cutoff::Exact(n) => {
+                    for entry in entries {
+                        if results.len() >= n {
+                            return results;
+                        }
+                        results.push(Ranked { value, entry, result });
+                    }
+                }
+            }
+        }
+        results
+    }
+}
+
+// Separate boards for wins and losses, so a record can be reported in either
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "K: Ord + Deserialize<'de>, E: Deserialize<'de>"))]
+pub struct SplitLeaderboard<K, E> {
+    wins: Leaderboard<K, E>,
+    losses: Leaderboard<K, E>,
+}
+
+impl<K: Ord + Copy + Default, E: LeaderboardEntry> SplitLeaderboard<K, E> {
+    pub fn new(order: Order, cutoff: Cutoff) -> Self {
+        Self { wins: Leaderboard::new(order, cutoff), losses: Leaderboard::new(order, cutoff) }
+    }
+
+    pub fn skipping_zero(self) -> Self {
+        Self { wins: self.wins.skipping_zero(), losses: self.losses.skipping_zero() }
+    }
+
+    pub fn insert(&mut self, value: K, entry: E, is_win: bool) {
+        if is_win {
+            self.wins.insert(value, entry);
+        } else {
+            self.losses.insert(value, entry);
+        }
+    }
+
+    pub fn wins(&self) -> Vec<Ranked<'_, K, E>> {
+        self.wins.top_with_result(Some(GameResult::Win))
+    }
+
+    pub fn losses(&self) -> Vec<Ranked<'_, K, E>> {
+        self.losses.top_with_result(Some(GameResult::Loss))
+    }
+
+    // Top of the wins board and top of the losses board, merged by value and
+    // then by the first player's name
+    pub fn merged(&self) -> Vec<Ranked<'_, K, E>> {
+        let mut results = self.wins();
+        results.extend(self.losses());
+        let order = self.wins.order();
+        results.sort_by(|a, b| {
+            let by_value = match order {
+                Order::Highest => b.value.cmp(&a.value),
+                Order::Lowest => a.value.cmp(&b.value),
+            };
+            by_value.then_with(|| a.entry.players().first().cmp(&b.entry.players().first()))
+        });
+        results
+    }
+}
+
+// Write a board under `heading`, one "Match <id>: <line>" row per entry
+pub fn write_leaderboard<K, E: LeaderboardEntry>(
+    file: &mut File,
+    heading: Option<&str>,
+    rows: &[Ranked<'_, K, E>],
+    line: impl Fn(&Ranked<'_, K, E>) -> String,
+) {
+    if let Some(heading) = heading {
+        writeln!(file, "{}", heading).unwrap();
+    }
+
+    if rows.is_empty() {
+        writeln!(file, "No records found.\n").unwrap();
+        return;
+    }
+
+    for row in rows {
+        writeln!(file, "  Match {}: {}", row.entry.match_id(), line(row)).unwrap();
+    }
+    writeln!(file).unwrap();
+}
+
+// One row of a finished board in a form the website and bots can consume.
+// `value` is in the units shown in the report (seconds, counts, or per-minute rates).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordRow {
+    pub stat: String,
+    pub scope: String,
+    pub rank: usize,
+    pub match_id: String,
+    pub players: Vec<String>,
+    pub value: f64,
+    pub result: Option<GameResult>,
+}
+
+// Flatten a board into rows. Tied values share a rank and the next value
+// skips past them (1, 2, 2, 4).
+pub fn record_rows<K: PartialEq + Copy, E: LeaderboardEntry>(
+    stat: &str,
+    scope: &str,
+    rows: &[Ranked<'_, K, E>],
+    value: impl Fn(K) -> f64,
+) -> Vec<RecordRow> {
+    let mut records = Vec::new();
+    let mut rank = 0;
+    for (index, row) in rows.iter().enumerate() {
+        if index == 0 || rows[index - 1].value != row.value {
+            rank = index + 1;
+        }
+        records.push(RecordRow {
+            stat: stat.to_string(),
+            scope: scope.to_string(),
+            rank,
+            match_id: row.entry.match_id().to_string(),
+            players: row.entry.players().to_vec(),
+            value: value(row.value),
+            result: row.result,
+        });
+    }
+    records
+}
+
+pub fn write_records_json(rows: &[RecordRow], output_path: &str) {
+    let output_file = File::create(output_path).expect("Could not create output file");
+    serde_json::to_writer_pretty(output_file, rows).expect("Could not write records to file");
+}
+
+// Quote a CSV field if it contains a delimiter, quote, or newline
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Players within a row are separated by semicolons
+pub fn write_records_csv(rows: &[RecordRow], output_path: &str) {
+    let mut file = File::create(output_path).expect("Could not create output file");
+    writeln!(file, "stat,scope,rank,match_id,players,value,result").unwrap();
+    for row in rows {
+        writeln!(file, "{},{},{},{},{},{},{}",
+            csv_field(&row.stat),
+            csv_field(&row.scope),
+            row.rank,
+            csv_field(&row.match_id),
+            csv_field(&row.players.join("; ")),
+            row.value,
+            row.result.map_or("", GameResult::label),
+        ).unwrap();
+    }
+}
+
+// Escape characters that would break a Markdown table cell
+pub(crate) fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+// A forum-ready page: one section per scope, one table per stat, in row order
+pub fn write_records_markdown(rows: &[RecordRow], title: &str, output_path: &str) {
+    let mut file = File::create(output_path).expect("Could not create output file");
+    writeln!(file, "# {}", title).unwrap();
+
+    let mut current: Option<(&str, &str)> = None;
+    for row in rows {
+        if current.map(|(scope, _)| scope) != Some(row.scope.as_str()) {
+            writeln!(file, "\n## {}", markdown_cell(&row.scope)).unwrap();
+        }
+        if current != Some((row.scope.as_str(), row.stat.as_str())) {
+            writeln!(file, "\n### {}\n", markdown_cell(&row.stat)).unwrap();
+            writeln!(file, "| Rank | Match | Players | Value | Result |").unwrap();
+            writeln!(file, "|---:|---|---|---:|---|").unwrap();
+            current = Some((row.scope.as_str(), row.stat.as_str()));
+        }
+        writeln!(file, "| {} | {} | {} | {} | {} |",
+            row.rank,
+            markdown_cell(&row.match_id),
+            markdown_cell(&row.players.join(", ")),
+            row.value,
+            row.result.map_or("", GameResult::label),
+        ).unwrap();
+    }
+}
+
+// Write `<base_path>.json`, `<base_path>.csv`, and `<base_path>.md`
+pub fn write_record_outputs(rows: &[RecordRow], title: &str, base_path: &str) {
+    write_records_json(rows, &format!("{}.json", base_path));
+    write_records_csv(rows, &format!("{}.csv", base_path));
+    write_records_markdown(rows, title, &format!("{}.md", base_path));
+}
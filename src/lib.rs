@@ -9,4 +9,24 @@ pub mod ranked_ratings;
 pub mod analysis_types;
 pub mod event_processor;
 pub mod ranked_analysis;
-pub mod records;
\ No newline at end of file
+pub mod leaderboard;
+pub mod records;
+pub mod career_records;
+pub mod record_diff;
+pub mod participation;
+pub mod stints;
+pub mod plus_minus;
+pub mod chemistry;
+pub mod head_to_head;
+pub mod profiles;
+pub mod elo;
+pub mod rating_systems;
+pub mod map_adjustments;
+pub mod match_quality;
+pub mod matchmaker;
+pub mod aliases;
+pub mod seasons;
+pub mod maps;
+pub mod streaks;
+pub mod win_probability;
+
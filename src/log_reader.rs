@@ -53,21 +53,37 @@ const DEFAULT_START_INDEX: usize = 394;
 const DEFAULT_END_INDEX: usize = 403;
 
 impl MatchIterator {
+    // Matches from bulk files [start_index, end_index), loaded one file at a time
     pub fn new(start_index: usize, end_index: usize) -> MatchIterator {
-        let mut log_file_index = start_index;
-        let mut log_file_option = None;
-        while log_file_index < DEFAULT_END_INDEX && log_file_option.is_none() {
-            log_file_option = deserialize_log_file(
-                format!("data/matches{}.json", log_file_index));
-            log_file_index += 1;
-        }
-
         MatchIterator {
-            log_file_index,
-            log_file_iterator: log_file_option.unwrap(),
+            log_file_index: start_index,
+            log_file_iterator: BTreeMap::new().into_iter(),
             end_index
         }
     }
+
+    // Index of the bulk file the last match came from
+    pub fn file_index(&self) -> usize {
+        self.log_file_index - 1
+    }
+}
+
+// The bulk files to process: [start, end) from a `--files <start> <end>`
+// argument, or the given defaults when it isn't passed
+pub fn file_range_from_args(args: &[String], start_index: usize, end_index: usize) -> Result<(usize, usize), String> {
+    let Some(flag) = args.iter().position(|arg| arg == "--files") else {
+        return Ok((start_index, end_index));
+    };
+    let index = |offset: usize| -> Result<usize, String> {
+        args.get(flag + offset)
+            .and_then(|arg| arg.parse().ok())
+            .ok_or_else(|| "--files needs a start and end match file index".to_string())
+    };
+    let (start, end) = (index(1)?, index(2)?);
+    if start >= end {
+        return Err(format!("--files {} {} is an empty range", start, end));
+    }
+    Ok((start, end))
 }
 
 impl Default for MatchIterator {
@@ -103,4 +119,25 @@ fn deserialize_log_file(filepath: String) -> Option<IntoIter<String, MatchLog>>
     let match_logs: BTreeMap<String, MatchLog> = serde_json::from_str(&s).expect("Could not parse matches file");
     println!("{}", filepath);
     Some(match_logs.into_iter())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn file_range_defaults_unless_passed() {
+        assert_eq!(file_range_from_args(&args("collect"), 394, 414), Ok((394, 414)));
+        assert_eq!(file_range_from_args(&args("collect --files 403 417"), 394, 414), Ok((403, 417)));
+        assert!(file_range_from_args(&args("collect --files 403"), 394, 414).is_err());
+        assert!(file_range_from_args(&args("collect --files 417 403"), 394, 414).is_err());
+    }
+
+    #[test]
+    fn empty_file_range_has_no_matches() {
+        assert!(MatchIterator::new(417, 417).next().is_none());
+    }
+}
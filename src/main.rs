@@ -1,9 +1,25 @@
 #[allow(unused_imports)]
 use num_traits::FromPrimitive;
-use ranked_stats::log_reader::MatchIterator;
+use ranked_stats::log_reader::{file_range_from_args, MatchIterator};
 use ranked_stats::ranked_ratings::get_ranked_matchups;
+use ranked_stats::win_probability::WinProbabilityModel;
+use std::sync::Arc;
 
+const START_INDEX: usize = 394;
+const END_INDEX: usize = 417;
+
+// Usage: ranked-stats [--files <start> <end>] [--wpa-model <model.json>]
 fn main() {
-    let match_iterator = MatchIterator::new(394, 417);
-    get_ranked_matchups(match_iterator);
+    let args: Vec<String> = std::env::args().collect();
+    let (start_index, end_index) = file_range_from_args(&args, START_INDEX, END_INDEX).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+    let win_probability = WinProbabilityModel::from_args(&args, start_index, end_index).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+
+    let match_iterator = MatchIterator::new(start_index, end_index);
+    get_ranked_matchups(match_iterator, win_probability.map(Arc::new));
 }
@@ -0,0 +1,178 @@
+use crate::analysis_types::MatchFormat;
+use crate::elo::diff_mapping;
+use crate::log_reader::MatchIterator;
+use crate::maps::{MapRegistry, DEFAULT_MAPS_PATH};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+
+pub const DEFAULT_MAP_ADJUSTMENTS_PATH: &str = "analysis/map_adjustments.json";
+
+// Games' worth of weight the all-maps average gets when estimating each map,
+// so maps with few games stay close to it. The all-maps average is shrunk the
+// same way toward the notebook's flat red advantage.
+const PRIOR_GAMES: f64 = 50.0;
+const PRIOR_RED_ADVANTAGE: f64 = 0.1;
+
+// How one map shifts the expected outcome of a game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapAdjustment {
+    pub name: String,
+    pub games: usize,
+    // Red's expected score between even teams, on the Elo model's cap margin
+    // scale (the mean mapped margin, not the mean raw cap difference)
+    pub red_advantage: f64,
+    // Caps per game relative to the all-maps average. Skill gaps produce
+    // proportionally bigger margins on high-scoring maps.
+    pub scoring_factor: f64,
+    pub caps_per_game: f64,
+    pub red_win_rate: f64,
+}
+
+// Per-map side advantage and scoring factor, fitted from every ranked game's
+// final score. Maps without an entry use the all-maps values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapAdjustments {
+    pub default_red_advantage: f64,
+    pub maps: BTreeMap<usize, MapAdjustment>,
+}
+
+// Running totals for one map while fitting
+#[derive(Default)]
+struct MapTotals {
+    games: usize,
+    red_caps: usize,
+    blue_caps: usize,
+    red_wins: usize,
+    // Sum of each game's red margin on the Elo expected-score scale
+    red_margin: f64,
+}
+
+// Map totals built up one game at a time. Ratings fed games in order can
+// refit from this after each one, so a game's own result never shifts the
+// expectation it's rated against.
+#[derive(Default)]
+pub struct MapAdjustmentFit {
+    totals: BTreeMap<usize, MapTotals>,
+}
+
+impl MapAdjustmentFit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_game(&mut self, map_id: usize, red_caps: usize, blue_caps: usize) {
+        let map = self.totals.entry(map_id).or_default();
+        map.games += 1;
+        map.red_caps += red_caps;
+        map.blue_caps += blue_caps;
+        map.red_wins += (red_caps > blue_caps) as usize;
+        map.red_margin += diff_mapping(red_caps as isize - blue_caps as isize);
+    }
+
+    pub fn adjustments(&self, maps: &MapRegistry) -> MapAdjustments {
+        MapAdjustments::from_totals(&self.totals, maps)
+    }
+}
+
+impl MapAdjustments {
+    // No map effects: every map gets `red_advantage` and a scoring factor of 1
+    pub fn uniform(red_advantage: f64) -> Self {
+        Self { default_red_advantage: red_advantage, maps: BTreeMap::new() }
+    }
+
+    pub fn fit(match_iterator: MatchIterator, format: &MatchFormat, maps: &MapRegistry) -> Self {
+        let mut fit = MapAdjustmentFit::new();
+        for (_match_id, match_log) in match_iterator {
+            if !format.accepts(&match_log) {
+                continue;
+            }
+            fit.add_game(match_log.map_id, match_log.teams[0].score, match_log.teams[1].score);
+        }
+        fit.adjustments(maps)
+    }
+
+    fn from_totals(totals: &BTreeMap<usize, MapTotals>, maps: &MapRegistry) -> Self {
+        let shrink = |total: f64, map_games: usize, average: f64| (total + PRIOR_GAMES * average) / (map_games as f64 + PRIOR_GAMES);
+        let total_games = totals.values().map(|map| map.games).sum::<usize>();
+        let games = total_games.max(1) as f64;
+        let red_caps = totals.values().map(|map| map.red_caps).sum::<usize>() as f64;
+        let blue_caps = totals.values().map(|map| map.blue_caps).sum::<usize>() as f64;
+        let red_advantage = shrink(totals.values().map(|map| map.red_margin).sum::<f64>(), total_games, PRIOR_RED_ADVANTAGE);
+        let caps_per_game = (red_caps + blue_caps) / games;
+
+        Self {
+            default_red_advantage: red_advantage,
+            maps: totals.iter()
+                .map(|(&map_id, map)| {
+                    let map_caps_per_game = shrink((map.red_caps + map.blue_caps) as f64, map.games, caps_per_game);
+                    (map_id, MapAdjustment {
+                        name: maps.name(map_id),
+                        games: map.games,
+                        red_advantage: shrink(map.red_margin, map.games, red_advantage),
+                        scoring_factor: if caps_per_game > 0.0 { map_caps_per_game / caps_per_game } else { 1.0 },
+                        caps_per_game: (map.red_caps + map.blue_caps) as f64 / map.games as f64,
+                        red_win_rate: 100.0 * map.red_wins as f64 / map.games as f64,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn red_advantage(&self, map_id: usize) -> f64 {
+        self.maps.get(&map_id).map_or(self.default_red_advantage, |map| map.red_advantage)
+    }
+
+    pub fn scoring_factor(&self, map_id: usize) -> f64 {
+        self.maps.get(&map_id).map_or(1.0, |map| map.scoring_factor)
+    }
+
+    // Red's expected cap differential on a map, given red's rating edge
+    pub fn expected_score(&self, map_id: usize, rating_difference: f64) -> f64 {
+        rating_difference * self.scoring_factor(map_id) + self.red_advantage(map_id)
+    }
+
+    pub fn save(&self, output_path: &str) {
+        let output_file = File::create(output_path).expect("Could not create output file.");
+        serde_json::to_writer_pretty(output_file, self).expect("Could not write map adjustments to file.");
+    }
+
+    pub fn load(input_path: &str) -> Option<Self> {
+        let input_file = File::open(input_path).ok()?;
+        serde_json::from_reader(input_file).ok()
+    }
+
+    pub fn write_csv(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        writeln!(file, "map_id,map,games,red_advantage,scoring_factor,caps_per_game,red_win_rate").unwrap();
+        for (map_id, map) in &self.maps {
+            writeln!(file, "{},\"{}\",{},{:.4},{:.4},{:.4},{:.2}", map_id, map.name.escape_default(), map.games,
+                map.red_advantage, map.scoring_factor, map.caps_per_game, map.red_win_rate).unwrap();
+        }
+    }
+
+    // Maps from most red-favoured to most blue-favoured
+    pub fn generate_report(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        writeln!(file, "=== RANKED TAGPRO MAP SIDE ADVANTAGE ===\n").unwrap();
+        writeln!(file, "All maps: red by {:+.3} expected score per game\n", self.default_red_advantage).unwrap();
+
+        let mut maps: Vec<&MapAdjustment> = self.maps.values().collect();
+        maps.sort_by(|a, b| b.red_advantage.total_cmp(&a.red_advantage).then_with(|| a.name.cmp(&b.name)));
+        for (rank, map) in maps.iter().enumerate() {
+            writeln!(file, "  {}. {} - red by {:+.3} ({} games, {:.1}% red wins, {:.2} caps per game, scoring x{:.2})",
+                rank + 1, map.name, map.red_advantage, map.games, map.red_win_rate, map.caps_per_game, map.scoring_factor).unwrap();
+        }
+        writeln!(file).unwrap();
+    }
+}
+
+pub fn fit_map_adjustments(match_iterator: MatchIterator) {
+    let maps = MapRegistry::load(DEFAULT_MAPS_PATH).unwrap_or_default();
+    let adjustments = MapAdjustments::fit(match_iterator, &MatchFormat::ranked(), &maps);
+    adjustments.save(DEFAULT_MAP_ADJUSTMENTS_PATH);
+    adjustments.write_csv("analysis/map_adjustments.csv");
+    adjustments.generate_report("analysis/map_adjustments.txt");
+    println!("Map adjustments fitted! Output written to analysis/map_adjustments.{{json,csv,txt}}");
+}
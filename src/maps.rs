@@ -0,0 +1,224 @@
+use crate::leaderboard::{record_rows, write_leaderboard, write_record_outputs, Cutoff, Leaderboard, Order, RecordEntry, RecordRow};
+use crate::log_reader::{MatchIterator, MatchLog};
+use crate::records::{is_record_match, PlayerGameStats, PlayerStatLines, RecordCollector};
+use crate::seasons::slug;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+
+pub const DEFAULT_MAPS_PATH: &str = "data/bulkmaps.json";
+
+const PERFORMANCES_PER_MAP: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapInfo {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub map_type: String,
+}
+
+// Map ids (as strings, the way bulkmaps.json keys them) mapped to their names and types
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MapRegistry {
+    maps: BTreeMap<String, MapInfo>,
+}
+
+impl MapRegistry {
+    pub fn load(input_path: &str) -> Option<Self> {
+        let input_file = File::open(input_path).ok()?;
+        serde_json::from_reader(input_file).ok()
+    }
+
+    pub fn get(&self, map_id: usize) -> Option<&MapInfo> {
+        self.maps.get(&map_id.to_string())
+    }
+
+    // The map's name, or its id for maps missing from the registry
+    pub fn name(&self, map_id: usize) -> String {
+        self.get(map_id).map_or_else(|| format!("Map {}", map_id), |map| map.name.clone())
+    }
+}
+
+// Run a fresh collector for every map over the same matches, ordered by map
+// name. Maps with no record matches get no collector, and so no reports.
+pub fn collect_by_map<C: RecordCollector>(
+    match_iterator: MatchIterator,
+    maps: &MapRegistry,
+    new_collector: impl Fn() -> C,
+) -> Vec<(String, C)> {
+    let mut collectors: BTreeMap<String, C> = BTreeMap::new();
+    for (match_id, match_log) in match_iterator {
+        if !is_record_match(&match_log) {
+            continue;
+        }
+        collectors.entry(maps.name(match_log.map_id))
+            .or_insert_with(&new_collector)
+            .process_match(match_id, &match_log);
+    }
+    collectors.into_iter().collect()
+}
+
+// Write each map's text report and structured outputs as
+// analysis/<report name>_<map slug>.*
+pub fn write_map_reports<C: RecordCollector>(collectors: &[(String, C)]) {
+    for (map_name, collector) in collectors {
+        let base_path = format!("analysis/{}_{}", C::REPORT_NAME, slug(map_name));
+        collector.generate_report(&format!("{}.txt", base_path));
+        write_record_outputs(&collector.record_rows(), &format!("{} - {}", C::TITLE, map_name), &base_path);
+    }
+}
+
+// Player stats each map keeps its best single-game performances for
+struct PerformanceDefinition {
+    title: &'static str,
+    value: fn(&PlayerGameStats) -> usize,
+}
+
+const PERFORMANCES: &[PerformanceDefinition] = &[
+    PerformanceDefinition { title: "Captures", value: |stats| stats.caps },
+    PerformanceDefinition { title: "Returns", value: |stats| stats.returns },
+    PerformanceDefinition { title: "Tags", value: |stats| stats.tags },
+    PerformanceDefinition { title: "Hold (seconds)", value: |stats| stats.hold / 60 },
+    PerformanceDefinition { title: "Prevent (seconds)", value: |stats| stats.prevent / 60 },
+];
+
+// Totals over every game on one map
+#[derive(Serialize, Deserialize)]
+struct MapSummary {
+    games: usize,
+    caps: usize,
+    red_wins: usize,
+    blue_wins: usize,
+    duration: usize,
+    overtime_games: usize,
+    // One board per entry in PERFORMANCES
+    performances: Vec<Leaderboard<usize, RecordEntry>>,
+}
+
+impl Default for MapSummary {
+    fn default() -> Self {
+        Self {
+            games: 0,
+            caps: 0,
+            red_wins: 0,
+            blue_wins: 0,
+            duration: 0,
+            overtime_games: 0,
+            performances: PERFORMANCES.iter()
+                .map(|_| Leaderboard::new(Order::Highest, Cutoff::Ties(PERFORMANCES_PER_MAP)).skipping_zero())
+                .collect(),
+        }
+    }
+}
+
+impl MapSummary {
+    fn percent(&self, count: usize) -> f64 {
+        100.0 * count as f64 / self.games as f64
+    }
+}
+
+// Cap rates, win rates by side, game length, overtime rate, and top
+// performances for every map
+#[derive(Serialize, Deserialize)]
+pub struct MapSummaryCollector {
+    ingested: BTreeSet<String>,
+    summaries: BTreeMap<String, MapSummary>,
+    #[serde(skip)]
+    maps: MapRegistry,
+}
+
+impl RecordCollector for MapSummaryCollector {
+    const REPORT_NAME: &'static str = "map_summary";
+    const TITLE: &'static str = "Ranked TagPro Map Summary";
+
+    fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        Self::process_match(self, match_id, match_log);
+    }
+
+    fn generate_report(&self, output_path: &str) {
+        Self::generate_report(self, output_path);
+    }
+
+    fn record_rows(&self) -> Vec<RecordRow> {
+        Self::record_rows(self)
+    }
+}
+
+impl MapSummaryCollector {
+    pub fn new(maps: MapRegistry) -> Self {
+        Self { ingested: BTreeSet::new(), summaries: BTreeMap::new(), maps }
+    }
+
+    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        if !self.ingested.insert(match_id.clone()) {
+            return;
+        }
+
+        // Filter matches
+        if !is_record_match(match_log) {
+            return;
+        }
+
+        let lines = PlayerStatLines::from_match_log(match_log);
+        let summary = self.summaries.entry(self.maps.name(match_log.map_id)).or_default();
+        summary.games += 1;
+        summary.caps += lines.full.iter().map(|stats| stats.caps).sum::<usize>();
+        summary.red_wins += (lines.cap_diff > 0) as usize;
+        summary.blue_wins += (lines.cap_diff < 0) as usize;
+        summary.duration += match_log.duration;
+        summary.overtime_games += (match_log.duration > (match_log.time_limit * 3600.0) as usize) as usize;
+
+        for (player_idx, player) in match_log.players.iter().enumerate() {
+            for (definition, board) in PERFORMANCES.iter().zip(summary.performances.iter_mut()) {
+                board.insert((definition.value)(&lines.full[player_idx]), RecordEntry::player(&match_id, &player.name));
+            }
+        }
+    }
+
+    pub fn generate_report(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+
+        writeln!(file, "=== RANKED TAGPRO MAP SUMMARY ===\n").unwrap();
+
+        for (map_name, summary) in &self.summaries {
+            let average_seconds = summary.duration / summary.games / 60;
+            writeln!(file, "## {} ({} games)\n", map_name, summary.games).unwrap();
+            writeln!(file, "Average caps per game: {:.2}", summary.caps as f64 / summary.games as f64).unwrap();
+            writeln!(file, "Red win rate: {:.1}%", summary.percent(summary.red_wins)).unwrap();
+            writeln!(file, "Blue win rate: {:.1}%", summary.percent(summary.blue_wins)).unwrap();
+            writeln!(file, "Average duration: {}:{:02}", average_seconds / 60, average_seconds % 60).unwrap();
+            writeln!(file, "Overtime rate: {:.1}%\n", summary.percent(summary.overtime_games)).unwrap();
+
+            for (definition, board) in PERFORMANCES.iter().zip(summary.performances.iter()) {
+                write_leaderboard(&mut file, Some(&format!("### {}", definition.title)), &board.top(), |row| {
+                    format!("{} - {}", row.players(), row.value)
+                });
+            }
+        }
+    }
+
+    // Each map's top performances as structured rows, with the map as the scope
+    pub fn record_rows(&self) -> Vec<RecordRow> {
+        let mut rows = Vec::new();
+        for (map_name, summary) in &self.summaries {
+            for (definition, board) in PERFORMANCES.iter().zip(summary.performances.iter()) {
+                rows.extend(record_rows(definition.title, map_name, &board.top(), |value| value as f64));
+            }
+        }
+        rows
+    }
+}
+
+pub fn collect_map_summary(match_iterator: MatchIterator) {
+    let mut collector = MapSummaryCollector::new(MapRegistry::load(DEFAULT_MAPS_PATH).unwrap_or_default());
+
+    for (match_id, match_log) in match_iterator {
+        collector.process_match(match_id, &match_log);
+    }
+
+    collector.generate_report("analysis/map_summary.txt");
+    write_record_outputs(&collector.record_rows(), MapSummaryCollector::TITLE, "analysis/map_summary");
+    println!("Map summary collected! Output written to analysis/map_summary.{{txt,json,csv,md}}");
+}
@@ -0,0 +1,339 @@
+use crate::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use crate::elo::EloRatings;
+use crate::event_processor::process_ranked_match;
+use crate::log_reader::{MatchIterator, MatchLog};
+use crate::maps::{MapRegistry, DEFAULT_MAPS_PATH};
+use crate::participation::RosterSpot;
+use crate::ranked_analysis::RankedStatConfig;
+use crate::rating_systems::{RatedMatch, RatingSystem, DEFAULT_BURN_IN};
+use crate::seasons::{load_seasons, Period, DEFAULT_SEASONS_PATH};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::File;
+use std::io::Write;
+
+// A cap margin this large is treated as a mercy-rule blowout
+const MERCY_RULE_MARGIN: usize = 5;
+// Matches where the favourite's win probability is at least this are lopsided
+pub const LOPSIDED_PROBABILITY: f64 = 0.75;
+// Players seen in ranked games starting within this many seconds make up the queue
+const QUEUE_WINDOW: usize = 3600;
+const QUEUE_BUCKET_SIZE: usize = 8;
+const PROBABILITY_BUCKETS: usize = 10;
+
+// One ranked match, graded on balance from the ratings going into it
+#[derive(Debug, Clone)]
+pub struct MatchQuality {
+    pub match_id: String,
+    pub timestamp: usize,
+    pub map_id: usize,
+    pub season: Option<String>,
+    pub red_win_probability: f64,
+    // Red's average rating minus blue's
+    pub rating_gap: f64,
+    // Highest rating minus lowest, within each team
+    pub red_spread: f64,
+    pub blue_spread: f64,
+    pub cap_diff: isize,
+    // Distinct players in ranked games during the queue window
+    pub queue_population: usize,
+}
+
+impl MatchQuality {
+    pub fn favourite_probability(&self) -> f64 {
+        self.red_win_probability.max(1.0 - self.red_win_probability)
+    }
+
+    pub fn is_lopsided(&self) -> bool {
+        self.favourite_probability() >= LOPSIDED_PROBABILITY
+    }
+
+    pub fn is_blowout(&self) -> bool {
+        self.cap_diff.unsigned_abs() >= MERCY_RULE_MARGIN
+    }
+
+    // The team given under even odds won
+    pub fn is_upset(&self) -> bool {
+        (self.red_win_probability > 0.5 && self.cap_diff < 0) || (self.red_win_probability < 0.5 && self.cap_diff > 0)
+    }
+
+    // UTC hour the match started in
+    pub fn hour(&self) -> usize {
+        self.timestamp % 86400 / 3600
+    }
+}
+
+// Totals for one group of graded matches
+#[derive(Debug, Clone, Default)]
+pub struct QualitySummary {
+    pub games: usize,
+    pub favourite_probability: f64,
+    pub rating_gap: f64,
+    pub team_spread: f64,
+    pub lopsided: usize,
+    pub blowouts: usize,
+    pub lopsided_blowouts: usize,
+    pub upsets: usize,
+}
+
+impl QualitySummary {
+    fn add(&mut self, game: &MatchQuality) {
+        self.games += 1;
+        self.favourite_probability += game.favourite_probability();
+        self.rating_gap += game.rating_gap.abs();
+        self.team_spread += (game.red_spread + game.blue_spread) / 2.0;
+        self.lopsided += game.is_lopsided() as usize;
+        self.blowouts += game.is_blowout() as usize;
+        self.lopsided_blowouts += (game.is_lopsided() && game.is_blowout()) as usize;
+        self.upsets += game.is_upset() as usize;
+    }
+
+    fn average(&self, total: f64) -> f64 {
+        if self.games == 0 { 0.0 } else { total / self.games as f64 }
+    }
+
+    fn percent(count: usize, games: usize) -> f64 {
+        if games == 0 { 0.0 } else { 100.0 * count as f64 / games as f64 }
+    }
+
+    fn write_row(&self, file: &mut File, label: &str) {
+        writeln!(file, "{:<16} {:>6} {:>8.1}% {:>7.2} {:>7.2} {:>9.1}% {:>9.1}% {:>11.1}% {:>7.1}%", label, self.games,
+            100.0 * self.average(self.favourite_probability), self.average(self.rating_gap), self.average(self.team_spread),
+            Self::percent(self.lopsided, self.games), Self::percent(self.blowouts, self.games),
+            Self::percent(self.lopsided_blowouts, self.lopsided), Self::percent(self.upsets, self.games)).unwrap();
+    }
+}
+
+// Grades every ranked match on how balanced it was going in, rating each
+// match only after grading it so the grade uses what the matchmaker could know
+pub struct MatchQualityCollector {
+    ingested: BTreeSet<String>,
+    ratings: EloRatings,
+    aliases: AliasRegistry,
+    maps: MapRegistry,
+    seasons: Vec<Period>,
+    burn_in: usize,
+    seen: usize,
+    // Start time and players of recent ranked games, oldest first
+    recent: VecDeque<(usize, Vec<String>)>,
+    matches: Vec<MatchQuality>,
+}
+
+impl Default for MatchQualityCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MatchQualityCollector {
+    pub fn new() -> Self {
+        Self {
+            ingested: BTreeSet::new(),
+            ratings: EloRatings::new(),
+            aliases: AliasRegistry::default(),
+            maps: MapRegistry::default(),
+            seasons: Vec::new(),
+            burn_in: DEFAULT_BURN_IN,
+            seen: 0,
+            recent: VecDeque::new(),
+            matches: Vec::new(),
+        }
+    }
+
+    // Combine alternate accounts under their main names
+    pub fn with_aliases(mut self, aliases: AliasRegistry) -> Self {
+        self.ratings = self.ratings.with_aliases(aliases.clone());
+        self.aliases = aliases;
+        self
+    }
+
+    // Only CTF maps are graded. Maps missing from the registry are graded too.
+    pub fn with_maps(mut self, maps: MapRegistry) -> Self {
+        self.ratings = self.ratings.with_maps(maps.clone());
+        self.maps = maps;
+        self
+    }
+
+    // Rate with per-map adjustments fitted from the matches graded so far
+    pub fn with_prior_map_adjustments(mut self) -> Self {
+        self.ratings = self.ratings.with_prior_map_adjustments();
+        self
+    }
+
+    // Group the season report by these periods. Without any, matches are grouped by month.
+    pub fn with_seasons(mut self, seasons: Vec<Period>) -> Self {
+        self.seasons = seasons;
+        self
+    }
+
+    pub fn with_burn_in(mut self, burn_in: usize) -> Self {
+        self.burn_in = burn_in;
+        self
+    }
+
+    pub fn matches(&self) -> &[MatchQuality] {
+        &self.matches
+    }
+
+    pub fn add_matches(&mut self, mut match_iterator: MatchIterator) {
+        while let Some((match_id, match_log)) = match_iterator.next() {
+            let file_index = match_iterator.file_index();
+            self.process_match(match_id, file_index, &match_log);
+        }
+    }
+
+    pub fn process_match(&mut self, match_id: String, file_index: usize, match_log: &MatchLog) {
+        if self.ingested.contains(&match_id) {
+            return;
+        }
+        let is_ctf = self.maps.get(match_log.map_id).is_none_or(|map| map.map_type == "ctf");
+        if !is_ctf {
+            return;
+        }
+        let Some((result, player_names)) = process_ranked_match::<RankedStatConfig>(match_id.clone(), match_log) else {
+            return;
+        };
+        self.ingested.insert(match_id);
+        let game = RatedMatch::new(result, &player_names, &self.aliases);
+
+        let queue_population = self.queue_population(match_log.date, &game.player_names);
+        if self.seen >= self.burn_in {
+            let season = self.season_of(file_index, match_log);
+            let quality = self.grade(&game, season, queue_population);
+            self.matches.push(quality);
+        }
+        self.seen += 1;
+        self.ratings.update(&game);
+    }
+
+    // Distinct players in this match and the ranked games that started shortly before it
+    fn queue_population(&mut self, date: usize, players: &[String]) -> usize {
+        while self.recent.front().is_some_and(|(start, _)| start + QUEUE_WINDOW < date) {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((date, players.to_vec()));
+        self.recent.iter().flat_map(|(_, players)| players.iter()).collect::<BTreeSet<&String>>().len()
+    }
+
+    fn season_of(&self, file_index: usize, match_log: &MatchLog) -> Option<String> {
+        if self.seasons.is_empty() {
+            return Some(Period::month_of(match_log.date).name);
+        }
+        self.seasons.iter().find(|season| season.contains(file_index, match_log)).map(|season| season.name.clone())
+    }
+
+    fn grade(&self, game: &RatedMatch, season: Option<String>, queue_population: usize) -> MatchQuality {
+        let team_ratings = |team: &[RosterSpot]| -> Vec<f64> { team.iter().map(|spot| self.ratings.current_rating(&spot.name)).collect() };
+        let (red, blue) = (team_ratings(&game.red), team_ratings(&game.blue));
+        // Substitutes count toward the average by their share of the match
+        let average = |team: &[RosterSpot], ratings: &[f64]| {
+            team.iter().zip(ratings).map(|(spot, rating)| spot.share * rating).sum::<f64>()
+                / team.iter().map(|spot| spot.share).sum::<f64>().max(f64::EPSILON)
+        };
+        let spread = |ratings: &[f64]| {
+            ratings.iter().copied().fold(f64::MIN, f64::max) - ratings.iter().copied().fold(f64::MAX, f64::min)
+        };
+
+        MatchQuality {
+            match_id: game.result.match_id.clone(),
+            timestamp: game.result.timestamp,
+            map_id: game.result.map_id,
+            season,
+            red_win_probability: self.ratings.predict(game),
+            rating_gap: average(&game.red, &red) - average(&game.blue, &blue),
+            red_spread: spread(&red),
+            blue_spread: spread(&blue),
+            cap_diff: game.result.cap_diff,
+            queue_population,
+        }
+    }
+
+    fn summarize<K: Ord>(&self, key: impl Fn(&MatchQuality) -> Option<K>) -> BTreeMap<K, QualitySummary> {
+        let mut summaries: BTreeMap<K, QualitySummary> = BTreeMap::new();
+        for game in &self.matches {
+            if let Some(key) = key(game) {
+                summaries.entry(key).or_default().add(game);
+            }
+        }
+        summaries
+    }
+
+    fn write_header(file: &mut File, group: &str) {
+        writeln!(file, "{:<16} {:>6} {:>9} {:>7} {:>7} {:>10} {:>10} {:>12} {:>8}",
+            group, "Games", "Fav Win P", "Gap", "Spread", "Lopsided", "Blowouts", "Lop->Blow", "Upsets").unwrap();
+    }
+
+    pub fn generate_report(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        writeln!(file, "=== RANKED TAGPRO MATCH QUALITY ===\n").unwrap();
+        writeln!(file, "(Graded from Elo ratings going into each match, after the first {} matches)", self.burn_in).unwrap();
+        writeln!(file, "(Lopsided: favourite at {:.0}% or more. Blowout: won by {} or more caps. Lop->Blow: share of lopsided matches that became blowouts)\n",
+            100.0 * LOPSIDED_PROBABILITY, MERCY_RULE_MARGIN).unwrap();
+
+        if self.matches.is_empty() {
+            writeln!(file, "No records found.\n").unwrap();
+            return;
+        }
+
+        writeln!(file, "### Overall").unwrap();
+        Self::write_header(&mut file, "");
+        self.summarize(|_| Some(())).values().for_each(|summary| summary.write_row(&mut file, "All matches"));
+        writeln!(file).unwrap();
+
+        writeln!(file, "### By Favourite Win Probability").unwrap();
+        Self::write_header(&mut file, "Probability");
+        let bucket_width = 50.0 / PROBABILITY_BUCKETS as f64;
+        for (bucket, summary) in self.summarize(|game| {
+            Some((((game.favourite_probability() - 0.5) * 100.0 / bucket_width) as usize).min(PROBABILITY_BUCKETS - 1))
+        }) {
+            let low = 50.0 + bucket as f64 * bucket_width;
+            summary.write_row(&mut file, &format!("{:.0}-{:.0}%", low, low + bucket_width));
+        }
+        writeln!(file).unwrap();
+
+        writeln!(file, "### By Hour (UTC)").unwrap();
+        Self::write_header(&mut file, "Hour");
+        for (hour, summary) in self.summarize(|game| Some(game.hour())) {
+            summary.write_row(&mut file, &format!("{:02}:00", hour));
+        }
+        writeln!(file).unwrap();
+
+        writeln!(file, "### By Season").unwrap();
+        Self::write_header(&mut file, "Season");
+        for (season, summary) in self.summarize(|game| game.season.clone()) {
+            summary.write_row(&mut file, &season);
+        }
+        writeln!(file).unwrap();
+
+        writeln!(file, "### By Queue Population").unwrap();
+        Self::write_header(&mut file, "Players in queue");
+        for (bucket, summary) in self.summarize(|game| Some(game.queue_population / QUEUE_BUCKET_SIZE)) {
+            let low = bucket * QUEUE_BUCKET_SIZE;
+            summary.write_row(&mut file, &format!("{}-{}", low, low + QUEUE_BUCKET_SIZE - 1));
+        }
+        writeln!(file).unwrap();
+    }
+
+    pub fn write_csv(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        writeln!(file, "match_id,timestamp,map_id,season,red_win_probability,rating_gap,red_spread,blue_spread,cap_diff,queue_population,lopsided,blowout,upset").unwrap();
+        for game in &self.matches {
+            writeln!(file, "{},{},{},\"{}\",{:.4},{:.4},{:.4},{:.4},{},{},{},{},{}", game.match_id, game.timestamp, game.map_id,
+                game.season.as_deref().unwrap_or("").escape_default(), game.red_win_probability, game.rating_gap, game.red_spread,
+                game.blue_spread, game.cap_diff, game.queue_population, game.is_lopsided(), game.is_blowout(), game.is_upset()).unwrap();
+        }
+    }
+}
+
+pub fn collect_match_quality(match_iterator: MatchIterator) {
+    let mut collector = MatchQualityCollector::new()
+        .with_aliases(AliasRegistry::load(DEFAULT_ALIAS_PATH).unwrap_or_default())
+        .with_maps(MapRegistry::load(DEFAULT_MAPS_PATH).unwrap_or_default())
+        .with_prior_map_adjustments();
+    if let Some(seasons) = load_seasons(DEFAULT_SEASONS_PATH) {
+        collector = collector.with_seasons(seasons);
+    }
+    collector.add_matches(match_iterator);
+    collector.generate_report("analysis/match_quality.txt");
+    collector.write_csv("analysis/match_quality.csv");
+    println!("Match quality graded! Output written to analysis/match_quality.{{txt,csv}}");
+}
@@ -0,0 +1,440 @@
+use crate::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use crate::elo::{win_probability, EloRatings};
+use crate::log_reader::MatchIterator;
+use crate::maps::{MapRegistry, DEFAULT_MAPS_PATH};
+use crate::match_quality::LOPSIDED_PROBABILITY;
+use crate::participation::RosterSpot;
+use crate::rating_systems::{load_rated_matches, RatedMatch, RatingSystem};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+
+const TEAM_SIZE: usize = 4;
+const MATCH_SIZE: usize = 2 * TEAM_SIZE;
+const DEFAULT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+const DEFAULT_SIMULATED_DAYS: usize = 7;
+// Seconds a simulated match keeps its players out of the queue
+const MATCH_LENGTH: usize = 8 * 60;
+// Tries at drawing an arriving player who isn't already queued or playing
+const ARRIVAL_DRAWS: usize = 10;
+// Players the gap-limited matchmaker considers at once, oldest first
+const GAP_LIMIT_WINDOW: usize = 12;
+
+// Small deterministic generator so simulations are repeatable without extra dependencies
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Index into `cumulative`, a running total of weights, chosen in proportion to each weight
+    fn weighted(&mut self, cumulative: &[f64]) -> usize {
+        let target = self.next_f64() * cumulative.last().copied().unwrap_or(0.0);
+        cumulative.partition_point(|&total| total <= target).min(cumulative.len().saturating_sub(1))
+    }
+}
+
+// How players join the queue: a rate for each UTC hour, measured from when
+// historical ranked matches started
+#[derive(Debug, Clone)]
+pub struct ArrivalModel {
+    // Players per second
+    pub hourly_rate: [f64; 24],
+}
+
+impl ArrivalModel {
+    pub fn fit(matches: &[RatedMatch]) -> Self {
+        let mut players_by_hour = [0usize; 24];
+        for game in matches {
+            players_by_hour[game.result.timestamp % 86400 / 3600] += game.red.len() + game.blue.len();
+        }
+        let first = matches.iter().map(|game| game.result.timestamp).min().unwrap_or(0);
+        let last = matches.iter().map(|game| game.result.timestamp).max().unwrap_or(0);
+        let days = ((last - first) as f64 / 86400.0).max(1.0);
+        Self { hourly_rate: players_by_hour.map(|players| players as f64 / (days * 3600.0)) }
+    }
+
+    // Time of the next arrival after `now`, or None if no hour of the day has any
+    fn next_arrival(&self, now: usize, rng: &mut XorShift) -> Option<usize> {
+        if self.hourly_rate.iter().all(|&rate| rate <= 0.0) {
+            return None;
+        }
+        let mut time = now;
+        loop {
+            let rate = self.hourly_rate[time % 86400 / 3600];
+            let hour_end = (time / 3600 + 1) * 3600;
+            if rate > 0.0 {
+                let gap = -(1.0 - rng.next_f64()).ln() / rate;
+                if time as f64 + gap < hour_end as f64 {
+                    return Some(time + gap.ceil() as usize);
+                }
+            }
+            time = hour_end;
+        }
+    }
+}
+
+// How the matchmaker turns the queue into teams
+#[derive(Debug, Clone, Copy)]
+pub enum BalanceStrategy {
+    // First eight in the queue, sorted by rating and drafted ABBAABBA
+    SnakeDraft,
+    // First eight in the queue, split into the most even of the 35 possible teams
+    Exhaustive,
+    // Search the oldest players for the most even match, waiting for more
+    // players while the best split's rating gap is above `max_gap` caps,
+    // unless the longest-waiting player has waited `max_wait` seconds
+    RatingGapLimit { max_gap: f64, max_wait: usize },
+}
+
+impl BalanceStrategy {
+    pub fn name(&self) -> String {
+        match self {
+            BalanceStrategy::SnakeDraft => "Snake draft".to_string(),
+            BalanceStrategy::Exhaustive => "Exhaustive".to_string(),
+            BalanceStrategy::RatingGapLimit { max_gap, max_wait } => format!("Gap <= {:.2} ({}s max)", max_gap, max_wait),
+        }
+    }
+}
+
+// The most even split of eight ratings, as (red slot mask, red sum minus blue sum).
+// Slot 0 is always on red, so each split is tried once.
+fn best_split(ratings: &[f64]) -> (u32, f64) {
+    let total: f64 = ratings.iter().sum();
+    (0u32..1 << MATCH_SIZE)
+        .filter(|mask| mask & 1 == 1 && mask.count_ones() as usize == TEAM_SIZE)
+        .map(|mask| {
+            let red: f64 = (0..MATCH_SIZE).filter(|slot| mask & (1 << slot) != 0).map(|slot| ratings[slot]).sum();
+            (mask, 2.0 * red - total)
+        })
+        .min_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+        .unwrap()
+}
+
+fn snake_split(ratings: &[f64]) -> u32 {
+    let mut order: Vec<usize> = (0..MATCH_SIZE).collect();
+    order.sort_by(|&a, &b| ratings[b].total_cmp(&ratings[a]));
+    [0, 3, 4, 7].iter().fold(0, |mask, &pick| mask | 1 << order[pick])
+}
+
+// When the longest-waiting player hits the wait limit, if the queue is full
+// enough that a match will then be forced. The queue is in joining order.
+fn wait_deadline(queue: &[QueuedPlayer], strategy: BalanceStrategy) -> Option<usize> {
+    match strategy {
+        BalanceStrategy::RatingGapLimit { max_wait, .. } if queue.len() >= MATCH_SIZE => Some(queue[0].joined + max_wait),
+        _ => None,
+    }
+}
+
+// Running totals of weights, for XorShift::weighted
+fn cumulative(weights: impl Iterator<Item = usize>) -> Vec<f64> {
+    weights
+        .scan(0.0, |total, weight| {
+            *total += weight as f64;
+            Some(*total)
+        })
+        .collect()
+}
+
+// A queued player: index into the pool and when they joined
+#[derive(Debug, Clone, Copy)]
+struct QueuedPlayer {
+    player: usize,
+    joined: usize,
+}
+
+// Outcomes of one strategy over the simulated days
+#[derive(Debug, Clone, Default)]
+pub struct SimulationSummary {
+    pub strategy: String,
+    pub matches: usize,
+    // Seconds each matched player spent in the queue
+    pub wait_times: Vec<usize>,
+    // Red's expected cap differential in each match
+    pub expected_margins: Vec<f64>,
+    pub favourite_probability: f64,
+    pub lopsided: usize,
+    pub upsets: usize,
+    // Sum of p(1 - p) over matches, the variance of each simulated result
+    pub outcome_variance: f64,
+    // Players still waiting when the simulation ended
+    pub unmatched: usize,
+}
+
+impl SimulationSummary {
+    fn average(&self, total: f64) -> f64 {
+        if self.matches == 0 { 0.0 } else { total / self.matches as f64 }
+    }
+
+    fn wait_percentile(&self, percentile: f64) -> usize {
+        let mut waits = self.wait_times.clone();
+        waits.sort_unstable();
+        if waits.is_empty() {
+            return 0;
+        }
+        waits[((waits.len() - 1) as f64 * percentile).round() as usize]
+    }
+
+    fn margin_variance(&self) -> f64 {
+        let mean = self.average(self.expected_margins.iter().sum());
+        self.average(self.expected_margins.iter().map(|margin| (margin - mean).powi(2)).sum())
+    }
+}
+
+// Replays a queue of rated players against different team-balancing strategies
+pub struct MatchmakerSimulator {
+    ratings: EloRatings,
+    // Alias-resolved names with their ratings
+    pool: Vec<(String, f64)>,
+    // Running total of games played, for drawing players as often as they queued
+    pool_weights: Vec<f64>,
+    map_ids: Vec<usize>,
+    map_weights: Vec<f64>,
+    arrivals: ArrivalModel,
+    days: usize,
+    seed: u64,
+}
+
+impl MatchmakerSimulator {
+    // Fit ratings, the player pool, map frequencies and arrivals from historical matches
+    pub fn fit(matches: &[RatedMatch], mut ratings: EloRatings) -> Self {
+        let mut games_played: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut map_games: BTreeMap<usize, usize> = BTreeMap::new();
+        for game in matches {
+            for spot in game.red.iter().chain(game.blue.iter()) {
+                *games_played.entry(&spot.name).or_default() += 1;
+            }
+            *map_games.entry(game.result.map_id).or_default() += 1;
+            ratings.update(game);
+        }
+
+        Self {
+            pool: games_played.keys().map(|name| (name.to_string(), ratings.current_rating(name))).collect(),
+            pool_weights: cumulative(games_played.values().copied()),
+            map_ids: map_games.keys().copied().collect(),
+            map_weights: cumulative(map_games.values().copied()),
+            arrivals: ArrivalModel::fit(matches),
+            ratings,
+            days: DEFAULT_SIMULATED_DAYS,
+            seed: DEFAULT_SEED,
+        }
+    }
+
+    pub fn with_days(mut self, days: usize) -> Self {
+        self.days = days;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn simulate(&self, strategy: BalanceStrategy) -> SimulationSummary {
+        let mut summary = SimulationSummary { strategy: strategy.name(), ..Default::default() };
+        if self.pool.len() < MATCH_SIZE {
+            return summary;
+        }
+
+        let mut rng = XorShift::new(self.seed);
+        let mut queue: Vec<QueuedPlayer> = Vec::new();
+        let mut queued: BTreeSet<usize> = BTreeSet::new();
+        // Pool index mapped to when their current match ends
+        let mut busy_until: BTreeMap<usize, usize> = BTreeMap::new();
+        let end = self.days * 86400;
+
+        let mut next_arrival = self.arrivals.next_arrival(0, &mut rng);
+        while let Some(arrival) = next_arrival {
+            // A full queue can reach its wait limit before anyone else arrives
+            let deadline = wait_deadline(&queue, strategy).filter(|&deadline| deadline < arrival);
+            let now = deadline.unwrap_or(arrival);
+            if now >= end {
+                break;
+            }
+            if deadline.is_none() {
+                next_arrival = self.arrivals.next_arrival(now, &mut rng);
+                let player = (0..ARRIVAL_DRAWS)
+                    .map(|_| rng.weighted(&self.pool_weights))
+                    .find(|player| !queued.contains(player) && busy_until.get(player).is_none_or(|&until| until <= now));
+                let Some(player) = player else {
+                    continue;
+                };
+                queued.insert(player);
+                queue.push(QueuedPlayer { player, joined: now });
+            }
+
+            while let Some((players, red_mask)) = self.form_match(&queue, strategy, now) {
+                for queued_player in &players {
+                    queued.remove(&queued_player.player);
+                    busy_until.insert(queued_player.player, now + MATCH_LENGTH);
+                    summary.wait_times.push(now - queued_player.joined);
+                }
+                queue.retain(|waiting| !players.iter().any(|matched| matched.player == waiting.player));
+                self.play_match(&players, red_mask, &mut rng, &mut summary);
+            }
+        }
+
+        summary.unmatched = queue.len();
+        summary
+    }
+
+    // Players to pull from the queue and which of them are red, or None to keep waiting
+    fn form_match(&self, queue: &[QueuedPlayer], strategy: BalanceStrategy, now: usize) -> Option<(Vec<QueuedPlayer>, u32)> {
+        if queue.len() < MATCH_SIZE {
+            return None;
+        }
+        let rating = |queued_player: &QueuedPlayer| self.pool[queued_player.player].1;
+        match strategy {
+            BalanceStrategy::SnakeDraft => {
+                let players = queue[..MATCH_SIZE].to_vec();
+                let ratings: Vec<f64> = players.iter().map(rating).collect();
+                Some((players, snake_split(&ratings)))
+            }
+            BalanceStrategy::Exhaustive => {
+                let players = queue[..MATCH_SIZE].to_vec();
+                let ratings: Vec<f64> = players.iter().map(rating).collect();
+                Some((players, best_split(&ratings).0))
+            }
+            BalanceStrategy::RatingGapLimit { max_gap, max_wait } => {
+                // The oldest player always plays; try every set of seven others from the window
+                let window = &queue[..queue.len().min(GAP_LIMIT_WINDOW)];
+                let others = window.len() - 1;
+                let (players, (red_mask, gap)) = (0u32..1 << others)
+                    .filter(|mask| mask.count_ones() as usize == MATCH_SIZE - 1)
+                    .map(|mask| {
+                        let players: Vec<QueuedPlayer> = std::iter::once(window[0])
+                            .chain((0..others).filter(|other| mask & (1 << other) != 0).map(|other| window[other + 1]))
+                            .collect();
+                        let ratings: Vec<f64> = players.iter().map(rating).collect();
+                        (players, best_split(&ratings))
+                    })
+                    .min_by(|a, b| a.1.1.abs().total_cmp(&b.1.1.abs()))?;
+                if gap.abs() <= max_gap || now - window[0].joined >= max_wait {
+                    Some((players, red_mask))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    // Simulate the result with the rating model's win probability on a map drawn as often as it was played
+    fn play_match(&self, players: &[QueuedPlayer], red_mask: u32, rng: &mut XorShift, summary: &mut SimulationSummary) {
+        let team = |on_red: bool| -> Vec<RosterSpot> {
+            (0..MATCH_SIZE).filter(|slot| (red_mask & (1 << slot) != 0) == on_red)
+                .map(|slot| RosterSpot { name: self.pool[players[slot].player].0.clone(), share: 1.0 })
+                .collect()
+        };
+        let map_id = self.map_ids[rng.weighted(&self.map_weights)];
+        let expected_score = self.ratings.expected_score(&team(true), &team(false), map_id);
+        let probability = win_probability(expected_score);
+        let red_wins = rng.next_f64() < probability;
+
+        summary.matches += 1;
+        summary.expected_margins.push(expected_score);
+        summary.favourite_probability += probability.max(1.0 - probability);
+        summary.lopsided += (probability.max(1.0 - probability) >= LOPSIDED_PROBABILITY) as usize;
+        summary.upsets += (red_wins != (probability >= 0.5)) as usize;
+        summary.outcome_variance += probability * (1.0 - probability);
+    }
+}
+
+pub fn write_simulation_report(summaries: &[SimulationSummary], days: usize, output_path: &str) {
+    let mut file = File::create(output_path).expect("Could not create output file");
+    writeln!(file, "=== RANKED TAGPRO MATCHMAKER SIMULATION ===\n").unwrap();
+    writeln!(file, "({} simulated days of queue arrivals, results drawn from Elo win probabilities)", days).unwrap();
+    writeln!(file, "(Lopsided: favourite at {:.0}% or more. Outcome variance: mean p(1-p), 0.25 is a coin flip)\n", 100.0 * LOPSIDED_PROBABILITY).unwrap();
+
+    if summaries.iter().all(|summary| summary.matches == 0) {
+        writeln!(file, "No records found.\n").unwrap();
+        return;
+    }
+
+    writeln!(file, "### Match Balance").unwrap();
+    writeln!(file, "{:<24} {:>7} {:>9} {:>9} {:>9} {:>8} {:>9} {:>9}",
+        "Strategy", "Matches", "Fav Win P", "Lopsided", "Margin", "Upsets", "Outcome V", "Margin V").unwrap();
+    for summary in summaries {
+        writeln!(file, "{:<24} {:>7} {:>8.1}% {:>8.1}% {:>9.3} {:>7.1}% {:>9.4} {:>9.4}", summary.strategy, summary.matches,
+            100.0 * summary.average(summary.favourite_probability), 100.0 * summary.average(summary.lopsided as f64),
+            summary.average(summary.expected_margins.iter().map(|margin| margin.abs()).sum()), 100.0 * summary.average(summary.upsets as f64),
+            summary.average(summary.outcome_variance), summary.margin_variance()).unwrap();
+    }
+    writeln!(file).unwrap();
+
+    writeln!(file, "### Wait Times (seconds)").unwrap();
+    writeln!(file, "{:<24} {:>8} {:>8} {:>8} {:>8} {:>10}", "Strategy", "Mean", "Median", "90th", "Max", "Unmatched").unwrap();
+    for summary in summaries {
+        let mean = if summary.wait_times.is_empty() { 0.0 } else { summary.wait_times.iter().sum::<usize>() as f64 / summary.wait_times.len() as f64 };
+        writeln!(file, "{:<24} {:>8.1} {:>8} {:>8} {:>8} {:>10}", summary.strategy, mean, summary.wait_percentile(0.5),
+            summary.wait_percentile(0.9), summary.wait_percentile(1.0), summary.unmatched).unwrap();
+    }
+    writeln!(file).unwrap();
+}
+
+pub fn simulate_matchmaker(match_iterator: MatchIterator) {
+    let aliases = AliasRegistry::load(DEFAULT_ALIAS_PATH).unwrap_or_default();
+    let maps = MapRegistry::load(DEFAULT_MAPS_PATH).unwrap_or_default();
+    let matches = load_rated_matches(match_iterator, &aliases, &maps);
+    let ratings = EloRatings::new().with_aliases(aliases).with_maps(maps).with_prior_map_adjustments();
+
+    let simulator = MatchmakerSimulator::fit(&matches, ratings);
+    let summaries: Vec<SimulationSummary> = [
+        BalanceStrategy::SnakeDraft,
+        BalanceStrategy::Exhaustive,
+        BalanceStrategy::RatingGapLimit { max_gap: 1.0, max_wait: 120 },
+        BalanceStrategy::RatingGapLimit { max_gap: 0.5, max_wait: 300 },
+    ].into_iter()
+        .map(|strategy| simulator.simulate(strategy))
+        .collect();
+    write_simulation_report(&summaries, DEFAULT_SIMULATED_DAYS, "analysis/matchmaker_simulation.txt");
+    println!("Matchmaker simulated! Output written to analysis/matchmaker_simulation.txt");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A player every ten seconds for the first hour of the day, from a pool big
+    // enough that arrivals are rarely already queued or playing
+    const ARRIVAL_GAP: usize = 10;
+
+    fn steady_simulator() -> MatchmakerSimulator {
+        let pool_size = 1000;
+        let rate = 1.0 / ARRIVAL_GAP as f64;
+        MatchmakerSimulator {
+            ratings: EloRatings::new(),
+            pool: (0..pool_size).map(|index| (format!("player{}", index), index as f64 / 100.0)).collect(),
+            pool_weights: cumulative(std::iter::repeat_n(1, pool_size)),
+            map_ids: vec![0],
+            map_weights: cumulative(std::iter::once(1)),
+            arrivals: ArrivalModel { hourly_rate: std::array::from_fn(|hour| if hour == 0 { rate } else { 0.0 }) },
+            days: 1,
+            seed: DEFAULT_SEED,
+        }
+    }
+
+    #[test]
+    fn gap_limit_matches_players_once_they_reach_the_wait_limit() {
+        // No split is ever close enough, so every match is forced by the wait limit
+        let max_wait = 300;
+        let summary = steady_simulator().simulate(BalanceStrategy::RatingGapLimit { max_gap: -1.0, max_wait });
+        assert!(summary.matches > 0);
+        let longest = summary.wait_times.iter().max().copied().unwrap_or(0);
+        assert!(longest <= max_wait + ARRIVAL_GAP, "waited {}s", longest);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::analysis_types::{RosterChange, RosterChangeKind};
+use crate::events_reader::{EventsReader, Team};
+use crate::log_reader::MatchLog;
+use num_traits::FromPrimitive;
+
+const TICKS_PER_MINUTE: f64 = 60.0 * 60.0;
+
+// A span of time a player spent on one team, in ticks
+#[derive(Debug, Clone, Copy)]
+pub struct TeamInterval {
+    pub team: Team,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl TeamInterval {
+    pub fn length(&self) -> usize {
+        self.end - self.start
+    }
+
+    // Overlap between this interval and [start, end)
+    pub fn overlap(&self, start: usize, end: usize) -> usize {
+        self.end.min(end).saturating_sub(self.start.max(start))
+    }
+}
+
+// A player on one team's roster, with the fraction of the match they spent
+// on it. Ratings weigh substitutes by this share.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RosterSpot {
+    pub name: String,
+    pub share: f64,
+}
+
+// Time-on-team for every player in a match, built from join/quit/switch events
+#[derive(Debug, Clone, Default)]
+pub struct Participation {
+    pub intervals: Vec<Vec<TeamInterval>>,
+    pub duration: usize,
+}
+
+impl Participation {
+    pub fn from_roster_changes(initial_teams: &[Team], roster_changes: &[RosterChange], duration: usize) -> Self {
+        let mut intervals: Vec<Vec<TeamInterval>> = vec![Vec::new(); initial_teams.len()];
+        let mut open: Vec<Option<(Team, usize)>> = initial_teams.iter()
+            .map(|&team| if team == Team::None { None } else { Some((team, 0)) })
+            .collect();
+
+        let mut changes: Vec<&RosterChange> = roster_changes.iter().collect();
+        changes.sort_by_key(|change| change.time);
+
+        for change in changes {
+            let player = change.player_index;
+            match change.kind {
+                RosterChangeKind::Join => {
+                    if open[player].is_none() && change.team != Team::None {
+                        open[player] = Some((change.team, change.time));
+                    }
+                }
+                RosterChangeKind::Quit => {
+                    if let Some((team, start)) = open[player].take() {
+                        intervals[player].push(TeamInterval { team, start, end: change.time });
+                    }
+                }
+                RosterChangeKind::Switch => {
+                    let start = match open[player].take() {
+                        Some((team, start)) => {
+                            intervals[player].push(TeamInterval { team, start, end: change.time });
+                            change.time
+                        }
+                        None => change.time,
+                    };
+                    let new_team = match change.team {
+                        Team::Red => Team::Blue,
+                        Team::Blue => Team::Red,
+                        Team::None => Team::None,
+                    };
+                    if new_team != Team::None {
+                        open[player] = Some((new_team, start));
+                    }
+                }
+            }
+        }
+
+        // Close any intervals still open when the match ended
+        for (player, interval) in open.into_iter().enumerate() {
+            if let Some((team, start)) = interval.filter(|&(_, start)| start < duration) {
+                intervals[player].push(TeamInterval { team, start, end: duration });
+            }
+        }
+
+        Self { intervals, duration }
+    }
+
+    pub fn from_match_log(match_log: &MatchLog) -> Self {
+        let mut initial_teams = Vec::new();
+        let mut roster_changes = Vec::new();
+
+        for (player_idx, player) in match_log.players.iter().enumerate() {
+            let team = Team::from_usize(player.team).expect("Could not parse Team enum.");
+            initial_teams.push(team);
+
+            let player_events = EventsReader::new(player.events.clone())
+                .player_events(team, match_log.duration);
+            roster_changes.extend(player_events.iter().filter_map(|event| RosterChange::from_event(player_idx, event)));
+        }
+
+        Self::from_roster_changes(&initial_teams, &roster_changes, match_log.duration)
+    }
+
+    pub fn time_on_team(&self, player: usize, team: Team) -> usize {
+        self.intervals[player].iter()
+            .filter(|interval| interval.team == team)
+            .map(|interval| interval.length())
+            .sum()
+    }
+
+    pub fn time_played(&self, player: usize) -> usize {
+        self.intervals[player].iter().map(|interval| interval.length()).sum()
+    }
+
+    // Time on a team within [start, end)
+    pub fn time_on_team_between(&self, player: usize, team: Team, start: usize, end: usize) -> usize {
+        self.intervals[player].iter()
+            .filter(|interval| interval.team == team)
+            .map(|interval| interval.overlap(start, end))
+            .sum()
+    }
+
+    pub fn minutes_played(&self, player: usize) -> f64 {
+        self.time_played(player) as f64 / TICKS_PER_MINUTE
+    }
+
+    // Fraction of the match the player spent on the given team. This is the
+    // credit a substitute gets for a result.
+    pub fn share(&self, player: usize, team: Team) -> f64 {
+        if self.duration == 0 {
+            return 0.0;
+        }
+        self.time_on_team(player, team) as f64 / self.duration as f64
+    }
+
+    // The team the player spent the most time on
+    pub fn primary_team(&self, player: usize) -> Team {
+        let red = self.time_on_team(player, Team::Red);
+        let blue = self.time_on_team(player, Team::Blue);
+        if red == 0 && blue == 0 {
+            Team::None
+        } else if red >= blue {
+            Team::Red
+        } else {
+            Team::Blue
+        }
+    }
+
+    pub fn is_on_team_at(&self, player: usize, team: Team, time: usize) -> bool {
+        self.intervals[player].iter()
+            .any(|interval| interval.team == team && interval.start <= time && time < interval.end)
+    }
+
+    // Everyone whose primary team is `team`, most time played first
+    pub fn roster(&self, team: Team) -> Vec<usize> {
+        let mut players: Vec<usize> = (0..self.intervals.len())
+            .filter(|&player| self.primary_team(player) == team)
+            .collect();
+        players.sort_by_key(|&player| std::cmp::Reverse(self.time_on_team(player, team)));
+        players
+    }
+
+    // The roster with each player's share of the match, leaving out anyone with none
+    pub fn shares(&self, team: Team) -> Vec<(usize, f64)> {
+        self.roster(team).into_iter()
+            .map(|player| (player, self.share(player, team)))
+            .filter(|&(_, share)| share > 0.0)
+            .collect()
+    }
+
+    // Total player-time on a team, in units of full matches
+    pub fn team_coverage(&self, team: Team) -> f64 {
+        (0..self.intervals.len()).map(|player| self.share(player, team)).sum()
+    }
+
+    // Per-minute rate for a counting stat accumulated by `player`
+    pub fn per_minute(&self, player: usize, value: f64) -> f64 {
+        let minutes = self.minutes_played(player);
+        if minutes > 0.0 { value / minutes } else { 0.0 }
+    }
+}
@@ -0,0 +1,267 @@
+use crate::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use crate::log_reader::{MatchIterator, MatchLog};
+use crate::stints::Stint;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+
+// Ratings are reported in caps per regulation game
+const MINUTES_PER_GAME: f64 = 8.0;
+// How many minutes of evidence it takes to move a player halfway off zero
+const DEFAULT_RIDGE_LAMBDA: f64 = 60.0;
+const MINIMUM_MINUTES: f64 = 60.0;
+const PLAYERS_PER_BOARD: usize = 25;
+const SOLVER_TOLERANCE: f64 = 1e-10;
+const SOLVER_MAX_ITERATIONS: usize = 1000;
+
+// A player's on-field totals over every stint they played
+#[derive(Debug, Clone, Default)]
+pub struct PlusMinusTotals {
+    pub time_played: usize,
+    pub caps_for: usize,
+    pub caps_against: usize,
+}
+
+impl PlusMinusTotals {
+    pub fn plus_minus(&self) -> isize {
+        self.caps_for as isize - self.caps_against as isize
+    }
+
+    pub fn minutes_played(&self) -> f64 {
+        self.time_played as f64 / 3600.0
+    }
+
+    pub fn plus_minus_per_game(&self) -> f64 {
+        if self.time_played == 0 { 0.0 } else { self.plus_minus() as f64 * MINUTES_PER_GAME / self.minutes_played() }
+    }
+}
+
+// One stint as a row of the regression. Players are indices into
+// PlusMinusCollector.names.
+struct StintRow {
+    red: Vec<usize>,
+    blue: Vec<usize>,
+    minutes: f64,
+    cap_diff: f64,
+}
+
+// On-field cap plus/minus for every player, and adjusted plus/minus: a ridge
+// regression of each stint's cap rate on who was playing for and against
+pub struct PlusMinusCollector {
+    ingested: BTreeSet<String>,
+    names: Vec<String>,
+    player_index: BTreeMap<String, usize>,
+    totals: Vec<PlusMinusTotals>,
+    stints: Vec<StintRow>,
+    lambda: f64,
+    aliases: AliasRegistry,
+}
+
+impl Default for PlusMinusCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Public games with 8+ players, the same pool ratings/matchups.csv is built from
+pub fn is_rating_match(match_log: &MatchLog) -> bool {
+    match_log.official && match_log.players.len() >= 8 && match_log.group == Some("".to_string())
+}
+
+impl PlusMinusCollector {
+    pub fn new() -> Self {
+        Self {
+            ingested: BTreeSet::new(),
+            names: Vec::new(),
+            player_index: BTreeMap::new(),
+            totals: Vec::new(),
+            stints: Vec::new(),
+            lambda: DEFAULT_RIDGE_LAMBDA,
+            aliases: AliasRegistry::default(),
+        }
+    }
+
+    // Regularization strength, in minutes. Higher pulls players with little time harder toward zero.
+    pub fn with_lambda(mut self, lambda: f64) -> Self {
+        self.lambda = lambda;
+        self
+    }
+
+    // Combine alternate accounts under their main names
+    pub fn with_aliases(mut self, aliases: AliasRegistry) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    fn index_of(&mut self, name: &str) -> usize {
+        let name = self.aliases.resolve(name).to_string();
+        if let Some(&index) = self.player_index.get(&name) {
+            return index;
+        }
+        let index = self.names.len();
+        self.player_index.insert(name.clone(), index);
+        self.names.push(name);
+        self.totals.push(PlusMinusTotals::default());
+        index
+    }
+
+    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        if !self.ingested.insert(match_id) {
+            return;
+        }
+
+        // Filter matches
+        if !is_rating_match(match_log) {
+            return;
+        }
+
+        // Every cap counts toward plus/minus, so nothing is ignored after roster changes
+        for stint in Stint::from_match_log_settling(match_log, 0) {
+            if stint.red.is_empty() || stint.blue.is_empty() || stint.length() == 0 {
+                continue;
+            }
+            let red: Vec<usize> = stint.red.iter().map(|&player| self.index_of(&match_log.players[player].name)).collect();
+            let blue: Vec<usize> = stint.blue.iter().map(|&player| self.index_of(&match_log.players[player].name)).collect();
+
+            let team_caps = |team: &[usize]| team.iter().map(|&player| stint.player_stats[player].caps).sum::<usize>();
+            let (red_caps, blue_caps) = (team_caps(&stint.red), team_caps(&stint.blue));
+            for (players, caps_for, caps_against) in [(&red, red_caps, blue_caps), (&blue, blue_caps, red_caps)] {
+                for &player in players {
+                    let totals = &mut self.totals[player];
+                    totals.time_played += stint.length();
+                    totals.caps_for += caps_for;
+                    totals.caps_against += caps_against;
+                }
+            }
+
+            self.stints.push(StintRow {
+                red,
+                blue,
+                minutes: stint.length() as f64 / 3600.0,
+                cap_diff: stint.cap_diff as f64,
+            });
+        }
+    }
+
+    pub fn totals(&self) -> impl Iterator<Item = (&str, &PlusMinusTotals)> {
+        self.names.iter().map(String::as_str).zip(self.totals.iter())
+    }
+
+    // (X'WX + lambda * I) v, where each stint's row of X is +1 for red players
+    // and -1 for blue players, weighted by its length in minutes
+    fn apply_normal_matrix(&self, vector: &[f64]) -> Vec<f64> {
+        let mut result: Vec<f64> = vector.iter().map(|value| self.lambda * value).collect();
+        for stint in &self.stints {
+            let margin = stint.red.iter().map(|&player| vector[player]).sum::<f64>()
+                - stint.blue.iter().map(|&player| vector[player]).sum::<f64>();
+            let weighted = stint.minutes * margin;
+            stint.red.iter().for_each(|&player| result[player] += weighted);
+            stint.blue.iter().for_each(|&player| result[player] -= weighted);
+        }
+        result
+    }
+
+    // Adjusted plus/minus for every player, in caps per game, solved by conjugate
+    // gradient. Each stint's target is its cap rate, which makes X'Wy its raw cap diff.
+    pub fn adjusted_plus_minus(&self) -> Vec<f64> {
+        let mut target = vec![0.0; self.names.len()];
+        for stint in &self.stints {
+            stint.red.iter().for_each(|&player| target[player] += stint.cap_diff);
+            stint.blue.iter().for_each(|&player| target[player] -= stint.cap_diff);
+        }
+
+        let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(x, y)| x * y).sum::<f64>();
+        let mut solution = vec![0.0; self.names.len()];
+        let mut residual = target.clone();
+        let mut direction = residual.clone();
+        let mut residual_norm = dot(&residual, &residual);
+        let tolerance = SOLVER_TOLERANCE * dot(&target, &target).max(f64::MIN_POSITIVE);
+
+        for _ in 0..SOLVER_MAX_ITERATIONS {
+            if residual_norm <= tolerance {
+                break;
+            }
+            let product = self.apply_normal_matrix(&direction);
+            let step = residual_norm / dot(&direction, &product);
+            for i in 0..solution.len() {
+                solution[i] += step * direction[i];
+                residual[i] -= step * product[i];
+            }
+            let next_norm = dot(&residual, &residual);
+            let ratio = next_norm / residual_norm;
+            for i in 0..direction.len() {
+                direction[i] = residual[i] + ratio * direction[i];
+            }
+            residual_norm = next_norm;
+        }
+
+        solution.iter().map(|value| value * MINUTES_PER_GAME).collect()
+    }
+
+    // Adjusted plus/minus keyed by player name, for models that need a per-player rating
+    pub fn ratings(&self) -> BTreeMap<String, f64> {
+        self.names.iter().cloned().zip(self.adjusted_plus_minus()).collect()
+    }
+
+    // Players with enough minutes, best first by `value`
+    fn board(&self, value: impl Fn(usize) -> f64) -> Vec<(&str, &PlusMinusTotals, f64)> {
+        let mut board: Vec<(&str, &PlusMinusTotals, f64)> = self.totals()
+            .enumerate()
+            .filter(|(_, (_, totals))| totals.minutes_played() >= MINIMUM_MINUTES)
+            .map(|(index, (name, totals))| (name, totals, value(index)))
+            .collect();
+        board.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(b.0)));
+        board.truncate(PLAYERS_PER_BOARD);
+        board
+    }
+
+    pub fn generate_report(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        let apm = self.adjusted_plus_minus();
+
+        writeln!(file, "=== RANKED TAGPRO PLUS/MINUS ===\n").unwrap();
+        writeln!(file, "(Caps per {} minutes, players with at least {} minutes)\n", MINUTES_PER_GAME, MINIMUM_MINUTES).unwrap();
+
+        for (title, board) in [
+            ("### Adjusted Plus/Minus", self.board(|index| apm[index])),
+            ("### Plus/Minus", self.board(|index| self.totals[index].plus_minus_per_game())),
+        ] {
+            writeln!(file, "{}", title).unwrap();
+            if board.is_empty() {
+                writeln!(file, "No records found.\n").unwrap();
+                continue;
+            }
+            for (rank, (name, totals, value)) in board.iter().enumerate() {
+                writeln!(file, "  {}. {} - {:+.2} ({:.0} minutes, {:+} on field)",
+                    rank + 1, name, value, totals.minutes_played(), totals.plus_minus()).unwrap();
+            }
+            writeln!(file).unwrap();
+        }
+    }
+
+    // Every player, including those under the minimum
+    pub fn write_csv(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        let apm = self.adjusted_plus_minus();
+        writeln!(file, "player,minutes,caps_for,caps_against,plus_minus,plus_minus_per_game,adjusted_plus_minus").unwrap();
+        for ((name, totals), apm) in self.totals().zip(apm) {
+            writeln!(file, "\"{}\",{:.2},{},{},{},{:.4},{:.4}", name.escape_default(), totals.minutes_played(),
+                totals.caps_for, totals.caps_against, totals.plus_minus(), totals.plus_minus_per_game(), apm).unwrap();
+        }
+    }
+}
+
+pub fn collect_plus_minus(match_iterator: MatchIterator) {
+    let mut collector = PlusMinusCollector::new();
+    if let Some(aliases) = AliasRegistry::load(DEFAULT_ALIAS_PATH) {
+        collector = collector.with_aliases(aliases);
+    }
+
+    for (match_id, match_log) in match_iterator {
+        collector.process_match(match_id, &match_log);
+    }
+
+    collector.generate_report("analysis/plus_minus.txt");
+    collector.write_csv("analysis/plus_minus.csv");
+    println!("Plus/minus collected! Output written to analysis/plus_minus.{{txt,csv}}");
+}
@@ -0,0 +1,415 @@
+use crate::aliases::AliasRegistry;
+use crate::analysis_types::StatConfig;
+use crate::event_processor::process_ranked_match;
+use crate::events_reader::Team;
+use crate::leaderboard::{markdown_cell, RecordRow};
+use crate::log_reader::{MatchIterator, MatchLog};
+use crate::maps::MapRegistry;
+use crate::ranked_analysis::RankedStatConfig;
+use crate::records::{is_record_match, PlayerGameStats, PlayerStatLines};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+
+const MINIMUM_MAP_GAMES: usize = 3;
+const MAPS_PER_LIST: usize = 3;
+const PLAYERS_PER_LIST: usize = 10;
+// RankedStatConfig fields counted in ticks, which profiles show in seconds
+const RANKED_TIME_FIELDS: &[&str] = &["hold", "goodprevent", "garbage_time_hold"];
+
+// PlayerGameStats fields a profile averages, with time stats in seconds
+struct ProfileStatDefinition {
+    title: &'static str,
+    value: fn(&PlayerGameStats) -> f64,
+}
+
+const PROFILE_STATS: &[ProfileStatDefinition] = &[
+    ProfileStatDefinition { title: "Captures", value: |stats| stats.caps as f64 },
+    ProfileStatDefinition { title: "Returns", value: |stats| stats.returns as f64 },
+    ProfileStatDefinition { title: "Tags", value: |stats| stats.tags as f64 },
+    ProfileStatDefinition { title: "Pops", value: |stats| stats.pops as f64 },
+    ProfileStatDefinition { title: "Grabs", value: |stats| stats.grabs as f64 },
+    ProfileStatDefinition { title: "Powerups", value: |stats| stats.pups as f64 },
+    ProfileStatDefinition { title: "Quick Returns", value: |stats| stats.quick_returns as f64 },
+    ProfileStatDefinition { title: "Flaccid Grabs", value: |stats| stats.flaccid_grabs as f64 },
+    ProfileStatDefinition { title: "Hold (seconds)", value: |stats| stats.hold as f64 / 60.0 },
+    ProfileStatDefinition { title: "Prevent (seconds)", value: |stats| stats.prevent as f64 / 60.0 },
+    ProfileStatDefinition { title: "Button (seconds)", value: |stats| stats.button as f64 / 60.0 },
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatAverage {
+    pub stat: String,
+    pub total: f64,
+    pub per_game: f64,
+    pub per_minute: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BestGame {
+    pub stat: String,
+    pub match_id: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MapRecord {
+    pub map: String,
+    pub games: usize,
+    pub wins: usize,
+    pub losses: usize,
+}
+
+impl MapRecord {
+    pub fn win_rate(&self) -> f64 {
+        100.0 * self.wins as f64 / self.games as f64
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerCount {
+    pub player: String,
+    pub games: usize,
+}
+
+// A player's rating after one match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatingPoint {
+    pub match_id: String,
+    pub date: usize,
+    pub rating: f64,
+}
+
+// Everything known about one player over the archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerProfile {
+    pub name: String,
+    pub games: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub ties: usize,
+    pub minutes_played: f64,
+    pub averages: Vec<StatAverage>,
+    // Ranked games use the ranked stat config, whose pool and stats differ from the record games above
+    pub ranked_games: usize,
+    pub ranked_averages: Vec<StatAverage>,
+    pub best_games: Vec<BestGame>,
+    pub favourite_maps: Vec<MapRecord>,
+    pub worst_maps: Vec<MapRecord>,
+    pub teammates: Vec<PlayerCount>,
+    pub opponents: Vec<PlayerCount>,
+    pub rating_history: Vec<RatingPoint>,
+    // First-place rows this player is on
+    pub records: Vec<RecordRow>,
+}
+
+// Builds a PlayerProfile from every match the (alias-resolved) player appears in
+pub struct ProfileBuilder {
+    name: String,
+    ingested: BTreeSet<String>,
+    games: usize,
+    wins: usize,
+    losses: usize,
+    ties: usize,
+    time_played: usize,
+    // One total per entry in PROFILE_STATS, and the best game for each
+    totals: Vec<f64>,
+    best_games: Vec<Option<(f64, String)>>,
+    ranked_games: usize,
+    ranked_minutes: f64,
+    // One total per RankedStatConfig::STAT_FIELDS entry
+    ranked_totals: Vec<f64>,
+    maps: BTreeMap<String, MapRecord>,
+    teammates: BTreeMap<String, usize>,
+    opponents: BTreeMap<String, usize>,
+    rating_history: Vec<RatingPoint>,
+    records: Vec<RecordRow>,
+    aliases: AliasRegistry,
+    map_names: MapRegistry,
+}
+
+impl ProfileBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ingested: BTreeSet::new(),
+            games: 0,
+            wins: 0,
+            losses: 0,
+            ties: 0,
+            time_played: 0,
+            totals: vec![0.0; PROFILE_STATS.len()],
+            best_games: vec![None; PROFILE_STATS.len()],
+            ranked_games: 0,
+            ranked_minutes: 0.0,
+            ranked_totals: vec![0.0; RankedStatConfig::STAT_FIELDS.len()],
+            maps: BTreeMap::new(),
+            teammates: BTreeMap::new(),
+            opponents: BTreeMap::new(),
+            rating_history: Vec::new(),
+            records: Vec::new(),
+            aliases: AliasRegistry::default(),
+            map_names: MapRegistry::default(),
+        }
+    }
+
+    // Combine alternate accounts under their main names. The profile's own name is resolved too.
+    pub fn with_aliases(mut self, aliases: AliasRegistry) -> Self {
+        self.name = aliases.resolve(&self.name).to_string();
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn with_maps(mut self, maps: MapRegistry) -> Self {
+        self.map_names = maps;
+        self
+    }
+
+    // The player's ratings over time, from whichever rating model produced them
+    pub fn with_rating_history(mut self, history: Vec<RatingPoint>) -> Self {
+        self.rating_history = history;
+        self
+    }
+
+    // Keep the first-place rows from these boards that include the player
+    pub fn with_records(mut self, rows: &[RecordRow]) -> Self {
+        let name = self.name.clone();
+        self.records.extend(rows.iter()
+            .filter(|row| row.rank == 1 && row.players.iter().any(|player| self.aliases.resolve(player) == name))
+            .cloned());
+        self
+    }
+
+    // The player's entry in the match, if they played in it. Players who
+    // rejoined under a second entry are credited with their longest one.
+    fn player_index(&self, match_log: &MatchLog, participation_time: impl Fn(usize) -> usize) -> Option<usize> {
+        (0..match_log.players.len())
+            .filter(|&player_idx| self.aliases.resolve(&match_log.players[player_idx].name) == self.name)
+            .filter(|&player_idx| participation_time(player_idx) > 0)
+            .max_by_key(|&player_idx| participation_time(player_idx))
+    }
+
+    fn resolved_names(&self, match_log: &MatchLog, roster: &[usize], skip: usize) -> Vec<String> {
+        roster.iter()
+            .filter(|&&player_idx| player_idx != skip)
+            .map(|&player_idx| self.aliases.resolve(&match_log.players[player_idx].name).to_string())
+            .filter(|name| *name != self.name)
+            .collect()
+    }
+
+    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        if !self.ingested.insert(match_id.clone()) {
+            return;
+        }
+
+        if is_record_match(match_log) {
+            self.add_record_game(&match_id, match_log);
+        }
+        self.add_ranked_game(match_id, match_log);
+    }
+
+    fn add_record_game(&mut self, match_id: &str, match_log: &MatchLog) {
+        let lines = PlayerStatLines::from_match_log(match_log);
+        let participation = &lines.participation;
+        let Some(player_idx) = self.player_index(match_log, |player_idx| participation.time_played(player_idx)) else {
+            return;
+        };
+        let team = participation.primary_team(player_idx);
+        let opponent_team = if team == Team::Red { Team::Blue } else { Team::Red };
+        let stats = &lines.full[player_idx];
+
+        self.games += 1;
+        let won = lines.is_win(team);
+        let lost = lines.is_win(opponent_team);
+        self.wins += won as usize;
+        self.losses += lost as usize;
+        self.ties += (!won && !lost) as usize;
+        self.time_played += stats.time_played;
+
+        for (i, definition) in PROFILE_STATS.iter().enumerate() {
+            let value = (definition.value)(stats);
+            self.totals[i] += value;
+            if value > 0.0 && self.best_games[i].as_ref().is_none_or(|(best, _)| value > *best) {
+                self.best_games[i] = Some((value, match_id.to_string()));
+            }
+        }
+
+        let map = self.maps.entry(self.map_names.name(match_log.map_id)).or_default();
+        map.games += 1;
+        map.wins += won as usize;
+        map.losses += lost as usize;
+
+        for teammate in self.resolved_names(match_log, &participation.roster(team), player_idx) {
+            *self.teammates.entry(teammate).or_default() += 1;
+        }
+        for opponent in self.resolved_names(match_log, &participation.roster(opponent_team), player_idx) {
+            *self.opponents.entry(opponent).or_default() += 1;
+        }
+    }
+
+    fn add_ranked_game(&mut self, match_id: String, match_log: &MatchLog) {
+        let Some((result, _)) = process_ranked_match::<RankedStatConfig>(match_id, match_log) else {
+            return;
+        };
+        let on_roster = |player_idx: usize| result.red_team.contains(&player_idx) || result.blue_team.contains(&player_idx);
+        let Some(player_idx) = self.player_index(match_log, |player_idx| {
+            if on_roster(player_idx) { result.participation.time_played(player_idx) } else { 0 }
+        }) else {
+            return;
+        };
+
+        self.ranked_games += 1;
+        self.ranked_minutes += result.participation.minutes_played(player_idx);
+        for (total, value) in self.ranked_totals.iter_mut().zip(RankedStatConfig::to_values(&result.player_stats[player_idx])) {
+            *total += value;
+        }
+    }
+
+    fn averages(titles: impl Iterator<Item = String>, totals: &[f64], games: usize, minutes: f64) -> Vec<StatAverage> {
+        titles.zip(totals)
+            .map(|(stat, &total)| StatAverage {
+                stat,
+                total,
+                per_game: if games > 0 { total / games as f64 } else { 0.0 },
+                per_minute: if minutes > 0.0 { total / minutes } else { 0.0 },
+            })
+            .collect()
+    }
+
+    fn most_frequent(counts: &BTreeMap<String, usize>) -> Vec<PlayerCount> {
+        let mut players: Vec<PlayerCount> = counts.iter()
+            .map(|(player, &games)| PlayerCount { player: player.clone(), games })
+            .collect();
+        players.sort_by(|a, b| b.games.cmp(&a.games).then_with(|| a.player.cmp(&b.player)));
+        players.truncate(PLAYERS_PER_LIST);
+        players
+    }
+
+    pub fn build(&self) -> PlayerProfile {
+        let minutes_played = self.time_played as f64 / 3600.0;
+
+        // Maps with enough games, best win rate first
+        let mut maps: Vec<MapRecord> = self.maps.values()
+            .filter(|map| map.games >= MINIMUM_MAP_GAMES)
+            .cloned()
+            .collect();
+        maps.sort_by(|a, b| b.win_rate().total_cmp(&a.win_rate()).then_with(|| b.games.cmp(&a.games)));
+        // Worst maps only take maps that aren't already favourites
+        let worst_count = maps.len().saturating_sub(MAPS_PER_LIST).min(MAPS_PER_LIST);
+
+        let is_time = |field: &&str| RANKED_TIME_FIELDS.contains(field);
+        let ranked_titles = RankedStatConfig::STAT_FIELDS.iter()
+            .map(|field| if is_time(field) { format!("{} (seconds)", field) } else { field.to_string() });
+        let ranked_totals: Vec<f64> = RankedStatConfig::STAT_FIELDS.iter()
+            .zip(&self.ranked_totals)
+            .map(|(field, &total)| if is_time(field) { total / 60.0 } else { total })
+            .collect();
+
+        PlayerProfile {
+            name: self.name.clone(),
+            games: self.games,
+            wins: self.wins,
+            losses: self.losses,
+            ties: self.ties,
+            minutes_played,
+            averages: Self::averages(PROFILE_STATS.iter().map(|definition| definition.title.to_string()), &self.totals, self.games, minutes_played),
+            ranked_games: self.ranked_games,
+            ranked_averages: Self::averages(ranked_titles, &ranked_totals, self.ranked_games, self.ranked_minutes),
+            best_games: PROFILE_STATS.iter()
+                .zip(self.best_games.iter())
+                .filter_map(|(definition, best)| best.as_ref().map(|(value, match_id)| BestGame {
+                    stat: definition.title.to_string(),
+                    match_id: match_id.clone(),
+                    value: *value,
+                }))
+                .collect(),
+            favourite_maps: maps.iter().take(MAPS_PER_LIST).cloned().collect(),
+            worst_maps: maps.iter().rev().take(worst_count).cloned().collect(),
+            teammates: Self::most_frequent(&self.teammates),
+            opponents: Self::most_frequent(&self.opponents),
+            rating_history: self.rating_history.clone(),
+            records: self.records.clone(),
+        }
+    }
+}
+
+impl PlayerProfile {
+    pub fn write_json(&self, output_path: &str) {
+        let output_file = File::create(output_path).expect("Could not create output file.");
+        serde_json::to_writer_pretty(output_file, self).expect("Could not write profile to file.");
+    }
+
+    pub fn write_markdown(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+        let win_rate = if self.games > 0 { 100.0 * self.wins as f64 / self.games as f64 } else { 0.0 };
+
+        writeln!(file, "# {}\n", markdown_cell(&self.name)).unwrap();
+        writeln!(file, "{} games ({}-{}-{}, {:.1}% wins), {:.0} minutes played\n",
+            self.games, self.wins, self.losses, self.ties, win_rate, self.minutes_played).unwrap();
+
+        let ranked_title = format!("Ranked Averages ({} games)", self.ranked_games);
+        for (title, averages) in [("Averages", &self.averages), (ranked_title.as_str(), &self.ranked_averages)] {
+            writeln!(file, "## {}\n", title).unwrap();
+            writeln!(file, "| Stat | Total | Per Game | Per Minute |").unwrap();
+            writeln!(file, "|---|---:|---:|---:|").unwrap();
+            for average in averages {
+                writeln!(file, "| {} | {:.0} | {:.2} | {:.2} |", average.stat, average.total, average.per_game, average.per_minute).unwrap();
+            }
+            writeln!(file).unwrap();
+        }
+
+        writeln!(file, "## Best Games\n").unwrap();
+        writeln!(file, "| Stat | Match | Value |").unwrap();
+        writeln!(file, "|---|---|---:|").unwrap();
+        for best in &self.best_games {
+            writeln!(file, "| {} | {} | {} |", best.stat, best.match_id, best.value).unwrap();
+        }
+        writeln!(file).unwrap();
+
+        for (title, maps) in [("Favourite Maps", &self.favourite_maps), ("Worst Maps", &self.worst_maps)] {
+            writeln!(file, "## {}\n", title).unwrap();
+            if maps.is_empty() {
+                writeln!(file, "No maps with at least {} games.\n", MINIMUM_MAP_GAMES).unwrap();
+                continue;
+            }
+            for map in maps {
+                writeln!(file, "- {}: {:.1}% wins ({}-{} in {} games)", markdown_cell(&map.map), map.win_rate(), map.wins, map.losses, map.games).unwrap();
+            }
+            writeln!(file).unwrap();
+        }
+
+        for (title, players) in [("Most Frequent Teammates", &self.teammates), ("Most Frequent Opponents", &self.opponents)] {
+            writeln!(file, "## {}\n", title).unwrap();
+            for count in players {
+                writeln!(file, "- {} ({} games)", markdown_cell(&count.player), count.games).unwrap();
+            }
+            writeln!(file).unwrap();
+        }
+
+        writeln!(file, "## Rating History\n").unwrap();
+        match (self.rating_history.first(), self.rating_history.last()) {
+            (Some(first), Some(last)) => {
+                let peak = self.rating_history.iter().map(|point| point.rating).fold(f64::MIN, f64::max);
+                writeln!(file, "{} rated games: started at {:.0}, peaked at {:.0}, now {:.0}\n",
+                    self.rating_history.len(), first.rating, peak, last.rating).unwrap();
+            }
+            _ => writeln!(file, "No rating history.\n").unwrap(),
+        }
+
+        writeln!(file, "## Records Held\n").unwrap();
+        if self.records.is_empty() {
+            writeln!(file, "No records held.").unwrap();
+        }
+        for row in &self.records {
+            writeln!(file, "- {} ({}): {} in match {}", markdown_cell(&row.stat), row.scope, row.value, row.match_id).unwrap();
+        }
+    }
+}
+
+pub fn build_profile(match_iterator: MatchIterator, mut builder: ProfileBuilder) -> PlayerProfile {
+    for (match_id, match_log) in match_iterator {
+        builder.process_match(match_id, &match_log);
+    }
+    builder.build()
+}
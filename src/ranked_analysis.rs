@@ -1,4 +1,4 @@
-use crate::analysis_types::{RelevantEvent, StatConfig};
+use crate::analysis_types::{GarbageTime, MatchState, RelevantEvent, StatConfig};
 use crate::events_reader::{Event, Team};
 
 #[derive(Debug, Clone, Default)]
@@ -20,9 +20,16 @@ pub struct RankedPlayerStats {
     pub resets: usize,
     pub badflaccids: usize,
     pub sparkedouts: usize,
+    pub garbage_time_hold: usize,
+    pub garbage_time_returns: usize,
+    pub wpa: f64,
     // Tracking fields for complex stats
     pub prevent_start: Option<usize>,
     pub prevent: usize,
+    // Garbage time hold is counted up to the checkpoint whenever the score
+    // changes, and only credited once the hold ends in a drop
+    pub hold_checkpoint: Option<usize>,
+    pub pending_garbage_time_hold: usize,
 }
 
 pub struct RankedStatConfig;
@@ -46,40 +53,49 @@ impl StatConfig for RankedStatConfig {
     
     const STAT_FIELDS: &'static [&'static str] = &[
         "caps", "garbage_time_caps", "hold", "ndps", "returns", "quick_returns", "nrts", "pups", 
-        "keypops", "handoffs", "goodprevent", "resets", "badflaccids", "sparkedouts"
+        "keypops", "handoffs", "goodprevent", "resets", "badflaccids", "sparkedouts",
+        "garbage_time_hold", "garbage_time_returns", "wpa"
     ];
     
     fn process_event(
         event: &RelevantEvent,
-        cap_diff: &mut isize,
-        garbage_time_cap_diff: &mut isize,
+        state: &mut MatchState,
+        garbage_time: &GarbageTime,
         _match_duration: usize,
-        red_fc: &mut Option<usize>,
-        blue_fc: &mut Option<usize>,
-        red_grab_time: &mut Option<usize>,
-        blue_grab_time: &mut Option<usize>,
         all_player_stats: &mut [Self::Stats],
     ) {
+        let lead = Self::team_lead(event.team, state.cap_diff);
+
+        // A capture changes the lead for whoever is carrying the other flag, so
+        // count their garbage time hold up to this point under the old score
+        if let Event::Capture = event.event_type {
+            let opposing_fc = match event.team {
+                Team::Red => state.blue_fc,
+                Team::Blue => state.red_fc,
+                Team::None => None,
+            };
+            if let Some(fc) = opposing_fc {
+                Self::checkpoint_garbage_time_hold(&mut all_player_stats[fc], garbage_time, -lead, event.time);
+            }
+        }
+
         let stats = &mut all_player_stats[event.player_index];
         match event.event_type {
             Event::Capture => {
-                // Check if this is garbage time based on the original logic
-                let is_garbage_time = (event.time > 330 * 60 && (*cap_diff >= 4 || *cap_diff <= -4))
-                    || (event.time > 360 * 60 && (*cap_diff >= 3 || *cap_diff <= -3))
-                    || (event.time > 390 * 60 && (*cap_diff >= 2 || *cap_diff <= -2));
+                let is_garbage_time = garbage_time.is_garbage_time(event.time, state.cap_diff);
                 
                 match event.team {
                     Team::Red => {
-                        *cap_diff += 1;
-                        if is_garbage_time && *cap_diff > 0 {
-                            *garbage_time_cap_diff += 1;
+                        state.cap_diff += 1;
+                        if is_garbage_time && state.cap_diff > 0 {
+                            state.garbage_time_cap_diff += 1;
                             stats.garbage_time_caps += 1;
                         }
                     }
                     Team::Blue => {
-                        *cap_diff -= 1;
-                        if is_garbage_time && *cap_diff < 0 {
-                            *garbage_time_cap_diff -= 1;
+                        state.cap_diff -= 1;
+                        if is_garbage_time && state.cap_diff < 0 {
+                            state.garbage_time_cap_diff -= 1;
                             stats.garbage_time_caps += 1;
                         }
                     }
@@ -87,32 +103,35 @@ impl StatConfig for RankedStatConfig {
                 }
                 stats.caps += 1;
                 stats.hold_start = None; // Cap ends hold
+                stats.hold_checkpoint = None;
+                stats.pending_garbage_time_hold = 0;
                 
                 // Clear flag carrier tracking on capture
                 match event.team {
                     Team::Red => {
-                        *red_fc = None;
-                        *red_grab_time = None;
+                        state.red_fc = None;
+                        state.red_grab_time = None;
                     }
                     Team::Blue => {
-                        *blue_fc = None;
-                        *blue_grab_time = None;
+                        state.blue_fc = None;
+                        state.blue_grab_time = None;
                     }
                     _ => {}
                 }
             }
             Event::Grab => {
                 stats.hold_start = Some(event.time);
+                stats.hold_checkpoint = Some(event.time);
                 
                 // Track flag carrier for hwoh calculation
                 match event.team {
                     Team::Red => {
-                        *red_fc = Some(event.player_index);
-                        *red_grab_time = Some(event.time);
+                        state.red_fc = Some(event.player_index);
+                        state.red_grab_time = Some(event.time);
                     }
                     Team::Blue => {
-                        *blue_fc = Some(event.player_index);
-                        *blue_grab_time = Some(event.time);
+                        state.blue_fc = Some(event.player_index);
+                        state.blue_grab_time = Some(event.time);
                     }
                     _ => {}
                 }
@@ -125,22 +144,26 @@ impl StatConfig for RankedStatConfig {
                     }
                     None => {} // this shouldn't happen
                 }
+                Self::end_garbage_time_hold(stats, garbage_time, lead, event.time);
                 
                 // Clear flag carrier tracking on drop
                 match event.team {
                     Team::Red => {
-                        *red_fc = None;
-                        *red_grab_time = None;
+                        state.red_fc = None;
+                        state.red_grab_time = None;
                     }
                     Team::Blue => {
-                        *blue_fc = None;
-                        *blue_grab_time = None;
+                        state.blue_fc = None;
+                        state.blue_grab_time = None;
                     }
                     _ => {}
                 }
             }
             Event::Return => {
                 stats.returns += 1;
+                if lead > 0 && garbage_time.is_garbage_time(event.time, lead) {
+                    stats.garbage_time_returns += 1;
+                }
                 // TODO: Implement quick_returns logic (need team hold start times)
             }
             Event::Tag => {
@@ -173,6 +196,7 @@ impl StatConfig for RankedStatConfig {
                     }
                     None => {}
                 }
+                Self::end_garbage_time_hold(stats, garbage_time, lead, event.time);
                 stats.ndps += 1; // sort of the same effect as a pop
             }
             _ => {}
@@ -275,6 +299,28 @@ impl StatConfig for RankedStatConfig {
         Self::process_sparkedouts(all_events, all_player_stats, red_team, blue_team);
     }
     
+    fn to_values(stats: &Self::Stats) -> Vec<f64> {
+        vec![
+            stats.caps as f64,
+            stats.garbage_time_caps as f64,
+            stats.hold as f64,
+            stats.ndps as f64,
+            stats.returns as f64,
+            stats.quick_returns as f64,
+            stats.nrts as f64,
+            stats.pups as f64,
+            stats.keypops as f64,
+            stats.handoffs as f64,
+            stats.goodprevent as f64,
+            stats.resets as f64,
+            stats.badflaccids as f64,
+            stats.sparkedouts as f64,
+            stats.garbage_time_hold as f64,
+            stats.garbage_time_returns as f64,
+            stats.wpa,
+        ]
+    }
+
     fn to_csv_values(stats: &Self::Stats) -> Vec<String> {
         vec![
             stats.caps.to_string(),
@@ -291,11 +337,42 @@ impl StatConfig for RankedStatConfig {
             stats.resets.to_string(),
             stats.badflaccids.to_string(),
             stats.sparkedouts.to_string(),
+            stats.garbage_time_hold.to_string(),
+            stats.garbage_time_returns.to_string(),
+            format!("{:.4}", stats.wpa),
         ]
     }
+
+    fn record_wpa(stats: &mut Self::Stats, wpa: f64) {
+        stats.wpa = wpa;
+    }
 }
 
 impl RankedStatConfig {
+    // Caps `team` is ahead by (negative when behind)
+    fn team_lead(team: Team, cap_diff: isize) -> isize {
+        match team {
+            Team::Red => cap_diff,
+            Team::Blue => -cap_diff,
+            Team::None => 0,
+        }
+    }
+
+    fn checkpoint_garbage_time_hold(stats: &mut RankedPlayerStats, garbage_time: &GarbageTime, lead: isize, time: usize) {
+        if let Some(checkpoint) = stats.hold_checkpoint {
+            stats.pending_garbage_time_hold += garbage_time.overlap(lead, checkpoint, time);
+            stats.hold_checkpoint = Some(time);
+        }
+    }
+
+    // Hold only counts when it ends without a cap, so garbage time hold does too
+    fn end_garbage_time_hold(stats: &mut RankedPlayerStats, garbage_time: &GarbageTime, lead: isize, time: usize) {
+        Self::checkpoint_garbage_time_hold(stats, garbage_time, lead, time);
+        stats.garbage_time_hold += stats.pending_garbage_time_hold;
+        stats.pending_garbage_time_hold = 0;
+        stats.hold_checkpoint = None;
+    }
+
     fn process_goodprevent(
         all_events: &[RelevantEvent],
         all_player_stats: &mut [RankedPlayerStats],
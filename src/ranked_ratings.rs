@@ -1,28 +1,58 @@
 #[allow(unused_imports)]
 use num_traits::FromPrimitive;
 use crate::log_reader::MatchIterator;
-use crate::analysis_types::StatConfig;
-use crate::event_processor::process_ranked_match;
+use crate::analysis_types::{MatchFormat, StatConfig};
+use crate::event_processor::process_match;
 use crate::ranked_analysis::RankedStatConfig;
+use crate::win_probability::WinProbabilityModel;
 use std::fs::File;
 use std::io::Write;
+use std::sync::Arc;
 
 const OUTPUT_PATH: &str = "analysis/matchups_with_stats.csv";
+const JSON_OUTPUT_PATH: &str = "analysis/matchups_with_stats.json";
 
-pub fn get_ranked_matchups(match_iterator: MatchIterator) {
-    let mut output_file = File::create(OUTPUT_PATH)
-        .unwrap_or(File::open(OUTPUT_PATH).expect("Could not open output file."));
-    
-    // Generate header automatically from the stat config
-    let header = RankedStatConfig::generate_csv_header();
+pub fn get_ranked_matchups(match_iterator: MatchIterator, win_probability: Option<Arc<WinProbabilityModel>>) {
+    get_matchups(match_iterator, &ranked_format(win_probability), OUTPUT_PATH);
+}
+
+// Ranked games, with WPA credited only when given a model to measure it with
+fn ranked_format(win_probability: Option<Arc<WinProbabilityModel>>) -> MatchFormat {
+    match win_probability {
+        Some(model) => MatchFormat::ranked().with_win_probability(model),
+        None => MatchFormat::ranked(),
+    }
+}
+
+pub fn get_matchups(match_iterator: MatchIterator, format: &MatchFormat, output_path: &str) {
+    let mut output_file = File::create(output_path)
+        .unwrap_or(File::open(output_path).expect("Could not open output file."));
+
+    // Generate header automatically from the stat config and roster size
+    let header = RankedStatConfig::generate_csv_header(format);
     output_file.write_all(header.as_bytes()).expect("Could not write header to file.");
 
     for (match_id, match_log) in match_iterator {
-        if let Some((result, player_names)) = process_ranked_match::<RankedStatConfig>(match_id, &match_log) {
+        if let Some((result, player_names)) = process_match::<RankedStatConfig>(match_id, &match_log, format) {
             // Convert result to CSV row
-            let csv_row = format!("\n{}", result.to_csv_row::<RankedStatConfig>(&player_names));
+            let csv_row = format!("\n{}", result.to_csv_row::<RankedStatConfig>(&player_names, format));
             output_file.write_all(csv_row.as_bytes()).expect("Could not write matchup to file.");
         }
     }
 }
 
+pub fn get_ranked_matchups_json(match_iterator: MatchIterator, win_probability: Option<Arc<WinProbabilityModel>>) {
+    get_matchups_json(match_iterator, &ranked_format(win_probability), JSON_OUTPUT_PATH);
+}
+
+pub fn get_matchups_json(match_iterator: MatchIterator, format: &MatchFormat, output_path: &str) {
+    let mut matches = Vec::new();
+    for (match_id, match_log) in match_iterator {
+        if let Some((result, player_names)) = process_match::<RankedStatConfig>(match_id, &match_log, format) {
+            matches.push(result.to_json::<RankedStatConfig>(&player_names));
+        }
+    }
+
+    let output_file = File::create(output_path).expect("Could not create output file.");
+    serde_json::to_writer(output_file, &matches).expect("Could not write matchups to file.");
+}
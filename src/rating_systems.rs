@@ -0,0 +1,511 @@
+use crate::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use crate::analysis_types::MatchResult;
+use crate::elo::{win_probability, EloRatings};
+use crate::event_processor::process_ranked_match;
+use crate::events_reader::Team;
+use crate::log_reader::MatchIterator;
+use crate::maps::{MapRegistry, DEFAULT_MAPS_PATH};
+use crate::participation::RosterSpot;
+use crate::ranked_analysis::{RankedPlayerStats, RankedStatConfig};
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::Write;
+
+// Games each system sees before its predictions are scored (or, for match
+// quality, before matches are graded)
+pub const DEFAULT_BURN_IN: usize = 2000;
+const PROBABILITY_FLOOR: f64 = 1e-6;
+
+// Glicko-2, on the usual 1500/350 scale
+const GLICKO_SCALE: f64 = 173.7178;
+const GLICKO_RATING: f64 = 1500.0;
+const GLICKO_DEVIATION: f64 = 350.0;
+const GLICKO_VOLATILITY: f64 = 0.06;
+const GLICKO_TAU: f64 = 0.5;
+const GLICKO_CONVERGENCE: f64 = 1e-6;
+
+// TrueSkill-style Gaussian skills
+const TRUESKILL_MU: f64 = 25.0;
+const TRUESKILL_SIGMA: f64 = TRUESKILL_MU / 3.0;
+const TRUESKILL_BETA: f64 = TRUESKILL_SIGMA / 2.0;
+const TRUESKILL_TAU: f64 = TRUESKILL_SIGMA / 100.0;
+const TRUESKILL_DRAW_MARGIN: f64 = 0.5;
+
+// Bradley-Terry, refit on every game so far at a fixed interval
+const BRADLEY_TERRY_REFIT_INTERVAL: usize = 1000;
+const BRADLEY_TERRY_ITERATIONS: usize = 10;
+const BRADLEY_TERRY_REGULARIZATION: f64 = 1.0;
+
+// A ranked match as every rating system sees it: the result, plus each
+// team's roster by alias-resolved name and share of the match, substitutes included
+pub struct RatedMatch {
+    pub result: MatchResult<RankedPlayerStats>,
+    pub player_names: Vec<String>,
+    pub red: Vec<RosterSpot>,
+    pub blue: Vec<RosterSpot>,
+}
+
+impl RatedMatch {
+    pub fn new(result: MatchResult<RankedPlayerStats>, player_names: &[String], aliases: &AliasRegistry) -> Self {
+        let player_names: Vec<String> = player_names.iter().map(|name| aliases.resolve(name).to_string()).collect();
+        let spots = |team: Team| -> Vec<RosterSpot> {
+            result.participation.shares(team).into_iter()
+                .map(|(player_idx, share)| RosterSpot { name: player_names[player_idx].clone(), share })
+                .collect()
+        };
+        let red = spots(Team::Red);
+        let blue = spots(Team::Blue);
+        Self { result, player_names, red, blue }
+    }
+
+    // 1 for a red win, 0 for a blue win, 0.5 for a tie
+    pub fn red_score(&self) -> f64 {
+        match self.result.cap_diff {
+            diff if diff > 0 => 1.0,
+            diff if diff < 0 => 0.0,
+            _ => 0.5,
+        }
+    }
+}
+
+// A rating system that learns from ranked matches one at a time, in order
+pub trait RatingSystem {
+    const NAME: &'static str;
+
+    // Chance red beats blue, from what the system knew before the match
+    fn predict(&self, game: &RatedMatch) -> f64;
+
+    fn update(&mut self, game: &RatedMatch);
+
+    // Every rated player, by the number the system would rank them on
+    fn ratings(&self) -> BTreeMap<String, f64>;
+}
+
+impl RatingSystem for EloRatings {
+    const NAME: &'static str = "Notebook Elo";
+
+    fn predict(&self, game: &RatedMatch) -> f64 {
+        win_probability(self.expected_score(&game.red, &game.blue, game.result.map_id))
+    }
+
+    fn update(&mut self, game: &RatedMatch) {
+        self.add_result(&game.result, &game.player_names);
+    }
+
+    fn ratings(&self) -> BTreeMap<String, f64> {
+        EloRatings::ratings(self)
+    }
+}
+
+fn logistic(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn normal_pdf(x: f64) -> f64 {
+    (-x * x / 2.0).exp() / (2.0 * PI).sqrt()
+}
+
+// Standard normal CDF, from a Chebyshev fit of erfc (error under 1.2e-7)
+fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let erfc = t * (-z * z - 1.26551223 + t * (1.00002368 + t * (0.37409196 + t * (0.09678418 + t * (-0.18628806
+        + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))))).exp();
+    if x >= 0.0 { 1.0 - erfc / 2.0 } else { erfc / 2.0 }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GlickoRating {
+    // Glicko-2 scale
+    mu: f64,
+    phi: f64,
+    volatility: f64,
+}
+
+impl Default for GlickoRating {
+    fn default() -> Self {
+        Self { mu: 0.0, phi: GLICKO_DEVIATION / GLICKO_SCALE, volatility: GLICKO_VOLATILITY }
+    }
+}
+
+// Glicko-2 with each match as its own rating period. A team plays as one
+// composite player: the mean of its players' ratings, and the root mean square
+// of their deviations, each weighted by share of the match. A substitute's
+// result counts as that fraction of a game.
+#[derive(Default)]
+pub struct Glicko2 {
+    players: BTreeMap<String, GlickoRating>,
+}
+
+impl Glicko2 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn player(&self, name: &str) -> GlickoRating {
+        self.players.get(name).copied().unwrap_or_default()
+    }
+
+    fn composite(&self, team: &[RosterSpot]) -> (f64, f64) {
+        let total_share = team.iter().map(|spot| spot.share).sum::<f64>();
+        let mu = team.iter().map(|spot| spot.share * self.player(&spot.name).mu).sum::<f64>() / total_share;
+        let phi = (team.iter().map(|spot| spot.share * self.player(&spot.name).phi.powi(2)).sum::<f64>() / total_share).sqrt();
+        (mu, phi)
+    }
+
+    fn g(phi: f64) -> f64 {
+        1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+    }
+
+    // New volatility, by the Illinois algorithm from the Glicko-2 paper
+    fn volatility(rating: &GlickoRating, delta: f64, v: f64) -> f64 {
+        let a = (rating.volatility * rating.volatility).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta * delta - rating.phi * rating.phi - v - ex) / (2.0 * (rating.phi * rating.phi + v + ex).powi(2))
+                - (x - a) / (GLICKO_TAU * GLICKO_TAU)
+        };
+
+        let mut low = a;
+        let mut high = if delta * delta > rating.phi * rating.phi + v {
+            (delta * delta - rating.phi * rating.phi - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * GLICKO_TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * GLICKO_TAU
+        };
+        let (mut f_low, mut f_high) = (f(low), f(high));
+        while (high - low).abs() > GLICKO_CONVERGENCE {
+            let next = low + (low - high) * f_low / (f_high - f_low);
+            let f_next = f(next);
+            if f_next * f_high <= 0.0 {
+                low = high;
+                f_low = f_high;
+            } else {
+                f_low /= 2.0;
+            }
+            high = next;
+            f_high = f_next;
+        }
+        (low / 2.0).exp()
+    }
+}
+
+impl RatingSystem for Glicko2 {
+    const NAME: &'static str = "Glicko-2";
+
+    fn predict(&self, game: &RatedMatch) -> f64 {
+        let (red_mu, red_phi) = self.composite(&game.red);
+        let (blue_mu, blue_phi) = self.composite(&game.blue);
+        logistic(Self::g((red_phi * red_phi + blue_phi * blue_phi).sqrt()) * (red_mu - blue_mu))
+    }
+
+    fn update(&mut self, game: &RatedMatch) {
+        let red = self.composite(&game.red);
+        let blue = self.composite(&game.blue);
+        let red_score = game.red_score();
+
+        for (team, (team_mu, _), (opponent_mu, opponent_phi), score) in [
+            (&game.red, red, blue, red_score),
+            (&game.blue, blue, red, 1.0 - red_score),
+        ] {
+            let g = Self::g(opponent_phi);
+            let expected = logistic(g * (team_mu - opponent_mu));
+            let v = 1.0 / (g * g * expected * (1.0 - expected));
+            let delta = v * g * (score - expected);
+            for spot in team {
+                // A partial game carries that share of a full game's information
+                let player_v = v / spot.share;
+                let rating = self.player(&spot.name);
+                let volatility = Self::volatility(&rating, delta, player_v);
+                let phi_star = (rating.phi * rating.phi + volatility * volatility).sqrt();
+                let phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / player_v).sqrt();
+                let mu = rating.mu + phi * phi * spot.share * g * (score - expected);
+                self.players.insert(spot.name.clone(), GlickoRating { mu, phi, volatility });
+            }
+        }
+    }
+
+    fn ratings(&self) -> BTreeMap<String, f64> {
+        self.players.iter()
+            .map(|(name, rating)| (name.clone(), GLICKO_RATING + GLICKO_SCALE * rating.mu))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Skill {
+    mu: f64,
+    sigma: f64,
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Self { mu: TRUESKILL_MU, sigma: TRUESKILL_SIGMA }
+    }
+}
+
+// TrueSkill-style team model: a team's performance is the sum of its players'
+// Gaussian skills, each scaled by share of the match as in TrueSkill's partial
+// play, and the result updates each skill by its share of the uncertainty
+#[derive(Default)]
+pub struct TrueSkill {
+    players: BTreeMap<String, Skill>,
+}
+
+impl TrueSkill {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn skill(&self, name: &str) -> Skill {
+        self.players.get(name).copied().unwrap_or_default()
+    }
+
+    // Mean and variance of each team's total skill
+    fn team(&self, team: &[RosterSpot]) -> (f64, f64) {
+        team.iter()
+            .map(|spot| (spot.share, self.skill(&spot.name)))
+            .fold((0.0, 0.0), |(mu, variance), (share, skill)| (mu + share * skill.mu, variance + (share * skill.sigma).powi(2)))
+    }
+
+    fn performance_deviation(&self, game: &RatedMatch) -> f64 {
+        let (_, red_variance) = self.team(&game.red);
+        let (_, blue_variance) = self.team(&game.blue);
+        let players = game.red.iter().chain(game.blue.iter()).map(|spot| spot.share * spot.share).sum::<f64>();
+        (red_variance + blue_variance + players * TRUESKILL_BETA * TRUESKILL_BETA).sqrt()
+    }
+}
+
+impl RatingSystem for TrueSkill {
+    const NAME: &'static str = "TrueSkill";
+
+    fn predict(&self, game: &RatedMatch) -> f64 {
+        let (red_mu, _) = self.team(&game.red);
+        let (blue_mu, _) = self.team(&game.blue);
+        normal_cdf((red_mu - blue_mu) / self.performance_deviation(game))
+    }
+
+    fn update(&mut self, game: &RatedMatch) {
+        // Skills drift a little between games
+        for spot in game.red.iter().chain(game.blue.iter()) {
+            let mut skill = self.skill(&spot.name);
+            skill.sigma = (skill.sigma * skill.sigma + TRUESKILL_TAU * TRUESKILL_TAU).sqrt();
+            self.players.insert(spot.name.clone(), skill);
+        }
+
+        let c = self.performance_deviation(game);
+        let (red_mu, _) = self.team(&game.red);
+        let (blue_mu, _) = self.team(&game.blue);
+        let margin = TRUESKILL_DRAW_MARGIN / c;
+
+        // v and w from the TrueSkill paper, from the winner's side (red's for ties)
+        let red_score = game.red_score();
+        let t = (if red_score < 0.5 { blue_mu - red_mu } else { red_mu - blue_mu }) / c;
+        let (v, w) = if red_score == 0.5 {
+            let denominator = (normal_cdf(margin - t) - normal_cdf(-margin - t)).max(PROBABILITY_FLOOR);
+            let v = (normal_pdf(-margin - t) - normal_pdf(margin - t)) / denominator;
+            (v, v * v + ((margin - t) * normal_pdf(margin - t) + (margin + t) * normal_pdf(margin + t)) / denominator)
+        } else {
+            let v = normal_pdf(t - margin) / normal_cdf(t - margin).max(PROBABILITY_FLOOR);
+            (v, v * (v + t - margin))
+        };
+
+        let red_sign = if red_score < 0.5 { -1.0 } else { 1.0 };
+        for (team, sign) in [(&game.red, red_sign), (&game.blue, -red_sign)] {
+            for spot in team {
+                let skill = self.skill(&spot.name);
+                let variance = skill.sigma * skill.sigma;
+                let mu = skill.mu + sign * spot.share * variance / c * v;
+                let sigma = (variance * (1.0 - spot.share * spot.share * variance / (c * c) * w).max(PROBABILITY_FLOOR)).sqrt();
+                self.players.insert(spot.name.clone(), Skill { mu, sigma });
+            }
+        }
+    }
+
+    // Conservative skill estimates, as TrueSkill leaderboards use
+    fn ratings(&self) -> BTreeMap<String, f64> {
+        self.players.iter()
+            .map(|(name, skill)| (name.clone(), skill.mu - 3.0 * skill.sigma))
+            .collect()
+    }
+}
+
+// Player indices with their shares of the match
+type WeightedRoster = Vec<(usize, f64)>;
+
+// Bradley-Terry over share-weighted team strength sums, with a red-side term and an L2
+// penalty. The batch fit is redone on every game so far each refit interval,
+// so predictions only ever use earlier games.
+#[derive(Default)]
+pub struct BradleyTerry {
+    player_index: BTreeMap<String, usize>,
+    strengths: Vec<f64>,
+    red_advantage: f64,
+    // Red then blue player indices with their shares, and red's score
+    games: Vec<(WeightedRoster, WeightedRoster, f64)>,
+}
+
+impl BradleyTerry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn index_of(&mut self, name: &str) -> usize {
+        if let Some(&index) = self.player_index.get(name) {
+            return index;
+        }
+        self.player_index.insert(name.to_string(), self.strengths.len());
+        self.strengths.push(0.0);
+        self.strengths.len() - 1
+    }
+
+    fn margin(&self, red: &[(usize, f64)], blue: &[(usize, f64)]) -> f64 {
+        red.iter().map(|&(player, share)| share * self.strengths[player]).sum::<f64>()
+            - blue.iter().map(|&(player, share)| share * self.strengths[player]).sum::<f64>()
+            + self.red_advantage
+    }
+
+    // Diagonal Newton steps on the penalized log likelihood, warm started from the last fit
+    fn fit(&mut self) {
+        for _ in 0..BRADLEY_TERRY_ITERATIONS {
+            let mut gradient: Vec<f64> = self.strengths.iter().map(|strength| -BRADLEY_TERRY_REGULARIZATION * strength).collect();
+            let mut curvature = vec![BRADLEY_TERRY_REGULARIZATION; self.strengths.len()];
+            let (mut advantage_gradient, mut advantage_curvature) = (0.0, PROBABILITY_FLOOR);
+
+            for (red, blue, red_score) in &self.games {
+                let probability = logistic(self.margin(red, blue));
+                let residual = red_score - probability;
+                let weight = probability * (1.0 - probability);
+                red.iter().for_each(|&(player, share)| { gradient[player] += share * residual; curvature[player] += share * share * weight; });
+                blue.iter().for_each(|&(player, share)| { gradient[player] -= share * residual; curvature[player] += share * share * weight; });
+                advantage_gradient += residual;
+                advantage_curvature += weight;
+            }
+
+            for ((strength, gradient), curvature) in self.strengths.iter_mut().zip(gradient).zip(curvature) {
+                *strength += gradient / curvature;
+            }
+            self.red_advantage += advantage_gradient / advantage_curvature;
+        }
+    }
+}
+
+impl RatingSystem for BradleyTerry {
+    const NAME: &'static str = "Bradley-Terry";
+
+    fn predict(&self, game: &RatedMatch) -> f64 {
+        let strength = |team: &[RosterSpot]| team.iter()
+            .map(|spot| spot.share * self.player_index.get(&spot.name).map_or(0.0, |&player| self.strengths[player]))
+            .sum::<f64>();
+        logistic(strength(&game.red) - strength(&game.blue) + self.red_advantage)
+    }
+
+    fn update(&mut self, game: &RatedMatch) {
+        let red: WeightedRoster = game.red.iter().map(|spot| (self.index_of(&spot.name), spot.share)).collect();
+        let blue: WeightedRoster = game.blue.iter().map(|spot| (self.index_of(&spot.name), spot.share)).collect();
+        self.games.push((red, blue, game.red_score()));
+        if self.games.len().is_multiple_of(BRADLEY_TERRY_REFIT_INTERVAL) {
+            self.fit();
+        }
+    }
+
+    fn ratings(&self) -> BTreeMap<String, f64> {
+        self.player_index.iter()
+            .map(|(name, &player)| (name.clone(), self.strengths[player]))
+            .collect()
+    }
+}
+
+// How well a system's pre-match predictions matched ranked outcomes. Ties
+// still update the systems but aren't scored.
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    pub system: &'static str,
+    pub games: usize,
+    pub log_loss: f64,
+    pub brier: f64,
+    // Share of games where the favourite won, counting coin flips as half
+    pub accuracy: f64,
+    // Average probability given to the eventual winner
+    pub winner_probability: f64,
+}
+
+// Ranked CTF matches, in order, ready to feed to any rating system
+pub fn load_rated_matches(match_iterator: MatchIterator, aliases: &AliasRegistry, maps: &MapRegistry) -> Vec<RatedMatch> {
+    let mut matches = Vec::new();
+    for (match_id, match_log) in match_iterator {
+        if maps.get(match_log.map_id).is_some_and(|map| map.map_type != "ctf") {
+            continue;
+        }
+        if let Some((result, player_names)) = process_ranked_match::<RankedStatConfig>(match_id, &match_log) {
+            matches.push(RatedMatch::new(result, &player_names, aliases));
+        }
+    }
+    matches
+}
+
+// Predict each match before showing it to the system, scoring every match after the first `burn_in`
+pub fn evaluate<R: RatingSystem>(system: &mut R, matches: &[RatedMatch], burn_in: usize) -> Evaluation {
+    let mut evaluation = Evaluation { system: R::NAME, games: 0, log_loss: 0.0, brier: 0.0, accuracy: 0.0, winner_probability: 0.0 };
+    for (index, game) in matches.iter().enumerate() {
+        let red_score = game.red_score();
+        if index >= burn_in && red_score != 0.5 {
+            let probability = system.predict(game).clamp(PROBABILITY_FLOOR, 1.0 - PROBABILITY_FLOOR);
+            let winner_probability = if red_score == 1.0 { probability } else { 1.0 - probability };
+            evaluation.games += 1;
+            evaluation.log_loss -= winner_probability.ln();
+            evaluation.brier += (1.0 - winner_probability).powi(2);
+            evaluation.accuracy += if winner_probability > 0.5 { 1.0 } else if winner_probability == 0.5 { 0.5 } else { 0.0 };
+            evaluation.winner_probability += winner_probability;
+        }
+        system.update(game);
+    }
+
+    if evaluation.games > 0 {
+        let games = evaluation.games as f64;
+        evaluation.log_loss /= games;
+        evaluation.brier /= games;
+        evaluation.accuracy /= games;
+        evaluation.winner_probability /= games;
+    }
+    evaluation
+}
+
+pub fn write_evaluation_report(evaluations: &[Evaluation], burn_in: usize, output_path: &str) {
+    let mut file = File::create(output_path).expect("Could not create output file");
+    writeln!(file, "=== RANKED TAGPRO RATING SYSTEM COMPARISON ===\n").unwrap();
+    writeln!(file, "(Predictions made before each match, scored after the first {} matches, ties excluded)\n", burn_in).unwrap();
+
+    let mut ranked: Vec<&Evaluation> = evaluations.iter().collect();
+    ranked.sort_by(|a, b| a.log_loss.total_cmp(&b.log_loss));
+    writeln!(file, "{:<16} {:>7} {:>9} {:>7} {:>9} {:>9}", "System", "Games", "Log Loss", "Brier", "Accuracy", "Winner P").unwrap();
+    for evaluation in ranked {
+        writeln!(file, "{:<16} {:>7} {:>9.4} {:>7.4} {:>8.2}% {:>8.2}%", evaluation.system, evaluation.games, evaluation.log_loss,
+            evaluation.brier, 100.0 * evaluation.accuracy, 100.0 * evaluation.winner_probability).unwrap();
+    }
+}
+
+// Run every system over the same matches and compare their predictions
+pub fn compare_rating_systems(match_iterator: MatchIterator) {
+    let aliases = AliasRegistry::load(DEFAULT_ALIAS_PATH).unwrap_or_default();
+    let maps = MapRegistry::load(DEFAULT_MAPS_PATH).unwrap_or_default();
+    let matches = load_rated_matches(match_iterator, &aliases, &maps);
+
+    // The Elo the crate ships refits per-map adjustments from prior matches;
+    // the flat red advantage shows what that buys
+    let mut shipped_elo = EloRatings::new().with_maps(maps.clone()).with_prior_map_adjustments();
+    let mut flat_elo = EloRatings::new().with_maps(maps);
+    let evaluations = vec![
+        Evaluation { system: "Elo (map fit)", ..evaluate(&mut shipped_elo, &matches, DEFAULT_BURN_IN) },
+        Evaluation { system: "Elo (flat red)", ..evaluate(&mut flat_elo, &matches, DEFAULT_BURN_IN) },
+        evaluate(&mut Glicko2::new(), &matches, DEFAULT_BURN_IN),
+        evaluate(&mut TrueSkill::new(), &matches, DEFAULT_BURN_IN),
+        evaluate(&mut BradleyTerry::new(), &matches, DEFAULT_BURN_IN),
+    ];
+    write_evaluation_report(&evaluations, DEFAULT_BURN_IN, "analysis/rating_systems.txt");
+    println!("Rating systems compared! Output written to analysis/rating_systems.txt");
+}
@@ -0,0 +1,135 @@
+use crate::leaderboard::{GameResult, RecordRow};
+use std::fs::File;
+use std::io::Write;
+
+// How a new entry relates to the entries that already shared its rank
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieChange {
+    None,
+    // Tied an existing entry
+    Joined,
+    // Took a rank that several entries used to share
+    Broken,
+}
+
+// What happened to one entry between two runs of a board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordChange {
+    New { rank: usize, tie: TieChange },
+    Moved { from: usize, to: usize },
+    Dropped { rank: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct BoardDiff {
+    pub scope: String,
+    pub stat: String,
+    pub changes: Vec<(RecordChange, RecordRow)>,
+}
+
+fn same_entry(a: &RecordRow, b: &RecordRow) -> bool {
+    a.match_id == b.match_id && a.players == b.players && a.result == b.result
+}
+
+// Scope and stat pairs in the order they first appear
+fn boards(rows: &[RecordRow]) -> Vec<(&str, &str)> {
+    let mut boards: Vec<(&str, &str)> = Vec::new();
+    for row in rows {
+        let board = (row.scope.as_str(), row.stat.as_str());
+        if !boards.contains(&board) {
+            boards.push(board);
+        }
+    }
+    boards
+}
+
+fn diff_board(old: &[&RecordRow], new: &[&RecordRow]) -> Vec<(RecordChange, RecordRow)> {
+    let mut changes = Vec::new();
+
+    for row in new {
+        let change = match old.iter().find(|old_row| same_entry(old_row, row)) {
+            Some(old_row) if old_row.rank == row.rank => continue,
+            Some(old_row) => RecordChange::Moved { from: old_row.rank, to: row.rank },
+            None => {
+                let previous_tier: Vec<&&RecordRow> = old.iter().filter(|old_row| old_row.rank == row.rank).collect();
+                let tie = if previous_tier.iter().any(|old_row| old_row.value == row.value) {
+                    TieChange::Joined
+                } else if previous_tier.len() > 1 {
+                    TieChange::Broken
+                } else {
+                    TieChange::None
+                };
+                RecordChange::New { rank: row.rank, tie }
+            }
+        };
+        changes.push((change, (*row).clone()));
+    }
+
+    for row in old {
+        if !new.iter().any(|new_row| same_entry(new_row, row)) {
+            changes.push((RecordChange::Dropped { rank: row.rank }, (*row).clone()));
+        }
+    }
+    changes
+}
+
+// Compare two runs board by board. Boards only in the old run still report
+// their entries as dropped.
+pub fn diff_records(old: &[RecordRow], new: &[RecordRow]) -> Vec<BoardDiff> {
+    let mut all_boards = boards(new);
+    for board in boards(old) {
+        if !all_boards.contains(&board) {
+            all_boards.push(board);
+        }
+    }
+
+    all_boards.into_iter()
+        .map(|(scope, stat)| {
+            let on_board = |row: &&RecordRow| row.scope == scope && row.stat == stat;
+            let old_rows: Vec<&RecordRow> = old.iter().filter(on_board).collect();
+            let new_rows: Vec<&RecordRow> = new.iter().filter(on_board).collect();
+            BoardDiff {
+                scope: scope.to_string(),
+                stat: stat.to_string(),
+                changes: diff_board(&old_rows, &new_rows),
+            }
+        })
+        .filter(|board| !board.changes.is_empty())
+        .collect()
+}
+
+fn describe(change: &RecordChange) -> String {
+    match change {
+        RecordChange::New { rank, tie: TieChange::None } => format!("New #{}", rank),
+        RecordChange::New { rank, tie: TieChange::Joined } => format!("New #{} (tied)", rank),
+        RecordChange::New { rank, tie: TieChange::Broken } => format!("New #{} (broke a tie)", rank),
+        RecordChange::Moved { from, to } => format!("Moved #{} -> #{}", from, to),
+        RecordChange::Dropped { rank } => format!("Dropped off from #{}", rank),
+    }
+}
+
+// A changelog with one section per scope and one list per board
+pub fn write_record_diff(diffs: &[BoardDiff], title: &str, output_path: &str) {
+    let mut file = File::create(output_path).expect("Could not create output file");
+    writeln!(file, "=== CHANGES: {} ===\n", title).unwrap();
+
+    if diffs.is_empty() {
+        writeln!(file, "No changes.").unwrap();
+        return;
+    }
+
+    let mut current_scope: Option<&str> = None;
+    for diff in diffs {
+        if current_scope != Some(diff.scope.as_str()) {
+            writeln!(file, "## {}\n", diff.scope).unwrap();
+            current_scope = Some(diff.scope.as_str());
+        }
+        writeln!(file, "### {}", diff.stat).unwrap();
+        for (change, row) in &diff.changes {
+            let result = row.result.map(|result| format!(" ({})", GameResult::label(result))).unwrap_or_default();
+            let players = if row.players.is_empty() { String::new() } else { format!("{} - ", row.players.join(", ")) };
+            writeln!(file, "  {}: Match {}: {}{}{}", describe(change), row.match_id, players, row.value, result).unwrap();
+        }
+        writeln!(file).unwrap();
+    }
+}
@@ -1,13 +1,151 @@
+use crate::career_records::CareerRecordsCollector;
 use crate::events_reader::{Event, EventsReader, Team};
+use crate::leaderboard::{
+    record_rows, write_leaderboard, write_record_outputs, Cutoff, Leaderboard, Order, RecordEntry, RecordRow, SplitLeaderboard,
+};
 use crate::log_reader::{MatchIterator, MatchLog};
+use crate::participation::Participation;
+use crate::record_diff::{diff_records, write_record_diff};
+use crate::streaks::StreakRecordsCollector;
+use crate::win_probability::{MatchTimeline, WinProbabilityModel};
 use num_traits::FromPrimitive;
-use std::collections::BTreeMap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
 use std::io::Write;
+use std::sync::Arc;
 
 const MINIMUM_RANKED_MATCH_LENGTH: usize = 180 * 60;
 const MINIMUM_RECORD_MATCH_LENGTH: usize = 90 * 60; // 90 seconds in ticks (60 ticks per second)
 const EIGHT_MINUTES: usize = 8 * 60 * 60; // 8 minutes in ticks (60 ticks per second)
+const MINIMUM_RATE_MINUTES: f64 = 4.0;
+const RATE_SCALE: usize = 100; // rates are stored in hundredths so they can key a leaderboard
+const TEAM_SIZE: usize = 4;
+const RECORDS_PER_BOARD: usize = 5;
+
+// Saved collector state, so a run over new bulk files resumes from the last one
+const ALL_TIME_RECORDS_STATE_PATH: &str = "analysis/all_time_records_state.json";
+const TEAM_RECORDS_STATE_PATH: &str = "analysis/team_records_state.json";
+const COMBINED_GAME_RECORDS_STATE_PATH: &str = "analysis/combined_game_records_state.json";
+const CAP_RUNS_AND_COMEBACKS_STATE_PATH: &str = "analysis/cap_runs_and_comebacks_state.json";
+
+// Scope names used in the structured outputs
+const FULL_SCOPE: &str = "full";
+const FIRST8_SCOPE: &str = "first8";
+const RATE_SCOPE: &str = "per_minute";
+const CAREER_SCOPE: &str = "career";
+
+fn to_rate_key(rate: f64) -> usize {
+    (rate * RATE_SCALE as f64).round() as usize
+}
+
+// Format a time in ticks as m:ss
+fn format_clock(ticks: usize) -> String {
+    let seconds = ticks / 60;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// Collectors that can be saved after a run and resumed by a later one. Each
+// remembers the match ids it has ingested, so feeding it a match again is a
+// no-op. Delete the state file after changing how a collector counts records.
+// State is tagged with the collector's report name, so a file is only ever
+// loaded back into the collector that wrote it.
+pub trait CollectorState: RecordCollector + Serialize + DeserializeOwned {
+    fn save_state(&self, output_path: &str) {
+        let output_file = File::create(output_path).expect("Could not create state file");
+        let saved = SavedState { kind: Self::REPORT_NAME.to_string(), state: self };
+        serde_json::to_writer(output_file, &saved).expect("Could not write collector state to file");
+    }
+
+    // None when nothing has been saved yet
+    fn load_state(input_path: &str) -> Result<Option<Self>, String> {
+        let Ok(contents) = std::fs::read_to_string(input_path) else {
+            return Ok(None);
+        };
+        let saved: SavedState<serde_json::Value> = serde_json::from_str(&contents)
+            .map_err(|error| format!("Could not parse collector state file {}: {}", input_path, error))?;
+        if saved.kind != Self::REPORT_NAME {
+            return Err(format!("{} holds {} state, not {}", input_path, saved.kind, Self::REPORT_NAME));
+        }
+        Self::from_saved(saved.state, input_path).map(Some)
+    }
+
+    fn from_saved(state: serde_json::Value, input_path: &str) -> Result<Self, String> {
+        serde_json::from_value(state)
+            .map_err(|error| format!("Could not parse {} state in {}: {}", Self::REPORT_NAME, input_path, error))
+    }
+}
+
+// Collector state as written to disk, tagged with the collector that wrote it
+#[derive(Serialize, Deserialize)]
+struct SavedState<T> {
+    kind: String,
+    state: T,
+}
+
+// What every record collector does, so they can also be run per season,
+// per month, or over a custom window
+pub trait RecordCollector {
+    // File name the reports are written under, without directory or extension
+    const REPORT_NAME: &'static str;
+    const TITLE: &'static str;
+
+    fn process_match(&mut self, match_id: String, match_log: &MatchLog);
+    fn generate_report(&self, output_path: &str);
+    fn record_rows(&self) -> Vec<RecordRow>;
+}
+
+// Rows from a structured report, or from any collector's saved state. None
+// when the file doesn't exist.
+pub fn load_record_rows(input_path: &str) -> Result<Option<Vec<RecordRow>>, String> {
+    let Ok(contents) = std::fs::read_to_string(input_path) else {
+        return Ok(None);
+    };
+    if let Ok(rows) = serde_json::from_str::<Vec<RecordRow>>(&contents) {
+        return Ok(Some(rows));
+    }
+    let saved: SavedState<serde_json::Value> = serde_json::from_str(&contents)
+        .map_err(|error| format!("{} is neither a record report nor collector state: {}", input_path, error))?;
+    let rows = match saved.kind.as_str() {
+        RecordsCollector::REPORT_NAME => saved_rows::<RecordsCollector>(saved.state, input_path)?,
+        TeamRecordsCollector::REPORT_NAME => saved_rows::<TeamRecordsCollector>(saved.state, input_path)?,
+        CombinedGameRecordsCollector::REPORT_NAME => saved_rows::<CombinedGameRecordsCollector>(saved.state, input_path)?,
+        CapRunsAndComebacksCollector::REPORT_NAME => saved_rows::<CapRunsAndComebacksCollector>(saved.state, input_path)?,
+        CareerRecordsCollector::REPORT_NAME => saved_rows::<CareerRecordsCollector>(saved.state, input_path)?,
+        StreakRecordsCollector::REPORT_NAME => saved_rows::<StreakRecordsCollector>(saved.state, input_path)?,
+        kind => return Err(format!("{} holds state for an unknown collector: {}", input_path, kind)),
+    };
+    Ok(Some(rows))
+}
+
+fn saved_rows<C: CollectorState>(state: serde_json::Value, input_path: &str) -> Result<Vec<RecordRow>, String> {
+    C::from_saved(state, input_path).map(|collector| collector.record_rows())
+}
+
+// When resuming from saved state, write what this run changed next to the report
+pub(crate) fn write_changes(previous: Option<Vec<RecordRow>>, rows: &[RecordRow], title: &str, output_path: &str) {
+    if let Some(previous) = previous {
+        write_record_diff(&diff_records(&previous, rows), title, output_path);
+    }
+}
+
+// Prefix for the highs and lows boards of the same stat
+fn order_label(order: Order) -> &'static str {
+    match order {
+        Order::Highest => "Highest",
+        Order::Lowest => "Lowest",
+    }
+}
+
+// Format a leaderboard value stored with the given scale (1 for raw totals)
+fn format_scaled(value: f64, scale: usize) -> String {
+    if scale == 1 {
+        format!("{}", value)
+    } else {
+        format!("{:.2}", value / scale as f64)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct PlayerRecord {
@@ -32,6 +170,7 @@ pub struct PlayerGameStats {
     pub hold: usize,
     pub prevent: usize,
     pub button: usize,
+    pub time_played: usize,
 
     // Tracking fields for ongoing activities
     pub hold_start: Option<usize>,
@@ -41,6 +180,14 @@ pub struct PlayerGameStats {
 }
 
 impl PlayerGameStats {
+    // Rate per minute of time actually played
+    pub fn per_minute(&self, value: usize) -> f64 {
+        if self.time_played == 0 {
+            return 0.0;
+        }
+        value as f64 * 3600.0 / self.time_played as f64
+    }
+
     fn finalize_time_stats(&mut self, end_time: usize, cutoff: usize) {
         // Finalize hold if still ongoing at cutoff
         if let Some(start) = self.hold_start {
@@ -65,79 +212,27 @@ impl PlayerGameStats {
     }
 }
 
-#[derive(Default)]
-struct StatLeaderboards {
-    caps: BTreeMap<usize, Vec<(String, String)>>,
-    returns: BTreeMap<usize, Vec<(String, String)>>,
-    tags: BTreeMap<usize, Vec<(String, String)>>,
-    pops: BTreeMap<usize, Vec<(String, String)>>,
-    grabs: BTreeMap<usize, Vec<(String, String)>>,
-    hold: BTreeMap<usize, Vec<(String, String)>>,
-    prevent: BTreeMap<usize, Vec<(String, String)>>,
-    button: BTreeMap<usize, Vec<(String, String)>>,
-    pups: BTreeMap<usize, Vec<(String, String)>>,
-    quick_returns: BTreeMap<usize, Vec<(String, String)>>,
-    flaccid_grabs: BTreeMap<usize, Vec<(String, String)>>,
-    tags_no_pops: BTreeMap<usize, Vec<(String, String)>>,
-    returns_no_grabs: BTreeMap<usize, Vec<(String, String)>>,
-    hold_no_returns: BTreeMap<usize, Vec<(String, String)>>,
-    caps_no_returns: BTreeMap<usize, Vec<(String, String)>>,
+// Official, full-roster 8-minute ranked games long enough to count for records
+pub fn is_record_match(match_log: &MatchLog) -> bool {
+    match_log.official
+        && match_log.players.len() >= 8
+        && match_log.group == Some("".to_string())
+        && match_log.time_limit == 8.0
+        && match_log.duration >= MINIMUM_RANKED_MATCH_LENGTH
+        && match_log.duration >= MINIMUM_RECORD_MATCH_LENGTH  // Skip games under 90 seconds
 }
 
-pub struct RecordsCollector {
-    full_all: StatLeaderboards,
-    full_wins: StatLeaderboards,
-    full_losses: StatLeaderboards,
-    first8_all: StatLeaderboards,
-    first8_wins: StatLeaderboards,
-    first8_losses: StatLeaderboards,
+// Every player's stat lines for one match, as the player records see them
+pub struct PlayerStatLines {
+    pub full: Vec<PlayerGameStats>,
+    pub first8: Vec<PlayerGameStats>,
+    pub participation: Participation,
+    // Red caps minus blue caps
+    pub cap_diff: isize,
 }
 
-impl RecordsCollector {
-    pub fn new() -> Self {
-        Self {
-            full_all: StatLeaderboards::default(),
-            full_wins: StatLeaderboards::default(),
-            full_losses: StatLeaderboards::default(),
-            first8_all: StatLeaderboards::default(),
-            first8_wins: StatLeaderboards::default(),
-            first8_losses: StatLeaderboards::default(),
-        }
-    }
-
-    fn insert_record(map: &mut BTreeMap<usize, Vec<(String, String)>>, match_id: String, player_name: String, value: usize) {
-        map.entry(value).or_insert_with(Vec::new).push((match_id, player_name));
-    }
-
-    fn insert_with_win_loss(
-        all_board: &mut BTreeMap<usize, Vec<(String, String)>>,
-        win_board: &mut BTreeMap<usize, Vec<(String, String)>>,
-        loss_board: &mut BTreeMap<usize, Vec<(String, String)>>,
-        match_id: String,
-        player_name: String,
-        value: usize,
-        is_win: bool,
-    ) {
-        Self::insert_record(all_board, match_id.clone(), player_name.clone(), value);
-        if is_win {
-            Self::insert_record(win_board, match_id, player_name, value);
-        } else {
-            Self::insert_record(loss_board, match_id, player_name, value);
-        }
-    }
-
-    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
-        // Filter matches
-        if !match_log.official
-            || match_log.players.len() < 8
-            || match_log.group != Some("".to_string())
-            || match_log.time_limit != 8.0
-            || match_log.duration < MINIMUM_RANKED_MATCH_LENGTH
-            || match_log.duration < MINIMUM_RECORD_MATCH_LENGTH  // Skip games under 90 seconds
-        {
-            return;
-        }
-
+impl PlayerStatLines {
+    pub fn from_match_log(match_log: &MatchLog) -> Self {
         let mut player_full_stats: Vec<PlayerGameStats> = vec![PlayerGameStats::default(); match_log.players.len()];
         let mut player_first8_stats: Vec<PlayerGameStats> = vec![PlayerGameStats::default(); match_log.players.len()];
 
@@ -191,7 +286,7 @@ impl RecordsCollector {
         let mut cap_diff: isize = 0;
 
         for event in all_events.iter() {
-            self.process_event(
+            RecordsCollector::process_event_static(
                 event.event_type,
                 event.time,
                 &mut player_full_stats[event.player_idx],
@@ -216,7 +311,7 @@ impl RecordsCollector {
         let mut blue_grab_time_first8: Option<usize> = None;
 
         for event in all_first8_events.iter() {
-            self.process_event(
+            RecordsCollector::process_event_static(
                 event.event_type,
                 event.time,
                 &mut player_first8_stats[event.player_idx],
@@ -233,386 +328,465 @@ impl RecordsCollector {
             player_first8_stats[player_idx].finalize_time_stats(match_log.duration, EIGHT_MINUTES);
         }
 
+        // Record how long each player was actually on a team
+        let participation = Participation::from_match_log(match_log);
+        for player_idx in 0..match_log.players.len() {
+            player_full_stats[player_idx].time_played = participation.time_played(player_idx);
+            player_first8_stats[player_idx].time_played =
+                participation.time_on_team_between(player_idx, Team::Red, 0, EIGHT_MINUTES)
+                + participation.time_on_team_between(player_idx, Team::Blue, 0, EIGHT_MINUTES);
+        }
+
+        Self { full: player_full_stats, first8: player_first8_stats, participation, cap_diff }
+    }
+
+    pub fn is_win(&self, team: Team) -> bool {
+        match team {
+            Team::Red => self.cap_diff > 0,
+            Team::Blue => self.cap_diff < 0,
+            _ => false,
+        }
+    }
+
+    // Fraction of a game the player is credited with: their time on either team
+    pub fn game_credit(&self, player: usize) -> f64 {
+        self.participation.share(player, Team::Red) + self.participation.share(player, Team::Blue)
+    }
+
+    // Fraction of a win the player is credited with: their time on the winning team
+    pub fn win_credit(&self, player: usize) -> f64 {
+        [Team::Red, Team::Blue].into_iter()
+            .filter(|&team| self.is_win(team))
+            .map(|team| self.participation.share(player, team))
+            .sum()
+    }
+}
+
+// A player stat with its own leaderboard. `value` is None when the player
+// doesn't qualify for the board.
+struct PlayerStatDefinition {
+    title: &'static str,
+    value: fn(&PlayerGameStats) -> Option<usize>,
+}
+
+const PLAYER_STATS: &[PlayerStatDefinition] = &[
+    PlayerStatDefinition { title: "Captures", value: |stats| Some(stats.caps) },
+    PlayerStatDefinition { title: "Returns", value: |stats| Some(stats.returns) },
+    PlayerStatDefinition { title: "Tags", value: |stats| Some(stats.tags) },
+    PlayerStatDefinition { title: "Pops", value: |stats| Some(stats.pops) },
+    PlayerStatDefinition { title: "Grabs", value: |stats| Some(stats.grabs) },
+    PlayerStatDefinition { title: "Hold (seconds)", value: |stats| Some(stats.hold / 60) },
+    PlayerStatDefinition { title: "Prevent (seconds)", value: |stats| Some(stats.prevent / 60) },
+    PlayerStatDefinition { title: "Button Time (seconds)", value: |stats| Some(stats.button / 60) },
+    PlayerStatDefinition { title: "Powerups", value: |stats| Some(stats.pups) },
+    PlayerStatDefinition { title: "Quick Returns", value: |stats| Some(stats.quick_returns) },
+    PlayerStatDefinition { title: "Flaccid Grabs", value: |stats| Some(stats.flaccid_grabs) },
+    PlayerStatDefinition {
+        title: "Tags (No Pops)",
+        value: |stats| (stats.tags > 0 && stats.pops == 0).then_some(stats.tags),
+    },
+    PlayerStatDefinition {
+        title: "Returns (No Grabs)",
+        value: |stats| (stats.returns > 0 && stats.grabs == 0).then_some(stats.returns),
+    },
+    PlayerStatDefinition {
+        title: "Hold (No Returns, seconds)",
+        value: |stats| (stats.hold > 0 && stats.returns == 0).then_some(stats.hold / 60),
+    },
+    PlayerStatDefinition {
+        title: "Caps (No Returns)",
+        value: |stats| (stats.caps > 0 && stats.returns == 0).then_some(stats.caps),
+    },
+];
+
+// One win/loss board per entry in PLAYER_STATS
+#[derive(Serialize, Deserialize)]
+struct StatLeaderboards {
+    boards: Vec<SplitLeaderboard<usize, RecordEntry>>,
+}
+
+impl Default for StatLeaderboards {
+    fn default() -> Self {
+        Self {
+            boards: PLAYER_STATS.iter()
+                .map(|_| SplitLeaderboard::new(Order::Highest, Cutoff::Ties(RECORDS_PER_BOARD)).skipping_zero())
+                .collect(),
+        }
+    }
+}
+
+// A player's career win probability added and games credited
+type CareerWpa<'a> = (&'a String, f64, f64);
+
+#[derive(Serialize, Deserialize)]
+pub struct RecordsCollector {
+    ingested: BTreeSet<String>,
+    full: StatLeaderboards,
+    first8: StatLeaderboards,
+    rate: StatLeaderboards,
+    minimum_rate_minutes: f64,
+    #[serde(skip)]
+    win_probability: Option<Arc<WinProbabilityModel>>,
+    // Match files the win probability model was fitted on, so a resumed run
+    // can't mix career totals from different models (or none)
+    wpa_model: Option<(usize, usize)>,
+    // Player name -> (total win probability added, games credited by share of the match)
+    career_wpa: BTreeMap<String, (f64, f64)>,
+}
+
+impl CollectorState for RecordsCollector {}
+
+impl RecordCollector for RecordsCollector {
+    const REPORT_NAME: &'static str = "all_time_records";
+    const TITLE: &'static str = "All-Time Ranked TagPro Records";
+
+    fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        Self::process_match(self, match_id, match_log);
+    }
+
+    fn generate_report(&self, output_path: &str) {
+        Self::generate_report(self, output_path);
+    }
+
+    fn record_rows(&self) -> Vec<RecordRow> {
+        Self::record_rows(self)
+    }
+}
+
+impl RecordsCollector {
+    pub fn new() -> Self {
+        Self {
+            ingested: BTreeSet::new(),
+            full: StatLeaderboards::default(),
+            first8: StatLeaderboards::default(),
+            rate: StatLeaderboards::default(),
+            minimum_rate_minutes: MINIMUM_RATE_MINUTES,
+            win_probability: None,
+            wpa_model: None,
+            career_wpa: BTreeMap::new(),
+        }
+    }
+
+    // Minimum minutes a player must have played for their per-minute rates to count
+    pub fn with_minimum_rate_minutes(mut self, minutes: f64) -> Self {
+        self.minimum_rate_minutes = minutes;
+        self
+    }
+
+    // Track career win probability added using this model
+    pub fn with_win_probability(mut self, model: Arc<WinProbabilityModel>) -> Self {
+        self.wpa_model = model.fitted_files;
+        self.win_probability = Some(model);
+        self
+    }
+
+    // Resume saved state with the model it was collected with. Matches already
+    // ingested aren't seen again, so a different model (or none) would leave
+    // career win probability added covering only some of them.
+    pub fn resume_with_win_probability(mut self, model: Option<Arc<WinProbabilityModel>>) -> Result<Self, String> {
+        let fitted_files = model.as_ref().and_then(|model| model.fitted_files);
+        if fitted_files != self.wpa_model {
+            let describe = |files: Option<(usize, usize)>| match files {
+                Some((start, end)) => format!("the win probability model fitted on match files {} through {}", start, end - 1),
+                None => "no win probability model".to_string(),
+            };
+            return Err(format!(
+                "Saved records were collected with {}, but this run has {}; pass the same --wpa-model or delete {}",
+                describe(self.wpa_model), describe(fitted_files), ALL_TIME_RECORDS_STATE_PATH,
+            ));
+        }
+        self.win_probability = model;
+        Ok(self)
+    }
+
+    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        if !self.ingested.insert(match_id.clone()) {
+            return;
+        }
+
+        // Filter matches
+        if !is_record_match(match_log) {
+            return;
+        }
+
+        let lines = PlayerStatLines::from_match_log(match_log);
+        let participation = &lines.participation;
+
         // Insert records for each player
         for (player_idx, player) in match_log.players.iter().enumerate() {
             let player_name = player.name.clone();
 
-            // Find the player's Join event to determine their actual team
-            let player_events = EventsReader::new(player.events.clone())
-                .player_events(
-                    Team::from_usize(player.team).expect("Could not parse Team enum."),
-                    match_log.duration,
-                );
+            // A single game's stat line is filed under the result of the team the
+            // player spent the most time on. Career totals credit results by share.
+            let player_team = participation.primary_team(player_idx);
+            let is_win = lines.is_win(player_team);
 
-            let player_team = player_events.iter()
-                .find(|e| e.event_type == Event::Join)
-                .map(|e| e.team)
-                .unwrap_or(Team::from_usize(player.team).expect("Could not parse Team enum."));
+            // Insert records for this player
+            let full = &lines.full[player_idx];
+            let first8 = &lines.first8[player_idx];
 
-            // Determine if this player won
-            let is_win = match player_team {
-                Team::Red => cap_diff > 0,
-                Team::Blue => cap_diff < 0,
-                _ => false,
-            };
+            // Full game and first 8 minutes, as raw totals
+            Self::insert_stat_line(&mut self.full, &match_id, &player_name, full, is_win, |value| value);
+            Self::insert_stat_line(&mut self.first8, &match_id, &player_name, first8, is_win, |value| value);
 
-            // Insert records for this player
-            let full = &player_full_stats[player_idx];
-            let first8 = &player_first8_stats[player_idx];
-
-            // Basic stats - full game
-            Self::insert_with_win_loss(&mut self.full_all.caps, &mut self.full_wins.caps, &mut self.full_losses.caps,
-                match_id.clone(), player_name.clone(), full.caps, is_win);
-            Self::insert_with_win_loss(&mut self.full_all.returns, &mut self.full_wins.returns, &mut self.full_losses.returns,
-                match_id.clone(), player_name.clone(), full.returns, is_win);
-            Self::insert_with_win_loss(&mut self.full_all.tags, &mut self.full_wins.tags, &mut self.full_losses.tags,
-                match_id.clone(), player_name.clone(), full.tags, is_win);
-            Self::insert_with_win_loss(&mut self.full_all.pops, &mut self.full_wins.pops, &mut self.full_losses.pops,
-                match_id.clone(), player_name.clone(), full.pops, is_win);
-            Self::insert_with_win_loss(&mut self.full_all.grabs, &mut self.full_wins.grabs, &mut self.full_losses.grabs,
-                match_id.clone(), player_name.clone(), full.grabs, is_win);
-            Self::insert_with_win_loss(&mut self.full_all.pups, &mut self.full_wins.pups, &mut self.full_losses.pups,
-                match_id.clone(), player_name.clone(), full.pups, is_win);
-            Self::insert_with_win_loss(&mut self.full_all.quick_returns, &mut self.full_wins.quick_returns, &mut self.full_losses.quick_returns,
-                match_id.clone(), player_name.clone(), full.quick_returns, is_win);
-            Self::insert_with_win_loss(&mut self.full_all.flaccid_grabs, &mut self.full_wins.flaccid_grabs, &mut self.full_losses.flaccid_grabs,
-                match_id.clone(), player_name.clone(), full.flaccid_grabs, is_win);
-            Self::insert_with_win_loss(&mut self.full_all.hold, &mut self.full_wins.hold, &mut self.full_losses.hold,
-                match_id.clone(), player_name.clone(), full.hold / 60, is_win);
-            Self::insert_with_win_loss(&mut self.full_all.prevent, &mut self.full_wins.prevent, &mut self.full_losses.prevent,
-                match_id.clone(), player_name.clone(), full.prevent / 60, is_win);
-            Self::insert_with_win_loss(&mut self.full_all.button, &mut self.full_wins.button, &mut self.full_losses.button,
-                match_id.clone(), player_name.clone(), full.button / 60, is_win);
-
-            // Conditional stats - full game
-            if full.tags > 0 && full.pops == 0 {
-                Self::insert_with_win_loss(&mut self.full_all.tags_no_pops, &mut self.full_wins.tags_no_pops, &mut self.full_losses.tags_no_pops,
-                    match_id.clone(), player_name.clone(), full.tags, is_win);
-            }
-            if full.returns > 0 && full.grabs == 0 {
-                Self::insert_with_win_loss(&mut self.full_all.returns_no_grabs, &mut self.full_wins.returns_no_grabs, &mut self.full_losses.returns_no_grabs,
-                    match_id.clone(), player_name.clone(), full.returns, is_win);
-            }
-            if full.hold > 0 && full.returns == 0 {
-                Self::insert_with_win_loss(&mut self.full_all.hold_no_returns, &mut self.full_wins.hold_no_returns, &mut self.full_losses.hold_no_returns,
-                    match_id.clone(), player_name.clone(), full.hold / 60, is_win);
-            }
-            if full.caps > 0 && full.returns == 0 {
-                Self::insert_with_win_loss(&mut self.full_all.caps_no_returns, &mut self.full_wins.caps_no_returns, &mut self.full_losses.caps_no_returns,
-                    match_id.clone(), player_name.clone(), full.caps, is_win);
+            // Full game, as rates per minute played, for players who played long enough
+            if full.time_played >= (self.minimum_rate_minutes * 3600.0) as usize {
+                Self::insert_stat_line(&mut self.rate, &match_id, &player_name, full, is_win,
+                    |value| to_rate_key(full.per_minute(value)));
             }
+        }
 
-            // First 8 minutes stats
-            Self::insert_with_win_loss(&mut self.first8_all.caps, &mut self.first8_wins.caps, &mut self.first8_losses.caps,
-                match_id.clone(), player_name.clone(), first8.caps, is_win);
-            Self::insert_with_win_loss(&mut self.first8_all.returns, &mut self.first8_wins.returns, &mut self.first8_losses.returns,
-                match_id.clone(), player_name.clone(), first8.returns, is_win);
-            Self::insert_with_win_loss(&mut self.first8_all.tags, &mut self.first8_wins.tags, &mut self.first8_losses.tags,
-                match_id.clone(), player_name.clone(), first8.tags, is_win);
-            Self::insert_with_win_loss(&mut self.first8_all.pops, &mut self.first8_wins.pops, &mut self.first8_losses.pops,
-                match_id.clone(), player_name.clone(), first8.pops, is_win);
-            Self::insert_with_win_loss(&mut self.first8_all.grabs, &mut self.first8_wins.grabs, &mut self.first8_losses.grabs,
-                match_id.clone(), player_name.clone(), first8.grabs, is_win);
-            Self::insert_with_win_loss(&mut self.first8_all.pups, &mut self.first8_wins.pups, &mut self.first8_losses.pups,
-                match_id.clone(), player_name.clone(), first8.pups, is_win);
-            Self::insert_with_win_loss(&mut self.first8_all.quick_returns, &mut self.first8_wins.quick_returns, &mut self.first8_losses.quick_returns,
-                match_id.clone(), player_name.clone(), first8.quick_returns, is_win);
-            Self::insert_with_win_loss(&mut self.first8_all.flaccid_grabs, &mut self.first8_wins.flaccid_grabs, &mut self.first8_losses.flaccid_grabs,
-                match_id.clone(), player_name.clone(), first8.flaccid_grabs, is_win);
-            Self::insert_with_win_loss(&mut self.first8_all.hold, &mut self.first8_wins.hold, &mut self.first8_losses.hold,
-                match_id.clone(), player_name.clone(), first8.hold / 60, is_win);
-            Self::insert_with_win_loss(&mut self.first8_all.prevent, &mut self.first8_wins.prevent, &mut self.first8_losses.prevent,
-                match_id.clone(), player_name.clone(), first8.prevent / 60, is_win);
-            Self::insert_with_win_loss(&mut self.first8_all.button, &mut self.first8_wins.button, &mut self.first8_losses.button,
-                match_id.clone(), player_name.clone(), first8.button / 60, is_win);
-
-            // Conditional stats - first 8 minutes
-            if first8.tags > 0 && first8.pops == 0 {
-                Self::insert_with_win_loss(&mut self.first8_all.tags_no_pops, &mut self.first8_wins.tags_no_pops, &mut self.first8_losses.tags_no_pops,
-                    match_id.clone(), player_name.clone(), first8.tags, is_win);
-            }
-            if first8.returns > 0 && first8.grabs == 0 {
-                Self::insert_with_win_loss(&mut self.first8_all.returns_no_grabs, &mut self.first8_wins.returns_no_grabs, &mut self.first8_losses.returns_no_grabs,
-                    match_id.clone(), player_name.clone(), first8.returns, is_win);
-            }
-            if first8.hold > 0 && first8.returns == 0 {
-                Self::insert_with_win_loss(&mut self.first8_all.hold_no_returns, &mut self.first8_wins.hold_no_returns, &mut self.first8_losses.hold_no_returns,
-                    match_id.clone(), player_name.clone(), first8.hold / 60, is_win);
-            }
-            if first8.caps > 0 && first8.returns == 0 {
-                Self::insert_with_win_loss(&mut self.first8_all.caps_no_returns, &mut self.first8_wins.caps_no_returns, &mut self.first8_losses.caps_no_returns,
-                    match_id.clone(), player_name.clone(), first8.caps, is_win);
+        // Career win probability added
+        if let Some(model) = &self.win_probability {
+            let wpa = model.player_wpa(&MatchTimeline::from_match_log(match_log), match_log.players.len());
+            for (player_idx, player) in match_log.players.iter().enumerate() {
+                if participation.time_played(player_idx) == 0 {
+                    continue;
+                }
+                let career = self.career_wpa.entry(player.name.clone()).or_insert((0.0, 0.0));
+                career.0 += wpa[player_idx];
+                career.1 += lines.game_credit(player_idx);
             }
         }
     }
 
-    fn process_event(
-        &self,
-        event_type: Event,
-        time: usize,
-        stats: &mut PlayerGameStats,
-        red_grab_time: &mut Option<usize>,
-        blue_grab_time: &mut Option<usize>,
-        team: Team,
-        cutoff: usize,
+    // Insert every stat in a player's stat line, passing each value through
+    // `scale` first (identity for totals, per-minute rate for rate boards)
+    fn insert_stat_line(
+        leaderboards: &mut StatLeaderboards,
+        match_id: &str,
+        player_name: &str,
+        stats: &PlayerGameStats,
+        is_win: bool,
+        scale: impl Fn(usize) -> usize,
     ) {
-        Self::process_event_static(event_type, time, stats, red_grab_time, blue_grab_time, team, cutoff);
+        for (definition, board) in PLAYER_STATS.iter().zip(leaderboards.boards.iter_mut()) {
+            if let Some(value) = (definition.value)(stats) {
+                board.insert(scale(value), RecordEntry::player(match_id, player_name), is_win);
+            }
+        }
     }
 
-
     pub fn generate_report(&self, output_path: &str) {
         let mut file = File::create(output_path).expect("Could not create output file");
 
         writeln!(file, "=== ALL-TIME RANKED TAGPRO RECORDS ===\n").unwrap();
 
         // Full game records
-        self.write_section(&mut file, "FULL GAME RECORDS (Including Overtime)", &self.full_all, &self.full_wins, &self.full_losses);
+        self.write_section(&mut file, "FULL GAME RECORDS (Including Overtime)", &self.full, 1);
 
         // First 8 minutes records
-        self.write_section(&mut file, "FIRST 8 MINUTES RECORDS", &self.first8_all, &self.first8_wins, &self.first8_losses);
-    }
+        self.write_section(&mut file, "FIRST 8 MINUTES RECORDS", &self.first8, 1);
 
-    fn write_section(&self, file: &mut File, title: &str, all: &StatLeaderboards, wins: &StatLeaderboards, losses: &StatLeaderboards) {
-        writeln!(file, "## {}\n", title).unwrap();
-        self.write_stat_group_merged(file, all, wins, losses);
-    }
-
-    fn write_stat_group_merged(&self, file: &mut File, all: &StatLeaderboards, wins: &StatLeaderboards, losses: &StatLeaderboards) {
-        self.write_leaderboard_merged(file, "Captures", &all.caps, &wins.caps, &losses.caps);
-        self.write_leaderboard_merged(file, "Returns", &all.returns, &wins.returns, &losses.returns);
-        self.write_leaderboard_merged(file, "Tags", &all.tags, &wins.tags, &losses.tags);
-        self.write_leaderboard_merged(file, "Pops", &all.pops, &wins.pops, &losses.pops);
-        self.write_leaderboard_merged(file, "Grabs", &all.grabs, &wins.grabs, &losses.grabs);
-        self.write_leaderboard_merged(file, "Hold (seconds)", &all.hold, &wins.hold, &losses.hold);
-        self.write_leaderboard_merged(file, "Prevent (seconds)", &all.prevent, &wins.prevent, &losses.prevent);
-        self.write_leaderboard_merged(file, "Button Time (seconds)", &all.button, &wins.button, &losses.button);
-        self.write_leaderboard_merged(file, "Powerups", &all.pups, &wins.pups, &losses.pups);
-        self.write_leaderboard_merged(file, "Quick Returns", &all.quick_returns, &wins.quick_returns, &losses.quick_returns);
-        self.write_leaderboard_merged(file, "Flaccid Grabs", &all.flaccid_grabs, &wins.flaccid_grabs, &losses.flaccid_grabs);
-        self.write_leaderboard_merged(file, "Tags (No Pops)", &all.tags_no_pops, &wins.tags_no_pops, &losses.tags_no_pops);
-        self.write_leaderboard_merged(file, "Returns (No Grabs)", &all.returns_no_grabs, &wins.returns_no_grabs, &losses.returns_no_grabs);
-        self.write_leaderboard_merged(file, "Hold (No Returns, seconds)", &all.hold_no_returns, &wins.hold_no_returns, &losses.hold_no_returns);
-        self.write_leaderboard_merged(file, "Caps (No Returns)", &all.caps_no_returns, &wins.caps_no_returns, &losses.caps_no_returns);
-    }
-
-    fn get_top_n_with_status(map: &BTreeMap<usize, Vec<(String, String)>>, n: usize, is_win: bool) -> Vec<(String, String, usize, bool)> {
-        let mut results = Vec::new();
-        let mut current_rank = 1;
-
-        for (&value, players) in map.iter().rev() {
-            if value == 0 {
-                continue; // Skip zero values
-            }
+        // Per-minute records
+        let title = format!("PER-MINUTE RECORDS (Minimum {} Minutes Played)", self.minimum_rate_minutes);
+        self.write_section(&mut file, &title, &self.rate, RATE_SCALE);
 
-            // Only include this value tier if the rank is within top N
-            if current_rank > n {
-                break;
-            }
+        // Career win probability added
+        if self.tracks_wpa() {
+            self.write_career_wpa(&mut file);
+        }
+    }
 
-            for (match_id, player_name) in players {
-                results.push((match_id.clone(), player_name.clone(), value, is_win));
+    // Every board as structured rows, in report order
+    pub fn record_rows(&self) -> Vec<RecordRow> {
+        let mut rows = Vec::new();
+        for (scope, leaderboards, scale) in [(FULL_SCOPE, &self.full, 1), (FIRST8_SCOPE, &self.first8, 1), (RATE_SCOPE, &self.rate, RATE_SCALE)] {
+            for (definition, board) in PLAYER_STATS.iter().zip(leaderboards.boards.iter()) {
+                rows.extend(record_rows(definition.title, scope, &board.merged(), |value| value as f64 / scale as f64));
             }
-
-            // Next rank is current_rank + number of people at this value
-            current_rank += players.len();
         }
-
-        results
+        if self.tracks_wpa() {
+            rows.extend(self.career_wpa_rows());
+        }
+        rows
     }
 
-    fn write_leaderboard_merged(
-        &self,
-        file: &mut File,
-        title: &str,
-        _all_map: &BTreeMap<usize, Vec<(String, String)>>,
-        wins_map: &BTreeMap<usize, Vec<(String, String)>>,
-        losses_map: &BTreeMap<usize, Vec<(String, String)>>
-    ) {
-        writeln!(file, "### {}", title).unwrap();
+    fn tracks_wpa(&self) -> bool {
+        self.win_probability.is_some() || self.wpa_model.is_some()
+    }
 
-        // Get top 5 from wins and top 5 from losses separately
-        let mut top_wins = Self::get_top_n_with_status(wins_map, 5, true);
-        let mut top_losses = Self::get_top_n_with_status(losses_map, 5, false);
+    // Most and least career win probability added. The least board only takes
+    // careers the most board didn't.
+    fn career_wpa_boards(&self) -> [(&'static str, Vec<CareerWpa<'_>>); 2] {
+        let mut careers: Vec<CareerWpa> = self.career_wpa.iter().map(|(name, &(wpa, games))| (name, wpa, games)).collect();
+        careers.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let least_count = careers.len().saturating_sub(RECORDS_PER_BOARD).min(RECORDS_PER_BOARD);
+        let least = careers.iter().rev().take(least_count).copied().collect();
+        careers.truncate(RECORDS_PER_BOARD);
+        [("Most", careers), ("Least", least)]
+    }
 
-        // Merge the two lists
-        let mut results = Vec::new();
-        results.append(&mut top_wins);
-        results.append(&mut top_losses);
+    fn career_wpa_rows(&self) -> Vec<RecordRow> {
+        let mut rows = Vec::new();
+        for (label, careers) in self.career_wpa_boards() {
+            let stat = format!("{} Career Win Probability Added", label);
+            let mut rank = 0;
+            for (index, &(player_name, wpa, _)) in careers.iter().enumerate() {
+                if index == 0 || careers[index - 1].1 != wpa {
+                    rank = index + 1;
+                }
+                rows.push(RecordRow {
+                    stat: stat.clone(),
+                    scope: CAREER_SCOPE.to_string(),
+                    rank,
+                    match_id: String::new(),
+                    players: vec![player_name.clone()],
+                    value: wpa,
+                    result: None,
+                });
+            }
+        }
+        rows
+    }
 
-        // Sort by value descending, then by player name
-        results.sort_by(|a, b| {
-            b.2.cmp(&a.2).then_with(|| a.1.cmp(&b.1))
-        });
+    fn write_career_wpa(&self, file: &mut File) {
+        writeln!(file, "## CAREER WIN PROBABILITY ADDED\n").unwrap();
 
-        if results.is_empty() {
-            writeln!(file, "No records found.\n").unwrap();
-            return;
+        for (label, careers) in self.career_wpa_boards() {
+            writeln!(file, "{}:", label).unwrap();
+            for (rank, (player_name, wpa, games)) in careers.iter().enumerate() {
+                writeln!(file, "  {}. {}: {:.3} ({:.1} games)", rank + 1, player_name, wpa, games).unwrap();
+            }
         }
+        writeln!(file).unwrap();
+    }
+
+    fn write_section(&self, file: &mut File, title: &str, leaderboards: &StatLeaderboards, scale: usize) {
+        writeln!(file, "## {}\n", title).unwrap();
 
-        for (match_id, player_name, value, is_win) in results {
-            let status = if is_win { "Win" } else { "Loss" };
-            writeln!(
-                file,
-                "  Match {}: {} - {} ({})",
-                match_id, player_name, value, status
-            ).unwrap();
+        // Top 5 from wins and top 5 from losses, merged
+        for (definition, board) in PLAYER_STATS.iter().zip(leaderboards.boards.iter()) {
+            write_leaderboard(file, Some(&format!("### {}", definition.title)), &board.merged(), |row| {
+                format!("{} - {} ({})", row.players(), format_scaled(row.value as f64, scale), row.result_label())
+            });
         }
-        writeln!(file).unwrap();
     }
 }
 
-#[derive(Default)]
-struct TeamLeaderboards {
+// A team stat with its own leaderboard, from the team's and their opponents' totals
+struct TeamStatDefinition {
+    title: &'static str,
+    order: Order,
+    skip_zero: bool,
+    value: fn(&PlayerGameStats, &PlayerGameStats) -> isize,
+}
+
+const TEAM_STATS: &[TeamStatDefinition] = &[
     // Highs
-    caps: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    tags: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    returns: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    hold: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    prevent: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    pups: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    quick_returns: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    non_tag_pops: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    hold_differential: BTreeMap<isize, Vec<(String, Vec<String>)>>,
+    TeamStatDefinition { title: "Captures", order: Order::Highest, skip_zero: true, value: |team, _| team.caps as isize },
+    TeamStatDefinition { title: "Tags", order: Order::Highest, skip_zero: true, value: |team, _| team.tags as isize },
+    TeamStatDefinition { title: "Returns", order: Order::Highest, skip_zero: true, value: |team, _| team.returns as isize },
+    TeamStatDefinition { title: "Hold (seconds)", order: Order::Highest, skip_zero: true, value: |team, _| (team.hold / 60) as isize },
+    TeamStatDefinition { title: "Prevent (seconds)", order: Order::Highest, skip_zero: true, value: |team, _| (team.prevent / 60) as isize },
+    TeamStatDefinition { title: "Powerups", order: Order::Highest, skip_zero: true, value: |team, _| team.pups as isize },
+    TeamStatDefinition { title: "Quick Returns", order: Order::Highest, skip_zero: true, value: |team, _| team.quick_returns as isize },
+    // Non-tag pops: team pops minus opponent tags
+    TeamStatDefinition {
+        title: "Non-Tag Pops",
+        order: Order::Highest,
+        skip_zero: true,
+        value: |team, opponent| team.pops.saturating_sub(opponent.tags) as isize,
+    },
+    // Hold differential: team hold minus opponent hold (in seconds)
+    TeamStatDefinition {
+        title: "Hold Differential (seconds)",
+        order: Order::Highest,
+        skip_zero: false,
+        value: |team, opponent| (team.hold as isize - opponent.hold as isize) / 60,
+    },
 
     // Lows (for select stats)
-    tags_low: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    returns_low: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    hold_low: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    prevent_low: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    pups_low: BTreeMap<usize, Vec<(String, Vec<String>)>>,
+    TeamStatDefinition { title: "Tags", order: Order::Lowest, skip_zero: false, value: |team, _| team.tags as isize },
+    TeamStatDefinition { title: "Returns", order: Order::Lowest, skip_zero: false, value: |team, _| team.returns as isize },
+    TeamStatDefinition { title: "Hold (seconds)", order: Order::Lowest, skip_zero: false, value: |team, _| (team.hold / 60) as isize },
+    TeamStatDefinition { title: "Prevent (seconds)", order: Order::Lowest, skip_zero: false, value: |team, _| (team.prevent / 60) as isize },
+    TeamStatDefinition { title: "Powerups", order: Order::Lowest, skip_zero: false, value: |team, _| team.pups as isize },
+];
+
+// One win/loss board per entry in TEAM_STATS
+#[derive(Serialize, Deserialize)]
+struct TeamLeaderboards {
+    boards: Vec<SplitLeaderboard<isize, RecordEntry>>,
+}
+
+impl Default for TeamLeaderboards {
+    fn default() -> Self {
+        Self {
+            boards: TEAM_STATS.iter()
+                .map(|definition| {
+                    let board = SplitLeaderboard::new(definition.order, Cutoff::Ties(RECORDS_PER_BOARD));
+                    if definition.skip_zero { board.skipping_zero() } else { board }
+                })
+                .collect(),
+        }
+    }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TeamRecordsCollector {
-    full_wins: TeamLeaderboards,
-    full_losses: TeamLeaderboards,
-    first8_wins: TeamLeaderboards,
-    first8_losses: TeamLeaderboards,
+    ingested: BTreeSet<String>,
+    full: TeamLeaderboards,
+    first8: TeamLeaderboards,
+    rate: TeamLeaderboards,
+    minimum_rate_minutes: f64,
+}
+
+impl CollectorState for TeamRecordsCollector {}
+
+impl RecordCollector for TeamRecordsCollector {
+    const REPORT_NAME: &'static str = "team_records";
+    const TITLE: &'static str = "All-Time Ranked TagPro Team Records";
+
+    fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        Self::process_match(self, match_id, match_log);
+    }
+
+    fn generate_report(&self, output_path: &str) {
+        Self::generate_report(self, output_path);
+    }
+
+    fn record_rows(&self) -> Vec<RecordRow> {
+        Self::record_rows(self)
+    }
 }
 
 impl TeamRecordsCollector {
     pub fn new() -> Self {
         Self {
-            full_wins: TeamLeaderboards::default(),
-            full_losses: TeamLeaderboards::default(),
-            first8_wins: TeamLeaderboards::default(),
-            first8_losses: TeamLeaderboards::default(),
+            ingested: BTreeSet::new(),
+            full: TeamLeaderboards::default(),
+            first8: TeamLeaderboards::default(),
+            rate: TeamLeaderboards::default(),
+            minimum_rate_minutes: MINIMUM_RATE_MINUTES,
         }
     }
 
-    fn insert_team_record(map: &mut BTreeMap<usize, Vec<(String, Vec<String>)>>, match_id: String, team_players: Vec<String>, value: usize) {
-        map.entry(value).or_insert_with(Vec::new).push((match_id, team_players));
+    // Minimum minutes of team time (player-time divided by team size) for per-minute rates to count
+    pub fn with_minimum_rate_minutes(mut self, minutes: f64) -> Self {
+        self.minimum_rate_minutes = minutes;
+        self
     }
 
-    fn insert_team_record_signed(map: &mut BTreeMap<isize, Vec<(String, Vec<String>)>>, match_id: String, team_players: Vec<String>, value: isize) {
-        map.entry(value).or_insert_with(Vec::new).push((match_id, team_players));
+    // Per-minute rate over a team's actual time on the field
+    fn team_per_minute(team_stats: &PlayerGameStats, value: f64) -> f64 {
+        let team_minutes = team_stats.time_played as f64 / (TEAM_SIZE * 3600) as f64;
+        if team_minutes > 0.0 { value / team_minutes } else { 0.0 }
     }
 
     pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
-        // Filter matches
-        if !match_log.official
-            || match_log.players.len() < 8
-            || match_log.group != Some("".to_string())
-            || match_log.time_limit != 8.0
-            || match_log.duration < MINIMUM_RANKED_MATCH_LENGTH
-            || match_log.duration < MINIMUM_RECORD_MATCH_LENGTH  // Skip games under 90 seconds
-        {
+        if !self.ingested.insert(match_id.clone()) {
             return;
         }
 
-        let mut player_full_stats: Vec<PlayerGameStats> = vec![PlayerGameStats::default(); match_log.players.len()];
-        let mut player_first8_stats: Vec<PlayerGameStats> = vec![PlayerGameStats::default(); match_log.players.len()];
-
-        // Collect all events
-        #[derive(Clone)]
-        struct TimedEvent {
-            time: usize,
-            event_type: Event,
-            player_idx: usize,
-            team: Team,
-        }
-
-        let mut all_events = Vec::new();
-        let mut all_first8_events = Vec::new();
-
-        for (player_idx, player) in match_log.players.iter().enumerate() {
-            let player_events = EventsReader::new(player.events.clone())
-                .player_events(
-                    Team::from_usize(player.team).expect("Could not parse Team enum."),
-                    match_log.duration,
-                );
-
-            let team = Team::from_usize(player.team).expect("Could not parse Team enum.");
-
-            for event in player_events.iter() {
-                all_events.push(TimedEvent {
-                    time: event.time,
-                    event_type: event.event_type,
-                    player_idx,
-                    team,
-                });
-
-                if event.time <= EIGHT_MINUTES {
-                    all_first8_events.push(TimedEvent {
-                        time: event.time,
-                        event_type: event.event_type,
-                        player_idx,
-                        team,
-                    });
-                }
-            }
-        }
-
-        all_events.sort_by_key(|e| e.time);
-        all_first8_events.sort_by_key(|e| e.time);
-
-        // Process full game events
-        let mut red_grab_time: Option<usize> = None;
-        let mut blue_grab_time: Option<usize> = None;
-        let mut cap_diff: isize = 0;
-
-        for event in all_events.iter() {
-            RecordsCollector::process_event_static(
-                event.event_type,
-                event.time,
-                &mut player_full_stats[event.player_idx],
-                &mut red_grab_time,
-                &mut blue_grab_time,
-                event.team,
-                match_log.duration,
-            );
-
-            if event.event_type == Event::Capture {
-                match event.team {
-                    Team::Red => cap_diff += 1,
-                    Team::Blue => cap_diff -= 1,
-                    _ => {}
-                }
-            }
-        }
-
-        // Process first 8 minutes events
-        let mut red_grab_time_first8: Option<usize> = None;
-        let mut blue_grab_time_first8: Option<usize> = None;
-
-        for event in all_first8_events.iter() {
-            RecordsCollector::process_event_static(
-                event.event_type,
-                event.time,
-                &mut player_first8_stats[event.player_idx],
-                &mut red_grab_time_first8,
-                &mut blue_grab_time_first8,
-                event.team,
-                EIGHT_MINUTES,
-            );
+        // Filter matches
+        if !is_record_match(match_log) {
+            return;
         }
 
-        // Finalize time-based stats
-        for player_idx in 0..match_log.players.len() {
-            player_full_stats[player_idx].finalize_time_stats(match_log.duration, match_log.duration);
-            player_first8_stats[player_idx].finalize_time_stats(match_log.duration, EIGHT_MINUTES);
-        }
+        let lines = PlayerStatLines::from_match_log(match_log);
 
-        // Aggregate team stats - use Join event to determine actual team
+        // Aggregate team stats by each player's primary team
         let mut red_team_players = Vec::new();
         let mut blue_team_players = Vec::new();
         let mut red_full_stats = PlayerGameStats::default();
@@ -621,53 +795,63 @@ impl TeamRecordsCollector {
         let mut blue_first8_stats = PlayerGameStats::default();
 
         for (player_idx, player) in match_log.players.iter().enumerate() {
-            // Find the player's Join event to determine their actual team
-            let player_events = EventsReader::new(player.events.clone())
-                .player_events(
-                    Team::from_usize(player.team).expect("Could not parse Team enum."),
-                    match_log.duration,
-                );
-
-            // Find Join event
-            let player_team = player_events.iter()
-                .find(|e| e.event_type == Event::Join)
-                .map(|e| e.team)
-                .unwrap_or(Team::from_usize(player.team).expect("Could not parse Team enum."));
-
-            match player_team {
+            // Substitutes and switchers count for the team they spent the most time on
+            match lines.participation.primary_team(player_idx) {
                 Team::Red => {
                     red_team_players.push(player.name.clone());
-                    Self::add_stats(&mut red_full_stats, &player_full_stats[player_idx]);
-                    Self::add_stats(&mut red_first8_stats, &player_first8_stats[player_idx]);
+                    Self::add_stats(&mut red_full_stats, &lines.full[player_idx]);
+                    Self::add_stats(&mut red_first8_stats, &lines.first8[player_idx]);
                 }
                 Team::Blue => {
                     blue_team_players.push(player.name.clone());
-                    Self::add_stats(&mut blue_full_stats, &player_full_stats[player_idx]);
-                    Self::add_stats(&mut blue_first8_stats, &player_first8_stats[player_idx]);
+                    Self::add_stats(&mut blue_full_stats, &lines.full[player_idx]);
+                    Self::add_stats(&mut blue_first8_stats, &lines.first8[player_idx]);
                 }
                 _ => {}
             }
         }
 
+        // Team time counts everyone's time on that team, so a switcher's minutes
+        // on their other team aren't credited to their primary one
+        for (team, full_stats, first8_stats) in [(Team::Red, &mut red_full_stats, &mut red_first8_stats), (Team::Blue, &mut blue_full_stats, &mut blue_first8_stats)] {
+            full_stats.time_played = (0..match_log.players.len())
+                .map(|player_idx| lines.participation.time_on_team(player_idx, team))
+                .sum();
+            first8_stats.time_played = (0..match_log.players.len())
+                .map(|player_idx| lines.participation.time_on_team_between(player_idx, team, 0, EIGHT_MINUTES))
+                .sum();
+        }
+
         // Insert team records
-        let red_wins = cap_diff > 0;
-        let blue_wins = cap_diff < 0;
+        let red_wins = lines.is_win(Team::Red);
+        let blue_wins = lines.is_win(Team::Blue);
 
         // Full game records - Red team
-        Self::insert_team_stats(&mut self.full_wins, &mut self.full_losses,
-            match_id.clone(), red_team_players.clone(), &red_full_stats, &blue_full_stats, red_wins);
+        Self::insert_team_stats(&mut self.full,
+            match_id.clone(), red_team_players.clone(), &red_full_stats, &blue_full_stats, red_wins, |value| value);
 
         // Full game records - Blue team
-        Self::insert_team_stats(&mut self.full_wins, &mut self.full_losses,
-            match_id.clone(), blue_team_players.clone(), &blue_full_stats, &red_full_stats, blue_wins);
+        Self::insert_team_stats(&mut self.full,
+            match_id.clone(), blue_team_players.clone(), &blue_full_stats, &red_full_stats, blue_wins, |value| value);
 
         // First 8 minutes records - Red team
-        Self::insert_team_stats(&mut self.first8_wins, &mut self.first8_losses,
-            match_id.clone(), red_team_players.clone(), &red_first8_stats, &blue_first8_stats, red_wins);
+        Self::insert_team_stats(&mut self.first8,
+            match_id.clone(), red_team_players.clone(), &red_first8_stats, &blue_first8_stats, red_wins, |value| value);
 
         // First 8 minutes records - Blue team
-        Self::insert_team_stats(&mut self.first8_wins, &mut self.first8_losses,
-            match_id, blue_team_players, &blue_first8_stats, &red_first8_stats, blue_wins);
+        Self::insert_team_stats(&mut self.first8,
+            match_id.clone(), blue_team_players.clone(), &blue_first8_stats, &red_first8_stats, blue_wins, |value| value);
+
+        // Per-minute records, if both teams were on the field long enough
+        let minimum_team_time = (self.minimum_rate_minutes * (TEAM_SIZE * 3600) as f64) as usize;
+        if red_full_stats.time_played >= minimum_team_time && blue_full_stats.time_played >= minimum_team_time {
+            Self::insert_team_stats(&mut self.rate,
+                match_id.clone(), red_team_players, &red_full_stats, &blue_full_stats, red_wins,
+                |value| Self::team_per_minute(&red_full_stats, value) * RATE_SCALE as f64);
+            Self::insert_team_stats(&mut self.rate,
+                match_id, blue_team_players, &blue_full_stats, &red_full_stats, blue_wins,
+                |value| Self::team_per_minute(&blue_full_stats, value) * RATE_SCALE as f64);
+        }
     }
 
     fn add_stats(total: &mut PlayerGameStats, player: &PlayerGameStats) {
@@ -680,42 +864,24 @@ impl TeamRecordsCollector {
         total.quick_returns += player.quick_returns;
         total.hold += player.hold;
         total.prevent += player.prevent;
+        total.time_played += player.time_played;
     }
 
+    // Insert a team's stat line, passing each value through `scale` first
+    // (identity for totals, per-minute rate scaled by RATE_SCALE for rate boards)
     fn insert_team_stats(
-        wins: &mut TeamLeaderboards,
-        losses: &mut TeamLeaderboards,
+        leaderboards: &mut TeamLeaderboards,
         match_id: String,
         team_players: Vec<String>,
         team_stats: &PlayerGameStats,
         opponent_stats: &PlayerGameStats,
         is_win: bool,
+        scale: impl Fn(f64) -> f64,
     ) {
-        let board = if is_win { wins } else { losses };
-
-        // Highs
-        Self::insert_team_record(&mut board.caps, match_id.clone(), team_players.clone(), team_stats.caps);
-        Self::insert_team_record(&mut board.tags, match_id.clone(), team_players.clone(), team_stats.tags);
-        Self::insert_team_record(&mut board.returns, match_id.clone(), team_players.clone(), team_stats.returns);
-        Self::insert_team_record(&mut board.hold, match_id.clone(), team_players.clone(), team_stats.hold / 60);
-        Self::insert_team_record(&mut board.prevent, match_id.clone(), team_players.clone(), team_stats.prevent / 60);
-        Self::insert_team_record(&mut board.pups, match_id.clone(), team_players.clone(), team_stats.pups);
-        Self::insert_team_record(&mut board.quick_returns, match_id.clone(), team_players.clone(), team_stats.quick_returns);
-
-        // Non-tag pops: team pops minus opponent tags
-        let non_tag_pops = team_stats.pops.saturating_sub(opponent_stats.tags);
-        Self::insert_team_record(&mut board.non_tag_pops, match_id.clone(), team_players.clone(), non_tag_pops);
-
-        // Hold differential: team hold minus opponent hold (in seconds)
-        let hold_diff = (team_stats.hold as isize - opponent_stats.hold as isize) / 60;
-        Self::insert_team_record_signed(&mut board.hold_differential, match_id.clone(), team_players.clone(), hold_diff);
-
-        // Lows (for select stats)
-        Self::insert_team_record(&mut board.tags_low, match_id.clone(), team_players.clone(), team_stats.tags);
-        Self::insert_team_record(&mut board.returns_low, match_id.clone(), team_players.clone(), team_stats.returns);
-        Self::insert_team_record(&mut board.hold_low, match_id.clone(), team_players.clone(), team_stats.hold / 60);
-        Self::insert_team_record(&mut board.prevent_low, match_id.clone(), team_players.clone(), team_stats.prevent / 60);
-        Self::insert_team_record(&mut board.pups_low, match_id, team_players, team_stats.pups);
+        for (definition, board) in TEAM_STATS.iter().zip(leaderboards.boards.iter_mut()) {
+            let value = (definition.value)(team_stats, opponent_stats);
+            board.insert(scale(value as f64).round() as isize, RecordEntry::team(&match_id, team_players.clone()), is_win);
+        }
     }
 
     pub fn generate_report(&self, output_path: &str) {
@@ -724,204 +890,48 @@ impl TeamRecordsCollector {
         writeln!(file, "=== ALL-TIME RANKED TAGPRO TEAM RECORDS ===\n").unwrap();
 
         // Full game records
-        self.write_section(&mut file, "FULL GAME RECORDS (Including Overtime)", &self.full_wins, &self.full_losses);
+        self.write_section(&mut file, "FULL GAME RECORDS (Including Overtime)", &self.full, 1);
 
         // First 8 minutes records
-        self.write_section(&mut file, "FIRST 8 MINUTES RECORDS", &self.first8_wins, &self.first8_losses);
-    }
+        self.write_section(&mut file, "FIRST 8 MINUTES RECORDS", &self.first8, 1);
 
-    fn write_section(&self, file: &mut File, title: &str, wins: &TeamLeaderboards, losses: &TeamLeaderboards) {
-        writeln!(file, "## {}\n", title).unwrap();
-
-        writeln!(file, "### HIGHS\n").unwrap();
-        self.write_team_leaderboard(file, "Captures", &wins.caps, &losses.caps);
-        self.write_team_leaderboard(file, "Tags", &wins.tags, &losses.tags);
-        self.write_team_leaderboard(file, "Returns", &wins.returns, &losses.returns);
-        self.write_team_leaderboard(file, "Hold (seconds)", &wins.hold, &losses.hold);
-        self.write_team_leaderboard(file, "Prevent (seconds)", &wins.prevent, &losses.prevent);
-        self.write_team_leaderboard(file, "Powerups", &wins.pups, &losses.pups);
-        self.write_team_leaderboard(file, "Quick Returns", &wins.quick_returns, &losses.quick_returns);
-        self.write_team_leaderboard(file, "Non-Tag Pops", &wins.non_tag_pops, &losses.non_tag_pops);
-        self.write_team_leaderboard_signed(file, "Hold Differential (seconds)", &wins.hold_differential, &losses.hold_differential);
-
-        writeln!(file, "\n### LOWS\n").unwrap();
-        self.write_team_leaderboard_low(file, "Tags", &wins.tags_low, &losses.tags_low);
-        self.write_team_leaderboard_low(file, "Returns", &wins.returns_low, &losses.returns_low);
-        self.write_team_leaderboard_low(file, "Hold (seconds)", &wins.hold_low, &losses.hold_low);
-        self.write_team_leaderboard_low(file, "Prevent (seconds)", &wins.prevent_low, &losses.prevent_low);
-        self.write_team_leaderboard_low(file, "Powerups", &wins.pups_low, &losses.pups_low);
-    }
-
-    fn get_top_n_teams(map: &BTreeMap<usize, Vec<(String, Vec<String>)>>, n: usize, is_win: bool) -> Vec<(String, Vec<String>, usize, bool)> {
-        let mut results = Vec::new();
-        let mut current_rank = 1;
-
-        for (&value, teams) in map.iter().rev() {
-            if value == 0 {
-                continue;
-            }
-
-            if current_rank > n {
-                break;
-            }
-
-            for (match_id, team_players) in teams {
-                results.push((match_id.clone(), team_players.clone(), value, is_win));
-            }
-
-            current_rank += teams.len();
-        }
-
-        results
+        // Per-minute records
+        let title = format!("PER-MINUTE RECORDS (Minimum {} Minutes Played)", self.minimum_rate_minutes);
+        self.write_section(&mut file, &title, &self.rate, RATE_SCALE);
     }
 
-    fn get_bottom_n_teams(map: &BTreeMap<usize, Vec<(String, Vec<String>)>>, n: usize, is_win: bool) -> Vec<(String, Vec<String>, usize, bool)> {
-        let mut results = Vec::new();
-        let mut current_rank = 1;
-
-        for (&value, teams) in map.iter() {  // Iterate forward for lowest values
-            if current_rank > n {
-                break;
-            }
-
-            for (match_id, team_players) in teams {
-                results.push((match_id.clone(), team_players.clone(), value, is_win));
+    // Every board as structured rows, in report order
+    pub fn record_rows(&self) -> Vec<RecordRow> {
+        let mut rows = Vec::new();
+        for (scope, leaderboards, scale) in [(FULL_SCOPE, &self.full, 1), (FIRST8_SCOPE, &self.first8, 1), (RATE_SCOPE, &self.rate, RATE_SCALE)] {
+            for order in [Order::Highest, Order::Lowest] {
+                for (definition, board) in TEAM_STATS.iter().zip(leaderboards.boards.iter()) {
+                    if definition.order != order {
+                        continue;
+                    }
+                    let stat = format!("{} {}", order_label(order), definition.title);
+                    rows.extend(record_rows(&stat, scope, &board.merged(), |value| value as f64 / scale as f64));
+                }
             }
-
-            current_rank += teams.len();
         }
-
-        results
+        rows
     }
 
-    fn get_top_n_teams_signed(map: &BTreeMap<isize, Vec<(String, Vec<String>)>>, n: usize, is_win: bool) -> Vec<(String, Vec<String>, isize, bool)> {
-        let mut results = Vec::new();
-        let mut current_rank = 1;
-
-        for (&value, teams) in map.iter().rev() {
-            if current_rank > n {
-                break;
-            }
+    fn write_section(&self, file: &mut File, title: &str, leaderboards: &TeamLeaderboards, scale: usize) {
+        writeln!(file, "## {}\n", title).unwrap();
 
-            for (match_id, team_players) in teams {
-                results.push((match_id.clone(), team_players.clone(), value, is_win));
+        for (heading, order) in [("### HIGHS\n", Order::Highest), ("\n### LOWS\n", Order::Lowest)] {
+            writeln!(file, "{}", heading).unwrap();
+            for (definition, board) in TEAM_STATS.iter().zip(leaderboards.boards.iter()) {
+                if definition.order != order {
+                    continue;
+                }
+                // Top 5 from wins and top 5 from losses, merged
+                write_leaderboard(file, Some(&format!("#### {}", definition.title)), &board.merged(), |row| {
+                    format!("{} - {} ({})", row.players(), format_scaled(row.value as f64, scale), row.result_label())
+                });
             }
-
-            current_rank += teams.len();
-        }
-
-        results
-    }
-
-    fn write_team_leaderboard(
-        &self,
-        file: &mut File,
-        title: &str,
-        wins_map: &BTreeMap<usize, Vec<(String, Vec<String>)>>,
-        losses_map: &BTreeMap<usize, Vec<(String, Vec<String>)>>
-    ) {
-        writeln!(file, "#### {}", title).unwrap();
-
-        let mut top_wins = Self::get_top_n_teams(wins_map, 5, true);
-        let mut top_losses = Self::get_top_n_teams(losses_map, 5, false);
-
-        let mut results = Vec::new();
-        results.append(&mut top_wins);
-        results.append(&mut top_losses);
-
-        results.sort_by(|a, b| {
-            b.2.cmp(&a.2).then_with(|| a.1[0].cmp(&b.1[0]))
-        });
-
-        if results.is_empty() {
-            writeln!(file, "No records found.\n").unwrap();
-            return;
-        }
-
-        for (match_id, team_players, value, is_win) in results {
-            let status = if is_win { "Win" } else { "Loss" };
-            let players_str = team_players.join(", ");
-            writeln!(
-                file,
-                "  Match {}: {} - {} ({})",
-                match_id, players_str, value, status
-            ).unwrap();
-        }
-        writeln!(file).unwrap();
-    }
-
-    fn write_team_leaderboard_low(
-        &self,
-        file: &mut File,
-        title: &str,
-        wins_map: &BTreeMap<usize, Vec<(String, Vec<String>)>>,
-        losses_map: &BTreeMap<usize, Vec<(String, Vec<String>)>>
-    ) {
-        writeln!(file, "#### {}", title).unwrap();
-
-        let mut bottom_wins = Self::get_bottom_n_teams(wins_map, 5, true);
-        let mut bottom_losses = Self::get_bottom_n_teams(losses_map, 5, false);
-
-        let mut results = Vec::new();
-        results.append(&mut bottom_wins);
-        results.append(&mut bottom_losses);
-
-        results.sort_by(|a, b| {
-            a.2.cmp(&b.2).then_with(|| a.1[0].cmp(&b.1[0]))  // Sort ascending for lows
-        });
-
-        if results.is_empty() {
-            writeln!(file, "No records found.\n").unwrap();
-            return;
-        }
-
-        for (match_id, team_players, value, is_win) in results {
-            let status = if is_win { "Win" } else { "Loss" };
-            let players_str = team_players.join(", ");
-            writeln!(
-                file,
-                "  Match {}: {} - {} ({})",
-                match_id, players_str, value, status
-            ).unwrap();
-        }
-        writeln!(file).unwrap();
-    }
-
-    fn write_team_leaderboard_signed(
-        &self,
-        file: &mut File,
-        title: &str,
-        wins_map: &BTreeMap<isize, Vec<(String, Vec<String>)>>,
-        losses_map: &BTreeMap<isize, Vec<(String, Vec<String>)>>
-    ) {
-        writeln!(file, "#### {}", title).unwrap();
-
-        let mut top_wins = Self::get_top_n_teams_signed(wins_map, 5, true);
-        let mut top_losses = Self::get_top_n_teams_signed(losses_map, 5, false);
-
-        let mut results = Vec::new();
-        results.append(&mut top_wins);
-        results.append(&mut top_losses);
-
-        results.sort_by(|a, b| {
-            b.2.cmp(&a.2).then_with(|| a.1[0].cmp(&b.1[0]))
-        });
-
-        if results.is_empty() {
-            writeln!(file, "No records found.\n").unwrap();
-            return;
         }
-
-        for (match_id, team_players, value, is_win) in results {
-            let status = if is_win { "Win" } else { "Loss" };
-            let players_str = team_players.join(", ");
-            writeln!(
-                file,
-                "  Match {}: {} - {} ({})",
-                match_id, players_str, value, status
-            ).unwrap();
-        }
-        writeln!(file).unwrap();
     }
 }
 
@@ -1070,200 +1080,175 @@ impl RecordsCollector {
     }
 }
 
-pub fn collect_all_records(match_iterator: MatchIterator) {
-    let mut collector = RecordsCollector::new();
+// Career win probability added is only tracked when given a model
+pub fn collect_all_records(match_iterator: MatchIterator, win_probability: Option<Arc<WinProbabilityModel>>) {
+    let saved = match RecordsCollector::load_state(ALL_TIME_RECORDS_STATE_PATH) {
+        Ok(saved) => saved,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+    let previous = saved.as_ref().map(RecordsCollector::record_rows);
+    let collector = match saved {
+        Some(saved) => saved.resume_with_win_probability(win_probability),
+        None => Ok(match win_probability {
+            Some(model) => RecordsCollector::new().with_win_probability(model),
+            None => RecordsCollector::new(),
+        }),
+    };
+    let mut collector = match collector {
+        Ok(collector) => collector,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
 
     for (match_id, match_log) in match_iterator {
         collector.process_match(match_id, &match_log);
     }
 
+    collector.save_state(ALL_TIME_RECORDS_STATE_PATH);
     collector.generate_report("analysis/all_time_records.txt");
-    println!("Records collected! Output written to analysis/all_time_records.txt");
+    let rows = collector.record_rows();
+    write_record_outputs(&rows, RecordsCollector::TITLE, "analysis/all_time_records");
+    write_changes(previous, &rows, RecordsCollector::TITLE, "analysis/all_time_records_changes.txt");
+    println!("Records collected! Output written to analysis/all_time_records.{{txt,json,csv,md}}");
 }
 
-#[derive(Default)]
-struct CombinedGameLeaderboards {
+// A whole-game stat with its own leaderboard, from both teams' combined totals
+struct GameStatDefinition {
+    title: &'static str,
+    order: Order,
+    value: fn(&PlayerGameStats) -> usize,
+}
+
+const GAME_STATS: &[GameStatDefinition] = &[
     // Highs
-    tags: BTreeMap<usize, Vec<String>>,
-    returns: BTreeMap<usize, Vec<String>>,
-    hold: BTreeMap<usize, Vec<String>>,
-    prevent: BTreeMap<usize, Vec<String>>,
-    quick_returns: BTreeMap<usize, Vec<String>>,
-    non_tag_pops: BTreeMap<usize, Vec<String>>,
+    GameStatDefinition { title: "Tags", order: Order::Highest, value: |stats| stats.tags },
+    GameStatDefinition { title: "Returns", order: Order::Highest, value: |stats| stats.returns },
+    GameStatDefinition { title: "Hold (seconds)", order: Order::Highest, value: |stats| stats.hold / 60 },
+    GameStatDefinition { title: "Prevent (seconds)", order: Order::Highest, value: |stats| stats.prevent / 60 },
+    GameStatDefinition { title: "Quick Returns", order: Order::Highest, value: |stats| stats.quick_returns },
+    // Returns always pop the carrier, so pops beyond returns are drops and self-pops
+    GameStatDefinition { title: "Non-Tag Pops", order: Order::Highest, value: |stats| stats.pops.saturating_sub(stats.returns) },
 
     // Lows (excluding non-tag pops)
-    tags_low: BTreeMap<usize, Vec<String>>,
-    returns_low: BTreeMap<usize, Vec<String>>,
-    hold_low: BTreeMap<usize, Vec<String>>,
-    prevent_low: BTreeMap<usize, Vec<String>>,
-    quick_returns_low: BTreeMap<usize, Vec<String>>,
-}
-
-pub struct CombinedGameRecordsCollector {
-    full: CombinedGameLeaderboards,
-    first8: CombinedGameLeaderboards,
+    GameStatDefinition { title: "Tags", order: Order::Lowest, value: |stats| stats.tags },
+    GameStatDefinition { title: "Returns", order: Order::Lowest, value: |stats| stats.returns },
+    GameStatDefinition { title: "Hold (seconds)", order: Order::Lowest, value: |stats| stats.hold / 60 },
+    GameStatDefinition { title: "Prevent (seconds)", order: Order::Lowest, value: |stats| stats.prevent / 60 },
+    GameStatDefinition { title: "Quick Returns", order: Order::Lowest, value: |stats| stats.quick_returns },
+];
+
+// One board per entry in GAME_STATS. Zeroes only count as lows.
+#[derive(Serialize, Deserialize)]
+struct CombinedGameLeaderboards {
+    boards: Vec<Leaderboard<usize, RecordEntry>>,
 }
 
-impl CombinedGameRecordsCollector {
-    pub fn new() -> Self {
+impl Default for CombinedGameLeaderboards {
+    fn default() -> Self {
         Self {
-            full: CombinedGameLeaderboards::default(),
-            first8: CombinedGameLeaderboards::default(),
+            boards: GAME_STATS.iter()
+                .map(|definition| {
+                    let board = Leaderboard::new(definition.order, Cutoff::Ties(RECORDS_PER_BOARD));
+                    if definition.order == Order::Highest { board.skipping_zero() } else { board }
+                })
+                .collect(),
         }
     }
+}
 
-    fn insert_game_record(map: &mut BTreeMap<usize, Vec<String>>, match_id: String, value: usize) {
-        map.entry(value).or_insert_with(Vec::new).push(match_id);
-    }
-
-    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
-        // Filter matches
-        if !match_log.official
-            || match_log.players.len() < 8
-            || match_log.group != Some("".to_string())
-            || match_log.time_limit != 8.0
-            || match_log.duration < MINIMUM_RANKED_MATCH_LENGTH
-            || match_log.duration < MINIMUM_RECORD_MATCH_LENGTH  // Skip games under 90 seconds
-        {
-            return;
-        }
-
-        let mut player_full_stats: Vec<PlayerGameStats> = vec![PlayerGameStats::default(); match_log.players.len()];
-        let mut player_first8_stats: Vec<PlayerGameStats> = vec![PlayerGameStats::default(); match_log.players.len()];
-
-        // Collect all events
-        #[derive(Clone)]
-        struct TimedEvent {
-            time: usize,
-            event_type: Event,
-            player_idx: usize,
-            team: Team,
-        }
-
-        let mut all_events = Vec::new();
-        let mut all_first8_events = Vec::new();
-
-        for (player_idx, player) in match_log.players.iter().enumerate() {
-            let player_events = EventsReader::new(player.events.clone())
-                .player_events(
-                    Team::from_usize(player.team).expect("Could not parse Team enum."),
-                    match_log.duration,
-                );
+#[derive(Serialize, Deserialize)]
+pub struct CombinedGameRecordsCollector {
+    ingested: BTreeSet<String>,
+    full: CombinedGameLeaderboards,
+    first8: CombinedGameLeaderboards,
+    rate: CombinedGameLeaderboards,
+    minimum_rate_minutes: f64,
+}
 
-            let team = Team::from_usize(player.team).expect("Could not parse Team enum.");
+impl CollectorState for CombinedGameRecordsCollector {}
 
-            for event in player_events.iter() {
-                all_events.push(TimedEvent {
-                    time: event.time,
-                    event_type: event.event_type,
-                    player_idx,
-                    team,
-                });
+impl RecordCollector for CombinedGameRecordsCollector {
+    const REPORT_NAME: &'static str = "combined_game_records";
+    const TITLE: &'static str = "All-Time Ranked TagPro Combined Game Records";
 
-                if event.time <= EIGHT_MINUTES {
-                    all_first8_events.push(TimedEvent {
-                        time: event.time,
-                        event_type: event.event_type,
-                        player_idx,
-                        team,
-                    });
-                }
-            }
-        }
+    fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        Self::process_match(self, match_id, match_log);
+    }
 
-        all_events.sort_by_key(|e| e.time);
-        all_first8_events.sort_by_key(|e| e.time);
+    fn generate_report(&self, output_path: &str) {
+        Self::generate_report(self, output_path);
+    }
 
-        // Process full game events
-        let mut red_grab_time: Option<usize> = None;
-        let mut blue_grab_time: Option<usize> = None;
+    fn record_rows(&self) -> Vec<RecordRow> {
+        Self::record_rows(self)
+    }
+}
 
-        for event in all_events.iter() {
-            RecordsCollector::process_event_static(
-                event.event_type,
-                event.time,
-                &mut player_full_stats[event.player_idx],
-                &mut red_grab_time,
-                &mut blue_grab_time,
-                event.team,
-                match_log.duration,
-            );
+impl CombinedGameRecordsCollector {
+    pub fn new() -> Self {
+        Self {
+            ingested: BTreeSet::new(),
+            full: CombinedGameLeaderboards::default(),
+            first8: CombinedGameLeaderboards::default(),
+            rate: CombinedGameLeaderboards::default(),
+            minimum_rate_minutes: MINIMUM_RATE_MINUTES,
         }
+    }
 
-        // Process first 8 minutes events
-        let mut red_grab_time_first8: Option<usize> = None;
-        let mut blue_grab_time_first8: Option<usize> = None;
+    // Minimum minutes of game time (player-time divided by both rosters) for per-minute rates to count
+    pub fn with_minimum_rate_minutes(mut self, minutes: f64) -> Self {
+        self.minimum_rate_minutes = minutes;
+        self
+    }
 
-        for event in all_first8_events.iter() {
-            RecordsCollector::process_event_static(
-                event.event_type,
-                event.time,
-                &mut player_first8_stats[event.player_idx],
-                &mut red_grab_time_first8,
-                &mut blue_grab_time_first8,
-                event.team,
-                EIGHT_MINUTES,
-            );
+    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        if !self.ingested.insert(match_id.clone()) {
+            return;
         }
 
-        // Finalize time-based stats
-        for player_idx in 0..match_log.players.len() {
-            player_full_stats[player_idx].finalize_time_stats(match_log.duration, match_log.duration);
-            player_first8_stats[player_idx].finalize_time_stats(match_log.duration, EIGHT_MINUTES);
+        // Filter matches
+        if !is_record_match(match_log) {
+            return;
         }
 
+        let lines = PlayerStatLines::from_match_log(match_log);
+
         // Aggregate combined stats (all players across both teams)
         let mut combined_full_stats = PlayerGameStats::default();
         let mut combined_first8_stats = PlayerGameStats::default();
 
-        for player_idx in 0..match_log.players.len() {
-            TeamRecordsCollector::add_stats(&mut combined_full_stats, &player_full_stats[player_idx]);
-            TeamRecordsCollector::add_stats(&mut combined_first8_stats, &player_first8_stats[player_idx]);
+        for (full, first8) in lines.full.iter().zip(lines.first8.iter()) {
+            TeamRecordsCollector::add_stats(&mut combined_full_stats, full);
+            TeamRecordsCollector::add_stats(&mut combined_first8_stats, first8);
         }
 
         // Insert records
-        Self::insert_combined_stats(&mut self.full, match_id.clone(), &combined_full_stats);
-        Self::insert_combined_stats(&mut self.first8, match_id, &combined_first8_stats);
+        Self::insert_combined_stats(&mut self.full, match_id.clone(), &combined_full_stats, |value| value);
+        Self::insert_combined_stats(&mut self.first8, match_id.clone(), &combined_first8_stats, |value| value);
+
+        // Per-minute records over the game time actually played by both rosters
+        let game_minutes = combined_full_stats.time_played as f64 / (2 * TEAM_SIZE * 3600) as f64;
+        if game_minutes >= self.minimum_rate_minutes {
+            Self::insert_combined_stats(&mut self.rate, match_id, &combined_full_stats,
+                |value| to_rate_key(value as f64 / game_minutes));
+        }
     }
 
+    // Insert the combined stat line, passing each value through `scale` first
     fn insert_combined_stats(
-        board: &mut CombinedGameLeaderboards,
+        leaderboards: &mut CombinedGameLeaderboards,
         match_id: String,
         stats: &PlayerGameStats,
+        scale: impl Fn(usize) -> usize,
     ) {
-        // Highs
-        Self::insert_game_record(&mut board.tags, match_id.clone(), stats.tags);
-        Self::insert_game_record(&mut board.returns, match_id.clone(), stats.returns);
-        Self::insert_game_record(&mut board.hold, match_id.clone(), stats.hold / 60);
-        Self::insert_game_record(&mut board.prevent, match_id.clone(), stats.prevent / 60);
-        Self::insert_game_record(&mut board.quick_returns, match_id.clone(), stats.quick_returns);
-
-        // Non-tag pops: For combined, this doesn't make sense the same way, but we can calculate it as
-        // total pops across both teams. Actually, non-tag pops should be pops that weren't from tags.
-        // Since both teams combined: combined_pops - combined_tags doesn't make sense.
-        // Let me think... if we want non-tag pops for the game, it would be pops that happened
-        // due to spikes, OOB, etc. But we're aggregating both teams, so it's just total pops - total tags from opponent.
-        // Actually for a single team it's: team_pops - opponent_tags
-        // For combined game, we'd want: (red_pops - blue_tags) + (blue_pops - red_tags)
-        // But we don't have team separation here anymore.
-        // I think the user wants total non-tag pops across the game, which would be situations where
-        // the flag was dropped without a tag. But in our data model, we only track individual stats.
-        // Let me re-read the requirement... "non-tag pops" for combined game.
-        // I think it might just be: total pops that weren't from a Return event.
-        // But our pops include both Drop and Pop events, and tags include both Return and Tag events.
-        // Returns are a subset of tags. Pops can come from Returns (which are tags).
-        // So non-tag pops would be: pops that happened without a corresponding tag/return.
-        // For the game total, I think we can just use: total_pops - total_returns
-        // Since returns always result in a pop, and non-return pops are drops/self-pops.
-        let non_tag_pops = stats.pops.saturating_sub(stats.returns);
-        Self::insert_game_record(&mut board.non_tag_pops, match_id.clone(), non_tag_pops);
-
-        // Lows
-        Self::insert_game_record(&mut board.tags_low, match_id.clone(), stats.tags);
-        Self::insert_game_record(&mut board.returns_low, match_id.clone(), stats.returns);
-        Self::insert_game_record(&mut board.hold_low, match_id.clone(), stats.hold / 60);
-        Self::insert_game_record(&mut board.prevent_low, match_id.clone(), stats.prevent / 60);
-        Self::insert_game_record(&mut board.quick_returns_low, match_id, stats.quick_returns);
+        for (definition, board) in GAME_STATS.iter().zip(leaderboards.boards.iter_mut()) {
+            board.insert(scale((definition.value)(stats)), RecordEntry::game(&match_id));
+        }
     }
 
     pub fn generate_report(&self, output_path: &str) {
@@ -1272,260 +1257,247 @@ impl CombinedGameRecordsCollector {
         writeln!(file, "=== ALL-TIME RANKED TAGPRO COMBINED GAME RECORDS ===\n").unwrap();
 
         // Full game records
-        self.write_section(&mut file, "FULL GAME RECORDS (Including Overtime)", &self.full);
+        self.write_section(&mut file, "FULL GAME RECORDS (Including Overtime)", &self.full, 1);
 
         // First 8 minutes records
-        self.write_section(&mut file, "FIRST 8 MINUTES RECORDS", &self.first8);
-    }
-
-    fn write_section(&self, file: &mut File, title: &str, board: &CombinedGameLeaderboards) {
-        writeln!(file, "## {}\n", title).unwrap();
-
-        writeln!(file, "### HIGHS\n").unwrap();
-        self.write_game_leaderboard(file, "Tags", &board.tags);
-        self.write_game_leaderboard(file, "Returns", &board.returns);
-        self.write_game_leaderboard(file, "Hold (seconds)", &board.hold);
-        self.write_game_leaderboard(file, "Prevent (seconds)", &board.prevent);
-        self.write_game_leaderboard(file, "Quick Returns", &board.quick_returns);
-        self.write_game_leaderboard(file, "Non-Tag Pops", &board.non_tag_pops);
-
-        writeln!(file, "\n### LOWS\n").unwrap();
-        self.write_game_leaderboard_low(file, "Tags", &board.tags_low);
-        self.write_game_leaderboard_low(file, "Returns", &board.returns_low);
-        self.write_game_leaderboard_low(file, "Hold (seconds)", &board.hold_low);
-        self.write_game_leaderboard_low(file, "Prevent (seconds)", &board.prevent_low);
-        self.write_game_leaderboard_low(file, "Quick Returns", &board.quick_returns_low);
-    }
-
-    fn get_top_n_games(map: &BTreeMap<usize, Vec<String>>, n: usize) -> Vec<(String, usize)> {
-        let mut results = Vec::new();
-        let mut current_rank = 1;
-
-        for (&value, match_ids) in map.iter().rev() {
-            if value == 0 {
-                continue;
-            }
-
-            if current_rank > n {
-                break;
-            }
+        self.write_section(&mut file, "FIRST 8 MINUTES RECORDS", &self.first8, 1);
 
-            for match_id in match_ids {
-                results.push((match_id.clone(), value));
-            }
-
-            current_rank += match_ids.len();
-        }
-
-        results
+        // Per-minute records
+        let title = format!("PER-MINUTE RECORDS (Minimum {} Minutes Played)", self.minimum_rate_minutes);
+        self.write_section(&mut file, &title, &self.rate, RATE_SCALE);
     }
 
-    fn get_bottom_n_games(map: &BTreeMap<usize, Vec<String>>, n: usize) -> Vec<(String, usize)> {
-        let mut results = Vec::new();
-        let mut current_rank = 1;
-
-        for (&value, match_ids) in map.iter() {
-            if current_rank > n {
-                break;
-            }
-
-            for match_id in match_ids {
-                results.push((match_id.clone(), value));
+    // Every board as structured rows, in report order
+    pub fn record_rows(&self) -> Vec<RecordRow> {
+        let mut rows = Vec::new();
+        for (scope, leaderboards, scale) in [(FULL_SCOPE, &self.full, 1), (FIRST8_SCOPE, &self.first8, 1), (RATE_SCOPE, &self.rate, RATE_SCALE)] {
+            for order in [Order::Highest, Order::Lowest] {
+                for (definition, board) in GAME_STATS.iter().zip(leaderboards.boards.iter()) {
+                    if definition.order != order {
+                        continue;
+                    }
+                    let stat = format!("{} {}", order_label(order), definition.title);
+                    rows.extend(record_rows(&stat, scope, &board.top(), |value| value as f64 / scale as f64));
+                }
             }
-
-            current_rank += match_ids.len();
-        }
-
-        results
-    }
-
-    fn write_game_leaderboard(&self, file: &mut File, title: &str, map: &BTreeMap<usize, Vec<String>>) {
-        writeln!(file, "#### {}", title).unwrap();
-
-        let results = Self::get_top_n_games(map, 5);
-
-        if results.is_empty() {
-            writeln!(file, "No records found.\n").unwrap();
-            return;
-        }
-
-        for (match_id, value) in results {
-            writeln!(file, "  Match {}: {}", match_id, value).unwrap();
         }
-        writeln!(file).unwrap();
+        rows
     }
 
-    fn write_game_leaderboard_low(&self, file: &mut File, title: &str, map: &BTreeMap<usize, Vec<String>>) {
-        writeln!(file, "#### {}", title).unwrap();
-
-        let results = Self::get_bottom_n_games(map, 5);
-
-        if results.is_empty() {
-            writeln!(file, "No records found.\n").unwrap();
-            return;
-        }
+    fn write_section(&self, file: &mut File, title: &str, leaderboards: &CombinedGameLeaderboards, scale: usize) {
+        writeln!(file, "## {}\n", title).unwrap();
 
-        for (match_id, value) in results {
-            writeln!(file, "  Match {}: {}", match_id, value).unwrap();
+        for (heading, order) in [("### HIGHS\n", Order::Highest), ("\n### LOWS\n", Order::Lowest)] {
+            writeln!(file, "{}", heading).unwrap();
+            for (definition, board) in GAME_STATS.iter().zip(leaderboards.boards.iter()) {
+                if definition.order != order {
+                    continue;
+                }
+                write_leaderboard(file, Some(&format!("#### {}", definition.title)), &board.top(), |row| {
+                    format_scaled(row.value as f64, scale)
+                });
+            }
         }
-        writeln!(file).unwrap();
     }
 }
 
 pub fn collect_team_records(match_iterator: MatchIterator) {
-    let mut collector = TeamRecordsCollector::new();
+    let saved = match TeamRecordsCollector::load_state(TEAM_RECORDS_STATE_PATH) {
+        Ok(saved) => saved,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+    let previous = saved.as_ref().map(TeamRecordsCollector::record_rows);
+    let mut collector = saved.unwrap_or_else(TeamRecordsCollector::new);
 
     for (match_id, match_log) in match_iterator {
         collector.process_match(match_id, &match_log);
     }
 
+    collector.save_state(TEAM_RECORDS_STATE_PATH);
     collector.generate_report("analysis/team_records.txt");
-    println!("Team records collected! Output written to analysis/team_records.txt");
+    let rows = collector.record_rows();
+    write_record_outputs(&rows, TeamRecordsCollector::TITLE, "analysis/team_records");
+    write_changes(previous, &rows, TeamRecordsCollector::TITLE, "analysis/team_records_changes.txt");
+    println!("Team records collected! Output written to analysis/team_records.{{txt,json,csv,md}}");
 }
 
 pub fn collect_combined_game_records(match_iterator: MatchIterator) {
-    let mut collector = CombinedGameRecordsCollector::new();
+    let saved = match CombinedGameRecordsCollector::load_state(COMBINED_GAME_RECORDS_STATE_PATH) {
+        Ok(saved) => saved,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+    let previous = saved.as_ref().map(CombinedGameRecordsCollector::record_rows);
+    let mut collector = saved.unwrap_or_else(CombinedGameRecordsCollector::new);
 
     for (match_id, match_log) in match_iterator {
         collector.process_match(match_id, &match_log);
     }
 
+    collector.save_state(COMBINED_GAME_RECORDS_STATE_PATH);
     collector.generate_report("analysis/combined_game_records.txt");
-    println!("Combined game records collected! Output written to analysis/combined_game_records.txt");
+    let rows = collector.record_rows();
+    write_record_outputs(&rows, CombinedGameRecordsCollector::TITLE, "analysis/combined_game_records");
+    write_changes(previous, &rows, CombinedGameRecordsCollector::TITLE, "analysis/combined_game_records_changes.txt");
+    println!("Combined game records collected! Output written to analysis/combined_game_records.{{txt,json,csv,md}}");
 }
 
 // Cap Runs and Comebacks Tracking
 
-#[derive(Default)]
+// Run lengths and deficits that get their own boards
+const CAP_RUN_LENGTHS: [usize; 4] = [2, 3, 4, 5];
+const COMEBACK_DEFICITS: [usize; 3] = [2, 3, 4];
+
+// Run duration in ticks, one board per entry in CAP_RUN_LENGTHS
+#[derive(Serialize, Deserialize)]
 struct CapRunsLeaderboards {
-    fastest_2cap: BTreeMap<usize, Vec<(String, Vec<String>)>>, // time in ticks -> (match_id, team_players)
-    fastest_3cap: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    fastest_4cap: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    fastest_5cap: BTreeMap<usize, Vec<(String, Vec<String>)>>,
+    fastest: Vec<Leaderboard<usize, RecordEntry>>,
+}
+
+impl Default for CapRunsLeaderboards {
+    fn default() -> Self {
+        Self {
+            fastest: CAP_RUN_LENGTHS.iter()
+                .map(|_| Leaderboard::new(Order::Lowest, Cutoff::Exact(RECORDS_PER_BOARD)))
+                .collect(),
+        }
+    }
 }
 
-#[derive(Default)]
+// Time of the deficit in ticks, one board per entry in COMEBACK_DEFICITS
+#[derive(Serialize, Deserialize)]
 struct ComebackLeaderboards {
-    latest_2cap_comeback_win: BTreeMap<usize, Vec<(String, Vec<String>)>>, // time in ticks -> (match_id, team_players)
-    latest_2cap_comeback_loss: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    latest_3cap_comeback_win: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    latest_3cap_comeback_loss: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    latest_4cap_comeback_win: BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    latest_4cap_comeback_loss: BTreeMap<usize, Vec<(String, Vec<String>)>>,
+    latest: Vec<SplitLeaderboard<usize, RecordEntry>>,
 }
 
-#[derive(Default)]
+impl Default for ComebackLeaderboards {
+    fn default() -> Self {
+        Self {
+            latest: COMEBACK_DEFICITS.iter()
+                .map(|_| SplitLeaderboard::new(Order::Highest, Cutoff::Exact(RECORDS_PER_BOARD)))
+                .collect(),
+        }
+    }
+}
+
+// Duration in ticks
+#[derive(Serialize, Deserialize)]
 struct ShortestGameLeaderboard {
-    games: BTreeMap<usize, Vec<String>>, // duration in ticks -> match_ids
+    games: Leaderboard<usize, RecordEntry>,
 }
 
-pub struct CapRunsAndComebacksCollector {
-    cap_runs: CapRunsLeaderboards,
-    comebacks: ComebackLeaderboards,
-    shortest_games: ShortestGameLeaderboard,
+impl Default for ShortestGameLeaderboard {
+    fn default() -> Self {
+        Self { games: Leaderboard::new(Order::Lowest, Cutoff::Exact(RECORDS_PER_BOARD)) }
+    }
 }
 
-impl CapRunsAndComebacksCollector {
-    pub fn new() -> Self {
+// Each team's players and the final result, worked out once per match.
+// Substitutes and switchers count for the team they spent the most time on.
+struct MatchTeams {
+    participation: Participation,
+    // Sorted names
+    red_players: Vec<String>,
+    blue_players: Vec<String>,
+    // Red caps minus blue caps
+    cap_diff: isize,
+}
+
+impl MatchTeams {
+    fn from_match_log(match_log: &MatchLog) -> Self {
+        let participation = Participation::from_match_log(match_log);
+        let names = |team: Team| -> Vec<String> {
+            let mut players: Vec<String> = participation.roster(team).into_iter()
+                .map(|player_idx| match_log.players[player_idx].name.clone())
+                .collect();
+            players.sort();
+            players
+        };
         Self {
-            cap_runs: CapRunsLeaderboards::default(),
-            comebacks: ComebackLeaderboards::default(),
-            shortest_games: ShortestGameLeaderboard::default(),
+            red_players: names(Team::Red),
+            blue_players: names(Team::Blue),
+            cap_diff: match_log.teams[0].score as isize - match_log.teams[1].score as isize,
+            participation,
         }
     }
 
-    fn insert_cap_run_record(
-        board: &mut BTreeMap<usize, Vec<(String, Vec<String>)>>,
-        duration: usize,
-        match_id: String,
-        team_players: Vec<String>,
-    ) {
-        board
-            .entry(duration)
-            .or_insert_with(Vec::new)
-            .push((match_id, team_players));
-    }
-
-    fn insert_comeback_record(
-        board: &mut BTreeMap<usize, Vec<(String, Vec<String>)>>,
-        time: usize,
-        match_id: String,
-        team_players: Vec<String>,
-    ) {
-        board
-            .entry(time)
-            .or_insert_with(Vec::new)
-            .push((match_id, team_players));
+    fn players(&self, team: Team) -> &[String] {
+        match team {
+            Team::Red => &self.red_players,
+            Team::Blue => &self.blue_players,
+            Team::None => &[],
+        }
     }
 
-    fn get_team_players(&self, match_log: &MatchLog, team: Team) -> Vec<String> {
-        let mut players = Vec::new();
-        for player in &match_log.players {
-            // Find the player's Join event to determine their actual team
-            let player_events = EventsReader::new(player.events.clone())
-                .player_events(
-                    Team::from_usize(player.team).expect("Could not parse Team enum."),
-                    match_log.duration,
-                );
-
-            let player_team = player_events
-                .iter()
-                .find(|e| e.event_type == Event::Join)
-                .map(|e| e.team)
-                .unwrap_or(Team::from_usize(player.team).expect("Could not parse Team enum."));
-
-            if player_team == team {
-                players.push(player.name.clone());
-            }
+    fn won(&self, team: Team) -> bool {
+        match team {
+            Team::Red => self.cap_diff > 0,
+            Team::Blue => self.cap_diff < 0,
+            Team::None => false,
         }
-        players.sort();
-        players
     }
 
-    fn all_opponents_present(
-        &self,
-        match_log: &MatchLog,
-        team: Team,
-        start_time: usize,
-        _end_time: usize,
-    ) -> bool {
-        let opponent_team = match team {
+    // Whether `team`'s opponents had a full team on the field from `start` to `end`.
+    // Substitutes count, so a run against a team that swapped a player still qualifies.
+    fn opponents_full(&self, team: Team, start: usize, end: usize) -> bool {
+        let opponent = match team {
             Team::Red => Team::Blue,
             Team::Blue => Team::Red,
-            Team::None => return false, // Invalid team
+            Team::None => return false,
         };
+        // Opponent numbers can only drop when someone leaves, so check the
+        // start and every departure during the run
+        let departures = self.participation.intervals.iter().flatten()
+            .filter(|interval| interval.team == opponent && start < interval.end && interval.end < end)
+            .map(|interval| interval.end);
+        std::iter::once(start).chain(departures).all(|time| {
+            (0..self.participation.intervals.len())
+                .filter(|&player_idx| self.participation.is_on_team_at(player_idx, opponent, time))
+                .count() >= TEAM_SIZE
+        })
+    }
+}
 
-        let mut opponent_count = 0;
-        for player in &match_log.players {
-            let player_events = EventsReader::new(player.events.clone())
-                .player_events(
-                    Team::from_usize(player.team).expect("Could not parse Team enum."),
-                    match_log.duration,
-                );
+#[derive(Serialize, Deserialize)]
+pub struct CapRunsAndComebacksCollector {
+    ingested: BTreeSet<String>,
+    cap_runs: CapRunsLeaderboards,
+    comebacks: ComebackLeaderboards,
+    shortest_games: ShortestGameLeaderboard,
+}
 
-            let player_team = player_events
-                .iter()
-                .find(|e| e.event_type == Event::Join)
-                .map(|e| e.team)
-                .unwrap_or(Team::from_usize(player.team).expect("Could not parse Team enum."));
-
-            if player_team == opponent_team {
-                // Check if player was present during [start_time, end_time]
-                let join_time = player_events
-                    .iter()
-                    .find(|e| e.event_type == Event::Join)
-                    .map(|e| e.time)
-                    .unwrap_or(0);
-
-                // Player must have joined before the run started
-                if join_time <= start_time {
-                    opponent_count += 1;
-                }
-            }
-        }
+impl CollectorState for CapRunsAndComebacksCollector {}
 
-        // There should be exactly 4 opponents
-        opponent_count == 4
+impl RecordCollector for CapRunsAndComebacksCollector {
+    const REPORT_NAME: &'static str = "cap_runs_and_comebacks";
+    const TITLE: &'static str = "Cap Runs, Comebacks, and Shortest Games";
+
+    fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        Self::process_match(self, match_id, match_log);
+    }
+
+    fn generate_report(&self, output_path: &str) {
+        Self::generate_report(self, output_path);
+    }
+
+    fn record_rows(&self) -> Vec<RecordRow> {
+        Self::record_rows(self)
+    }
+}
+
+impl CapRunsAndComebacksCollector {
+    pub fn new() -> Self {
+        Self {
+            ingested: BTreeSet::new(),
+            cap_runs: CapRunsLeaderboards::default(),
+            comebacks: ComebackLeaderboards::default(),
+            shortest_games: ShortestGameLeaderboard::default(),
+        }
     }
 
     fn all_players_present_whole_game(&self, match_log: &MatchLog) -> bool {
@@ -1555,6 +1527,10 @@ impl CapRunsAndComebacksCollector {
     }
 
     pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        if !self.ingested.insert(match_id.clone()) {
+            return;
+        }
+
         // Filter matches - use same criteria as other record collection
         if !match_log.official
             || match_log.players.len() < 8
@@ -1564,6 +1540,9 @@ impl CapRunsAndComebacksCollector {
             return;
         }
 
+        // Substitutes and switchers count for the team they spent the most time on
+        let teams = MatchTeams::from_match_log(match_log);
+
         // Collect all capture events (excluding flagless caps)
         let mut captures: Vec<(usize, Team)> = Vec::new();
 
@@ -1581,18 +1560,14 @@ impl CapRunsAndComebacksCollector {
 
         let mut all_events = Vec::new();
 
-        for player in &match_log.players {
+        for (player_idx, player) in match_log.players.iter().enumerate() {
             let player_events = EventsReader::new(player.events.clone())
                 .player_events(
                     Team::from_usize(player.team).expect("Could not parse Team enum."),
                     match_log.duration,
                 );
 
-            let player_team = player_events
-                .iter()
-                .find(|e| e.event_type == Event::Join)
-                .map(|e| e.team)
-                .unwrap_or(Team::from_usize(player.team).expect("Could not parse Team enum."));
+            let player_team = teams.participation.primary_team(player_idx);
 
             for event in player_events.iter() {
                 all_events.push(TimedTeamEvent {
@@ -1645,26 +1620,22 @@ impl CapRunsAndComebacksCollector {
         // Process cap runs and comebacks only if game meets duration requirement
         if match_log.duration >= MINIMUM_RECORD_MATCH_LENGTH {
             // Process cap runs
-            self.process_cap_runs(&match_id, match_log, &captures);
+            self.process_cap_runs(&match_id, &teams, &captures);
 
             // Process comebacks
-            self.process_comebacks(&match_id, match_log, &captures);
+            self.process_comebacks(&match_id, &teams, &captures);
         }
 
         // Process shortest full-participation game (no duration minimum)
         if self.all_players_present_whole_game(match_log) {
-            self.shortest_games
-                .games
-                .entry(match_log.duration)
-                .or_insert_with(Vec::new)
-                .push(match_id.clone());
+            self.shortest_games.games.insert(match_log.duration, RecordEntry::game(&match_id));
         }
     }
 
     fn process_cap_runs(
         &mut self,
         match_id: &str,
-        match_log: &MatchLog,
+        teams: &MatchTeams,
         captures: &[(usize, Team)],
     ) {
         if captures.is_empty() {
@@ -1688,46 +1659,17 @@ impl CapRunsAndComebacksCollector {
                 let run_end_time = captures[j - 1].0;
                 let run_duration = run_end_time - run_start_time;
 
-                // Verify all opponents were present during the run
-                if !self.all_opponents_present(match_log, run_team, run_start_time, run_end_time) {
+                // Verify the opponents had a full team on the field for the whole run
+                if !teams.opponents_full(run_team, run_start_time, run_end_time) {
                     i = j;
                     continue;
                 }
 
-                let team_players = self.get_team_players(match_log, run_team);
-
                 // Record different run lengths
-                if run_length >= 2 {
-                    Self::insert_cap_run_record(
-                        &mut self.cap_runs.fastest_2cap,
-                        run_duration,
-                        match_id.to_string(),
-                        team_players.clone(),
-                    );
-                }
-                if run_length >= 3 {
-                    Self::insert_cap_run_record(
-                        &mut self.cap_runs.fastest_3cap,
-                        run_duration,
-                        match_id.to_string(),
-                        team_players.clone(),
-                    );
-                }
-                if run_length >= 4 {
-                    Self::insert_cap_run_record(
-                        &mut self.cap_runs.fastest_4cap,
-                        run_duration,
-                        match_id.to_string(),
-                        team_players.clone(),
-                    );
-                }
-                if run_length >= 5 {
-                    Self::insert_cap_run_record(
-                        &mut self.cap_runs.fastest_5cap,
-                        run_duration,
-                        match_id.to_string(),
-                        team_players.clone(),
-                    );
+                for (&length, board) in CAP_RUN_LENGTHS.iter().zip(self.cap_runs.fastest.iter_mut()) {
+                    if run_length >= length {
+                        board.insert(run_duration, RecordEntry::team(match_id, teams.players(run_team).to_vec()));
+                    }
                 }
             }
 
@@ -1738,20 +1680,16 @@ impl CapRunsAndComebacksCollector {
     fn process_comebacks(
         &mut self,
         match_id: &str,
-        match_log: &MatchLog,
+        teams: &MatchTeams,
         captures: &[(usize, Team)],
     ) {
         // Track score over time and find latest comebacks
         let mut red_score = 0;
         let mut blue_score = 0;
 
-        // Track latest time each team was down by 2/3/4
-        let mut red_latest_down_2 = None;
-        let mut red_latest_down_3 = None;
-        let mut red_latest_down_4 = None;
-        let mut blue_latest_down_2 = None;
-        let mut blue_latest_down_3 = None;
-        let mut blue_latest_down_4 = None;
+        // Latest time each team was down by each deficit in COMEBACK_DEFICITS
+        let mut red_latest_down = [None; COMEBACK_DEFICITS.len()];
+        let mut blue_latest_down = [None; COMEBACK_DEFICITS.len()];
 
         for (time, team) in captures {
             if *team == Team::Red {
@@ -1763,252 +1701,33 @@ impl CapRunsAndComebacksCollector {
             let diff = red_score as isize - blue_score as isize;
 
             // Update latest deficit times
-            if diff == -2 {
-                red_latest_down_2 = Some(*time);
-            } else if diff == -3 {
-                red_latest_down_3 = Some(*time);
-            } else if diff == -4 {
-                red_latest_down_4 = Some(*time);
-            } else if diff == 2 {
-                blue_latest_down_2 = Some(*time);
-            } else if diff == 3 {
-                blue_latest_down_3 = Some(*time);
-            } else if diff == 4 {
-                blue_latest_down_4 = Some(*time);
+            for (deficit_idx, &deficit) in COMEBACK_DEFICITS.iter().enumerate() {
+                if diff == -(deficit as isize) {
+                    red_latest_down[deficit_idx] = Some(*time);
+                } else if diff == deficit as isize {
+                    blue_latest_down[deficit_idx] = Some(*time);
+                }
             }
 
             // Check for comebacks when score is tied
             if diff == 0 {
-                // Check if red came back from deficits
-                if let Some(deficit_time) = red_latest_down_2 {
-                    if self.all_opponents_present(match_log, Team::Red, deficit_time, *time) {
-                        let red_players = self.get_team_players(match_log, Team::Red);
-                        let red_won = red_score > blue_score || (red_score == blue_score && self.team_won(match_log, Team::Red));
-
-                        if red_won {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_2cap_comeback_win,
-                                deficit_time,
-                                match_id.to_string(),
-                                red_players.clone(),
-                            );
-                        } else {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_2cap_comeback_loss,
-                                deficit_time,
-                                match_id.to_string(),
-                                red_players.clone(),
-                            );
-                        }
-                    }
-                }
-                if let Some(deficit_time) = red_latest_down_3 {
-                    if self.all_opponents_present(match_log, Team::Red, deficit_time, *time) {
-                        let red_players = self.get_team_players(match_log, Team::Red);
-                        let red_won = self.team_won(match_log, Team::Red);
-
-                        if red_won {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_3cap_comeback_win,
-                                deficit_time,
-                                match_id.to_string(),
-                                red_players.clone(),
-                            );
-                        } else {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_3cap_comeback_loss,
-                                deficit_time,
-                                match_id.to_string(),
-                                red_players.clone(),
-                            );
-                        }
-                    }
-                }
-                if let Some(deficit_time) = red_latest_down_4 {
-                    if self.all_opponents_present(match_log, Team::Red, deficit_time, *time) {
-                        let red_players = self.get_team_players(match_log, Team::Red);
-                        let red_won = self.team_won(match_log, Team::Red);
-
-                        if red_won {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_4cap_comeback_win,
-                                deficit_time,
-                                match_id.to_string(),
-                                red_players.clone(),
-                            );
-                        } else {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_4cap_comeback_loss,
-                                deficit_time,
-                                match_id.to_string(),
-                                red_players.clone(),
-                            );
-                        }
-                    }
-                }
-
-                // Check if blue came back from deficits
-                if let Some(deficit_time) = blue_latest_down_2 {
-                    if self.all_opponents_present(match_log, Team::Blue, deficit_time, *time) {
-                        let blue_players = self.get_team_players(match_log, Team::Blue);
-                        let blue_won = self.team_won(match_log, Team::Blue);
-
-                        if blue_won {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_2cap_comeback_win,
-                                deficit_time,
-                                match_id.to_string(),
-                                blue_players.clone(),
-                            );
-                        } else {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_2cap_comeback_loss,
-                                deficit_time,
-                                match_id.to_string(),
-                                blue_players.clone(),
-                            );
-                        }
-                    }
-                }
-                if let Some(deficit_time) = blue_latest_down_3 {
-                    if self.all_opponents_present(match_log, Team::Blue, deficit_time, *time) {
-                        let blue_players = self.get_team_players(match_log, Team::Blue);
-                        let blue_won = self.team_won(match_log, Team::Blue);
-
-                        if blue_won {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_3cap_comeback_win,
-                                deficit_time,
-                                match_id.to_string(),
-                                blue_players.clone(),
-                            );
-                        } else {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_3cap_comeback_loss,
-                                deficit_time,
-                                match_id.to_string(),
-                                blue_players.clone(),
-                            );
-                        }
-                    }
-                }
-                if let Some(deficit_time) = blue_latest_down_4 {
-                    if self.all_opponents_present(match_log, Team::Blue, deficit_time, *time) {
-                        let blue_players = self.get_team_players(match_log, Team::Blue);
-                        let blue_won = self.team_won(match_log, Team::Blue);
-
-                        if blue_won {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_4cap_comeback_win,
-                                deficit_time,
-                                match_id.to_string(),
-                                blue_players.clone(),
-                            );
-                        } else {
-                            Self::insert_comeback_record(
-                                &mut self.comebacks.latest_4cap_comeback_loss,
-                                deficit_time,
-                                match_id.to_string(),
-                                blue_players.clone(),
-                            );
+                for (team, latest_down) in [(Team::Red, &red_latest_down), (Team::Blue, &blue_latest_down)] {
+                    for (deficit_idx, deficit_time) in latest_down.iter().enumerate() {
+                        let Some(deficit_time) = *deficit_time else {
+                            continue;
+                        };
+                        if teams.opponents_full(team, deficit_time, *time) {
+                            let entry = RecordEntry::team(match_id, teams.players(team).to_vec());
+                            self.comebacks.latest[deficit_idx].insert(deficit_time, entry, teams.won(team));
                         }
                     }
                 }
 
                 // Clear all deficit markers after recording to avoid duplicates
-                red_latest_down_2 = None;
-                red_latest_down_3 = None;
-                red_latest_down_4 = None;
-                blue_latest_down_2 = None;
-                blue_latest_down_3 = None;
-                blue_latest_down_4 = None;
-            }
-        }
-    }
-
-    fn team_won(&self, match_log: &MatchLog, team: Team) -> bool {
-        let mut red_caps = 0;
-        let mut blue_caps = 0;
-
-        for player in &match_log.players {
-            let player_events = EventsReader::new(player.events.clone())
-                .player_events(
-                    Team::from_usize(player.team).expect("Could not parse Team enum."),
-                    match_log.duration,
-                );
-
-            let player_team = player_events
-                .iter()
-                .find(|e| e.event_type == Event::Join)
-                .map(|e| e.team)
-                .unwrap_or(Team::from_usize(player.team).expect("Could not parse Team enum."));
-
-            let caps = player_events
-                .iter()
-                .filter(|e| e.event_type == Event::Capture)
-                .count();
-
-            if player_team == Team::Red {
-                red_caps += caps;
-            } else {
-                blue_caps += caps;
-            }
-        }
-
-        match team {
-            Team::Red => red_caps > blue_caps,
-            Team::Blue => blue_caps > red_caps,
-            Team::None => false, // Invalid team can't win
-        }
-    }
-
-    fn get_top_n_cap_runs(
-        map: &BTreeMap<usize, Vec<(String, Vec<String>)>>,
-        n: usize,
-    ) -> Vec<(String, Vec<String>, usize)> {
-        let mut results = Vec::new();
-        for (time, entries) in map.iter() {
-            for (match_id, players) in entries {
-                results.push((match_id.clone(), players.clone(), *time));
-                if results.len() >= n {
-                    return results;
-                }
-            }
-        }
-        results
-    }
-
-    fn get_top_n_comebacks(
-        map: &BTreeMap<usize, Vec<(String, Vec<String>)>>,
-        n: usize,
-    ) -> Vec<(String, Vec<String>, usize)> {
-        let mut results = Vec::new();
-        // For comebacks, we want the LATEST time, so iterate in reverse
-        for (time, entries) in map.iter().rev() {
-            for (match_id, players) in entries {
-                results.push((match_id.clone(), players.clone(), *time));
-                if results.len() >= n {
-                    return results;
-                }
-            }
-        }
-        results
-    }
-
-    fn get_top_n_shortest_games(
-        map: &BTreeMap<usize, Vec<String>>,
-        n: usize,
-    ) -> Vec<(String, usize)> {
-        let mut results = Vec::new();
-        for (duration, match_ids) in map.iter() {
-            for match_id in match_ids {
-                results.push((match_id.clone(), *duration));
-                if results.len() >= n {
-                    return results;
-                }
+                red_latest_down = [None; COMEBACK_DEFICITS.len()];
+                blue_latest_down = [None; COMEBACK_DEFICITS.len()];
             }
         }
-        results
     }
 
     pub fn generate_report(&self, filename: &str) {
@@ -2020,119 +1739,166 @@ impl CapRunsAndComebacksCollector {
         writeln!(file, "## FASTEST CAP RUNS\n").unwrap();
         writeln!(file, "(Runs where at least one opponent was missing are excluded)\n").unwrap();
 
-        self.write_cap_run_leaderboard(&mut file, "### Fastest 2-Cap Run", &self.cap_runs.fastest_2cap);
-        self.write_cap_run_leaderboard(&mut file, "### Fastest 3-Cap Run", &self.cap_runs.fastest_3cap);
-        self.write_cap_run_leaderboard(&mut file, "### Fastest 4-Cap Run", &self.cap_runs.fastest_4cap);
-        self.write_cap_run_leaderboard(&mut file, "### Fastest 5-Cap Run", &self.cap_runs.fastest_5cap);
+        for (length, board) in CAP_RUN_LENGTHS.iter().zip(self.cap_runs.fastest.iter()) {
+            write_leaderboard(&mut file, Some(&format!("### Fastest {}-Cap Run", length)), &board.top(), |row| {
+                format!("{} seconds - {}", row.value / 60, row.players())
+            });
+        }
 
         // Comebacks
         writeln!(file, "\n## LATEST COMEBACKS\n").unwrap();
         writeln!(file, "(Comebacks where opponents were missing are excluded)\n").unwrap();
 
-        writeln!(file, "### 2-Cap Comebacks (In a Win)").unwrap();
-        self.write_comeback_leaderboard(&mut file, &self.comebacks.latest_2cap_comeback_win);
-        writeln!(file, "### 2-Cap Comebacks (In a Loss)").unwrap();
-        self.write_comeback_leaderboard(&mut file, &self.comebacks.latest_2cap_comeback_loss);
-
-        writeln!(file, "### 3-Cap Comebacks (In a Win)").unwrap();
-        self.write_comeback_leaderboard(&mut file, &self.comebacks.latest_3cap_comeback_win);
-        writeln!(file, "### 3-Cap Comebacks (In a Loss)").unwrap();
-        self.write_comeback_leaderboard(&mut file, &self.comebacks.latest_3cap_comeback_loss);
-
-        writeln!(file, "### 4-Cap Comebacks (In a Win)").unwrap();
-        self.write_comeback_leaderboard(&mut file, &self.comebacks.latest_4cap_comeback_win);
-        writeln!(file, "### 4-Cap Comebacks (In a Loss)").unwrap();
-        self.write_comeback_leaderboard(&mut file, &self.comebacks.latest_4cap_comeback_loss);
+        for (deficit, board) in COMEBACK_DEFICITS.iter().zip(self.comebacks.latest.iter()) {
+            for (result, rows) in [("Win", board.wins()), ("Loss", board.losses())] {
+                let heading = format!("### {}-Cap Comebacks (In a {})", deficit, result);
+                write_leaderboard(&mut file, Some(&heading), &rows, |row| {
+                    format!("{} - {}", format_clock(row.value), row.players())
+                });
+            }
+        }
 
         // Shortest games
         writeln!(file, "\n## SHORTEST GAMES (Full Participation)\n").unwrap();
         writeln!(file, "(All 8 players present from start to finish)\n").unwrap();
-        self.write_shortest_games(&mut file);
+        write_leaderboard(&mut file, None, &self.shortest_games.games.top(), |row| format_clock(row.value));
     }
 
-    fn write_cap_run_leaderboard(
-        &self,
-        file: &mut File,
-        title: &str,
-        map: &BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    ) {
-        writeln!(file, "{}", title).unwrap();
-
-        let results = Self::get_top_n_cap_runs(map, 5);
-
-        if results.is_empty() {
-            writeln!(file, "No records found.\n").unwrap();
-            return;
+    // Every board as structured rows, in report order. Values are in seconds.
+    pub fn record_rows(&self) -> Vec<RecordRow> {
+        let seconds = |ticks: usize| ticks as f64 / 60.0;
+        let mut rows = Vec::new();
+        for (length, board) in CAP_RUN_LENGTHS.iter().zip(self.cap_runs.fastest.iter()) {
+            rows.extend(record_rows(&format!("Fastest {}-Cap Run", length), FULL_SCOPE, &board.top(), seconds));
         }
-
-        for (match_id, players, duration) in results {
-            let duration_seconds = duration / 60;
-            writeln!(
-                file,
-                "  Match {}: {} seconds - {}",
-                match_id,
-                duration_seconds,
-                players.join(", ")
-            )
-            .unwrap();
+        for (deficit, board) in COMEBACK_DEFICITS.iter().zip(self.comebacks.latest.iter()) {
+            let stat = format!("Latest {}-Cap Comeback", deficit);
+            rows.extend(record_rows(&stat, FULL_SCOPE, &board.wins(), seconds));
+            rows.extend(record_rows(&stat, FULL_SCOPE, &board.losses(), seconds));
         }
-        writeln!(file).unwrap();
+        rows.extend(record_rows("Shortest Game", FULL_SCOPE, &self.shortest_games.games.top(), seconds));
+        rows
     }
+}
 
-    fn write_comeback_leaderboard(
-        &self,
-        file: &mut File,
-        map: &BTreeMap<usize, Vec<(String, Vec<String>)>>,
-    ) {
-        let results = Self::get_top_n_comebacks(map, 5);
-
-        if results.is_empty() {
-            writeln!(file, "No records found.\n").unwrap();
+pub fn collect_cap_runs_and_comebacks(match_iterator: MatchIterator) {
+    let saved = match CapRunsAndComebacksCollector::load_state(CAP_RUNS_AND_COMEBACKS_STATE_PATH) {
+        Ok(saved) => saved,
+        Err(error) => {
+            eprintln!("{}", error);
             return;
         }
+    };
+    let previous = saved.as_ref().map(CapRunsAndComebacksCollector::record_rows);
+    let mut collector = saved.unwrap_or_else(CapRunsAndComebacksCollector::new);
 
-        for (match_id, players, time) in results {
-            let time_seconds = time / 60;
-            let minutes = time_seconds / 60;
-            let seconds = time_seconds % 60;
-            writeln!(
-                file,
-                "  Match {}: {}:{:02} - {}",
-                match_id,
-                minutes,
-                seconds,
-                players.join(", ")
-            )
-            .unwrap();
-        }
-        writeln!(file).unwrap();
+    for (match_id, match_log) in match_iterator {
+        collector.process_match(match_id, &match_log);
     }
 
-    fn write_shortest_games(&self, file: &mut File) {
-        let results = Self::get_top_n_shortest_games(&self.shortest_games.games, 5);
+    collector.save_state(CAP_RUNS_AND_COMEBACKS_STATE_PATH);
+    collector.generate_report("analysis/cap_runs_and_comebacks.txt");
+    let rows = collector.record_rows();
+    write_record_outputs(&rows, CapRunsAndComebacksCollector::TITLE, "analysis/cap_runs_and_comebacks");
+    write_changes(previous, &rows, CapRunsAndComebacksCollector::TITLE, "analysis/cap_runs_and_comebacks_changes.txt");
+    println!("Cap runs and comebacks collected! Output written to analysis/cap_runs_and_comebacks.{{txt,json,csv,md}}");
+}
 
-        if results.is_empty() {
-            writeln!(file, "No records found.\n").unwrap();
-            return;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_reader::{Player, Team as LogTeam};
+
+    // An 8-minute 4v4 where nobody joins late or leaves early
+    fn full_match() -> MatchLog {
+        let player = |index: usize| Player {
+            auth: false,
+            name: format!("player{}", index),
+            flair: 0,
+            degree: 0,
+            score: 0,
+            points: 0,
+            team: 1 + index / TEAM_SIZE,
+            events: String::new(),
+        };
+        let team = |name: &str| LogTeam { name: name.to_string(), score: 0, splats: String::new() };
+        MatchLog {
+            server: String::new(),
+            port: 0,
+            official: true,
+            group: Some(String::new()),
+            date: 0,
+            time_limit: 8.0,
+            duration: EIGHT_MINUTES,
+            finished: true,
+            map_id: 0,
+            players: (0..2 * TEAM_SIZE).map(player).collect(),
+            teams: [team("Red"), team("Blue")],
         }
+    }
 
-        for (match_id, duration) in results {
-            let duration_seconds = duration / 60;
-            let minutes = duration_seconds / 60;
-            let seconds = duration_seconds % 60;
-            writeln!(file, "  Match {}: {}:{:02}", match_id, minutes, seconds).unwrap();
-        }
-        writeln!(file).unwrap();
+    fn has_rate_rows(rows: &[RecordRow]) -> bool {
+        rows.iter().any(|row| row.scope == RATE_SCOPE)
     }
-}
 
-pub fn collect_cap_runs_and_comebacks(match_iterator: MatchIterator) {
-    let mut collector = CapRunsAndComebacksCollector::new();
+    #[test]
+    fn team_rate_boards_fill_for_a_full_match() {
+        let mut collector = TeamRecordsCollector::new();
+        collector.process_match("1".to_string(), &full_match());
+        assert!(has_rate_rows(&collector.record_rows()));
+    }
 
-    for (match_id, match_log) in match_iterator {
-        collector.process_match(match_id, &match_log);
+    #[test]
+    fn combined_game_rate_boards_fill_for_a_full_match() {
+        let mut collector = CombinedGameRecordsCollector::new();
+        collector.process_match("1".to_string(), &full_match());
+        assert!(has_rate_rows(&collector.record_rows()));
     }
 
-    collector.generate_report("analysis/cap_runs_and_comebacks.txt");
-    println!("Cap runs and comebacks collected! Output written to analysis/cap_runs_and_comebacks.txt");
+    #[test]
+    fn saved_state_loads_only_into_its_own_collector() {
+        let path = std::env::temp_dir().join(format!("ranked_stats_state_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut collector = CareerRecordsCollector::new();
+        collector.process_match("1".to_string(), &full_match());
+        collector.save_state(path);
+
+        let rows = load_record_rows(path).unwrap().unwrap();
+        assert_eq!(rows.len(), collector.record_rows().len());
+        assert!(CareerRecordsCollector::load_state(path).unwrap().is_some());
+        assert!(RecordsCollector::load_state(path).is_err());
+
+        std::fs::write(path, "{").unwrap();
+        assert!(CareerRecordsCollector::load_state(path).is_err());
+        assert!(load_record_rows(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn resuming_needs_the_same_win_probability_model() {
+        let model = |fitted_files| {
+            let mut model = WinProbabilityModel::new(EIGHT_MINUTES);
+            model.fitted_files = Some(fitted_files);
+            Arc::new(model)
+        };
+        let saved = || RecordsCollector::new().with_win_probability(model((403, 414)));
+        assert!(saved().resume_with_win_probability(Some(model((403, 414)))).is_ok());
+        assert!(saved().resume_with_win_probability(Some(model((394, 403)))).is_err());
+        assert!(saved().resume_with_win_probability(None).is_err());
+        assert!(RecordsCollector::new().resume_with_win_probability(Some(model((403, 414)))).is_err());
+    }
+
+    #[test]
+    fn career_wpa_boards_are_record_rows() {
+        let mut model = WinProbabilityModel::new(EIGHT_MINUTES);
+        model.fitted_files = Some((403, 414));
+        let mut collector = RecordsCollector::new().with_win_probability(Arc::new(model));
+        collector.process_match("1".to_string(), &full_match());
+
+        let rows: Vec<RecordRow> = collector.record_rows().into_iter().filter(|row| row.scope == CAREER_SCOPE).collect();
+        let players: BTreeSet<&String> = rows.iter().flat_map(|row| &row.players).collect();
+        // Eight careers: five on the most board and the other three on the least
+        assert_eq!(rows.len(), 2 * TEAM_SIZE);
+        assert_eq!(players.len(), 2 * TEAM_SIZE);
+    }
 }
@@ -0,0 +1,181 @@
+use crate::leaderboard::write_record_outputs;
+use crate::log_reader::{MatchIterator, MatchLog};
+use crate::records::RecordCollector;
+use serde::Deserialize;
+use std::fs::File;
+
+pub const DEFAULT_SEASONS_PATH: &str = "analysis/seasons.json";
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// Days since 1970-01-01 for a proleptic Gregorian date
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Year, month, and day for a number of days since 1970-01-01
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+// Unix timestamp at midnight UTC of a "YYYY-MM-DD" date
+pub fn parse_date(date: &str) -> Option<usize> {
+    let mut parts = date.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    usize::try_from(days_from_civil(year, month, day) * SECONDS_PER_DAY).ok()
+}
+
+// Year and month of a unix timestamp, in UTC
+pub fn year_month(timestamp: usize) -> (i64, u32) {
+    let (year, month, _) = civil_from_days(timestamp as i64 / SECONDS_PER_DAY);
+    (year, month)
+}
+
+// Lowercase name with anything but letters and digits turned into underscores, for file names
+pub fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+}
+
+// Which matches a period covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeriodRange {
+    // Unix timestamps, from `start` up to (not including) `end`
+    Dates { start: usize, end: usize },
+    // Bulk file indices, as passed to MatchIterator::new
+    Files { start_index: usize, end_index: usize },
+}
+
+// A named stretch of matches: a season, a month, or a custom window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Period {
+    pub name: String,
+    pub range: PeriodRange,
+}
+
+impl Period {
+    pub fn dates(name: &str, start: usize, end: usize) -> Self {
+        Self { name: name.to_string(), range: PeriodRange::Dates { start, end } }
+    }
+
+    pub fn files(name: &str, start_index: usize, end_index: usize) -> Self {
+        Self { name: name.to_string(), range: PeriodRange::Files { start_index, end_index } }
+    }
+
+    // The calendar month (UTC) a timestamp falls in
+    pub fn month_of(timestamp: usize) -> Self {
+        let (year, month) = year_month(timestamp);
+        let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+        let start = (days_from_civil(year, month, 1) * SECONDS_PER_DAY) as usize;
+        let end = (days_from_civil(next_year, next_month, 1) * SECONDS_PER_DAY) as usize;
+        Self::dates(&format!("{}-{:02}", year, month), start, end)
+    }
+
+    pub fn contains(&self, file_index: usize, match_log: &MatchLog) -> bool {
+        match self.range {
+            PeriodRange::Dates { start, end } => start <= match_log.date && match_log.date < end,
+            PeriodRange::Files { start_index, end_index } => start_index <= file_index && file_index < end_index,
+        }
+    }
+
+    pub fn slug(&self) -> String {
+        slug(&self.name)
+    }
+}
+
+// One season in the seasons file, either by dates ("YYYY-MM-DD", end exclusive)
+// or by bulk file indices
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SeasonConfig {
+    Dates { name: String, start: String, end: String },
+    Files { name: String, files: (usize, usize) },
+}
+
+pub fn load_seasons(input_path: &str) -> Option<Vec<Period>> {
+    let input_file = File::open(input_path).ok()?;
+    let seasons: Vec<SeasonConfig> = serde_json::from_reader(input_file).expect("Could not parse seasons file");
+    Some(seasons.into_iter()
+        .map(|season| match season {
+            SeasonConfig::Dates { name, start, end } => {
+                let start = parse_date(&start).unwrap_or_else(|| panic!("Invalid start date for {}", name));
+                let end = parse_date(&end).unwrap_or_else(|| panic!("Invalid end date for {}", name));
+                Period::dates(&name, start, end)
+            }
+            SeasonConfig::Files { name, files: (start_index, end_index) } => Period::files(&name, start_index, end_index),
+        })
+        .collect())
+}
+
+// How to split matches into periods
+#[derive(Debug, Clone)]
+pub enum Segmentation {
+    // Fixed periods, such as seasons or a custom window. Periods may overlap.
+    Periods(Vec<Period>),
+    // One period per calendar month with matches in it
+    Monthly,
+}
+
+// Run a fresh collector for every period over the same matches
+pub fn collect_by_period<C: RecordCollector>(
+    mut match_iterator: MatchIterator,
+    segmentation: &Segmentation,
+    new_collector: impl Fn() -> C,
+) -> Vec<(Period, C)> {
+    let mut collectors: Vec<(Period, C)> = match segmentation {
+        Segmentation::Periods(periods) => periods.iter().map(|period| (period.clone(), new_collector())).collect(),
+        Segmentation::Monthly => Vec::new(),
+    };
+
+    while let Some((match_id, match_log)) = match_iterator.next() {
+        let file_index = match_iterator.file_index();
+        if let Segmentation::Monthly = segmentation {
+            let month = Period::month_of(match_log.date);
+            if !collectors.iter().any(|(period, _)| *period == month) {
+                collectors.push((month, new_collector()));
+            }
+        }
+        for (period, collector) in collectors.iter_mut() {
+            if period.contains(file_index, &match_log) {
+                collector.process_match(match_id.clone(), &match_log);
+            }
+        }
+    }
+
+    if let Segmentation::Monthly = segmentation {
+        collectors.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+    }
+    collectors
+}
+
+// Write each period's text report and structured outputs as
+// analysis/<report name>_<period slug>.*
+pub fn write_period_reports<C: RecordCollector>(collectors: &[(Period, C)]) {
+    for (period, collector) in collectors {
+        let base_path = format!("analysis/{}_{}", C::REPORT_NAME, period.slug());
+        collector.generate_report(&format!("{}.txt", base_path));
+        write_record_outputs(&collector.record_rows(), &format!("{} - {}", C::TITLE, period.name), &base_path);
+        println!("{}: {} written to {}.{{txt,json,csv,md}}", period.name, C::TITLE, base_path);
+    }
+}
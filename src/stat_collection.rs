@@ -1,13 +1,11 @@
 #[allow(unused_imports)]
 use num_traits::FromPrimitive;
-use crate::log_reader::MatchIterator;
+use crate::log_reader::{MatchIterator, MatchLog};
+use crate::stints::{Stint, RESPAWN_DURATION};
 use crate::events_reader::{Event, EventsReader, Powerup, Team};
 use std::fs::File;
 use std::io::Write;
 
-const TIME_AFTER_JOIN_TO_IGNORE: isize = 10 * 60;
-const MINIMUM_MATCHUP_LENGTH: isize = 31 * 60;
-const RESPAWN_DURATION: isize = 3 * 60;
 const MINIMUM_RANKED_MATCH_LENGTH: usize = 2 * 60 * 60;
 
 const CSV_HEADER_WITHOUT_STATS: &str = "timestamp,map,duration,diff,r1,r2,r3,r4,b1,b2,b3,b4";
@@ -32,7 +30,6 @@ struct RelevantEvent {
 
 struct PlayerStats {
     name: String,
-    auth: usize,
     caps: usize,
     hold_start: Option<usize>,
     hold: usize,
@@ -104,7 +101,6 @@ pub fn get_ranked_matchups_with_stats(match_iterator: MatchIterator) {
             for player in match_log.players.iter() {
                 player_stats.push(PlayerStats {
                     name: player.name.clone(),
-                    auth: player.auth as usize,
                     caps: 0,
                     hold_start: None,
                     hold: 0,
@@ -381,210 +377,8 @@ pub fn get_matchups_with_stats(match_iterator: MatchIterator) {
         if match_log.official &&
                 match_log.players.len() >= 8 &&
                 match_log.group != Some("redacted".to_string()) {
-            let mut relevant_events: Vec<RelevantEvent> = Vec::new();
-            let mut player_stats: Vec<PlayerStats> = Vec::new();
-            for player in match_log.players.iter() {
-                player_stats.push(PlayerStats {
-                    name: player.name.clone(),
-                    auth: player.auth as usize,
-                    caps: 0,
-                    hold_start: None,
-                    hold: 0,
-                    returns: 0,
-                    prevent_start: None,
-                    prevent: 0,
-                    ndps: 0,
-                    pups: 0
-                });
-            }
-
-            for (i, player) in match_log.players.iter().enumerate() {
-                let player_events = EventsReader::new(player.events.clone())
-                    .player_events(Team::from_usize(player.team).expect("Could not parse Team enum."), match_log.duration);
-
-                // If the player is on a team at the start of the match, add a join event.
-                if Team::from_usize(player.team).expect("Could not parse Team enum.") != Team::None {
-                    relevant_events.push(RelevantEvent {
-                        time: 0,
-                        event_type: Event::Join,
-                        player_index: i,
-                        team: Team::from_usize(player.team).expect("Could not parse Team enum.")
-                    });
-                }
-
-                // track relevent events
-                for event in player_events {
-                    match event.event_type {
-                        Event::Capture => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Capture,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Grab => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Grab,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Pop => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Pop,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Drop => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Drop,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Return => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Return,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Powerup => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Powerup,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::DuplicatePowerup => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Powerup,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Join => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Join,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Quit => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Quit,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Switch => {
-                            relevant_events.push(RelevantEvent {
-                                time: event.time,
-                                event_type: Event::Join,
-                                player_index: i,
-                                team: event.team
-                            });
-                            relevant_events.push(RelevantEvent {
-                                time: event.time,
-                                event_type: Event::Quit,
-                                player_index: i,
-                                team: match event.team {
-                                    Team::Red => Team::Blue,
-                                    Team::Blue => Team::Red,
-                                    _ => Team::None
-                                }
-                            });
-                        },
-                        _ => {}
-                    };
-                }
-            }
-
-            relevant_events.sort_unstable_by_key(|x| x.time);
-
-            let mut red_team: Vec<usize> = Vec::new();
-            let mut blue_team: Vec<usize> = Vec::new();
-            let mut last_join_time: isize = -TIME_AFTER_JOIN_TO_IGNORE - RESPAWN_DURATION;
-            let mut cap_diff: isize = 0;
-
-            for event in relevant_events.iter() {
-                match event.event_type {
-                    Event::Join => {
-                        match event.team {
-                            Team::Red => red_team.push(event.player_index),
-                            Team::Blue => blue_team.push(event.player_index),
-                            _ => {}
-                        }
-                        cap_diff = 0;
-                        last_join_time = event.time as isize;
-                        player_stats.iter_mut().for_each(|player| {
-                            player.caps = 0;
-                            player.hold_start = None;
-                            player.hold = 0;
-                            player.returns = 0;
-                            player.ndps = 0;
-                            player.pups = 0;
-                        });
-                    },
-                    Event::Quit => {
-                        if red_team.len() == 4 &&
-                            blue_team.len() == 4 &&
-                            event.time as isize > (last_join_time + MINIMUM_MATCHUP_LENGTH) {
-                            write_matchup_with_stats(
-                                &mut output_file, match_log.date, match_log.map_id, event.time - (last_join_time as usize), cap_diff,
-                                &red_team, &blue_team, &player_stats
-                            );
-                        }
-                        match event.team {
-                            Team::Red => {red_team.retain(|&x| x != event.player_index);},
-                            Team::Blue => {blue_team.retain(|&x| x != event.player_index);},
-                            _ => {}
-                        };
-                    },
-                    Event::Switch => {
-                        match event.team {
-                            Team::Red => {
-                                red_team.retain(|&x| x != event.player_index);
-                                blue_team.push(event.player_index);
-                            },
-                            Team::Blue => {
-                                blue_team.retain(|&x| x != event.player_index);
-                                red_team.push(event.player_index);
-                            },
-                            _ => {}
-                        }
-                    }
-                    _ => {}
-                }
-
-                if event.time as isize > (last_join_time + TIME_AFTER_JOIN_TO_IGNORE) {
-                    match event.event_type {
-                        Event::Capture => {
-                            cap_diff += match event.team {
-                                Team::Red => 1,
-                                Team::Blue => -1,
-                                _ => 0
-                            };
-                            player_stats[event.player_index].caps += 1;
-                        },
-                        Event::Grab => player_stats[event.player_index].hold_start = Some(event.time),
-                        Event::Drop => match player_stats[event.player_index].hold_start {
-                            Some(hold_start) => {
-                                player_stats[event.player_index].hold += event.time - hold_start;
-                                player_stats[event.player_index].hold_start = None;
-                            },
-                            None => {
-                                player_stats[event.player_index].hold += event.time - (last_join_time + TIME_AFTER_JOIN_TO_IGNORE) as usize;
-                            }
-                        },
-                        Event::Return => player_stats[event.player_index].returns += 1,
-                        Event::Pop => player_stats[event.player_index].ndps += 1,
-                        Event::Powerup => player_stats[event.player_index].pups += 1,
-                        Event::DuplicatePowerup => player_stats[event.player_index].pups += 1,
-                        _ => {}
-                    }
-                }
-            }
-
-            if red_team.len() == 4 &&
-                blue_team.len() == 4 &&
-                match_log.duration as isize > (last_join_time + MINIMUM_MATCHUP_LENGTH) {
-                write_matchup_with_stats(
-                    &mut output_file, match_log.date, match_log.map_id, match_log.duration - last_join_time as usize + RESPAWN_DURATION as usize, cap_diff,
-                    &red_team, &blue_team, &player_stats
-                );
+            for stint in Stint::matchups(&match_log) {
+                write_matchup_with_stats(&mut output_file, &match_log, &stint);
             }
         }
     }
@@ -600,195 +394,40 @@ pub fn get_matchups_without_stats(match_iterator: MatchIterator) {
         if match_log.official &&
                 match_log.players.len() >= 8 &&
                 match_log.group == Some("".to_string()) {
-            let mut relevant_events: Vec<RelevantEvent> = Vec::new();
-            let mut player_stats: Vec<PlayerStats> = Vec::new();
-            for player in match_log.players.iter() {
-                player_stats.push(PlayerStats {
-                    name: player.name.clone(),
-                    auth: player.auth as usize,
-                    caps: 0,
-                    hold_start: None,
-                    hold: 0,
-                    returns: 0,
-                    prevent_start: None,
-                    prevent: 0,
-                    ndps: 0,
-                    pups: 0
-                });
-            }
-
-            for (i, player) in match_log.players.iter().enumerate() {
-                let player_events = EventsReader::new(player.events.clone())
-                    .player_events(Team::from_usize(player.team).expect("Could not parse Team enum."), match_log.duration);
-
-                // If the player is on a team at the start of the match, add a join event.
-                if Team::from_usize(player.team).expect("Could not parse Team enum.") != Team::None {
-                    relevant_events.push(RelevantEvent {
-                        time: 0,
-                        event_type: Event::Join,
-                        player_index: i,
-                        team: Team::from_usize(player.team).expect("Could not parse Team enum.")
-                    });
-                }
-
-                // track relevent events
-                for event in player_events {
-                    match event.event_type {
-                        Event::Capture => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Capture,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Join => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Join,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Quit => relevant_events.push(RelevantEvent {
-                            time: event.time,
-                            event_type: Event::Quit,
-                            player_index: i,
-                            team: event.team
-                        }),
-                        Event::Switch => {
-                            relevant_events.push(RelevantEvent {
-                                time: event.time,
-                                event_type: Event::Join,
-                                player_index: i,
-                                team: event.team
-                            });
-                            relevant_events.push(RelevantEvent {
-                                time: event.time,
-                                event_type: Event::Quit,
-                                player_index: i,
-                                team: match event.team {
-                                    Team::Red => Team::Blue,
-                                    Team::Blue => Team::Red,
-                                    _ => Team::None
-                                }
-                            });
-                        },
-                        _ => {}
-                    };
-                }
-            }
-
-            relevant_events.sort_unstable_by_key(|x| x.time);
-
-            let mut red_team: Vec<usize> = Vec::new();
-            let mut blue_team: Vec<usize> = Vec::new();
-            let mut last_join_time: isize = -TIME_AFTER_JOIN_TO_IGNORE - RESPAWN_DURATION;
-            let mut cap_diff: isize = 0;
-
-            for event in relevant_events.iter() {
-                match event.event_type {
-                    // If a player joins, add them to the appropriate team and reset cap_diff and last_join_time
-                    Event::Join => {
-                        match event.team {
-                            Team::Red => red_team.push(event.player_index),
-                            Team::Blue => blue_team.push(event.player_index),
-                            _ => {}
-                        }
-                        cap_diff = 0;
-                        last_join_time = event.time as isize;
-                    },
-                    // If a player quits, check if the teams are full and the matchup's been going
-                    // on long enough, and write the matchup to file if so. Then remove the leaver
-                    // from their team. (We don't have to update the last_join_time cause teams
-                    // aren't full, so we won't start tracking again till someone else joins.)
-                    Event::Quit => {
-                        if red_team.len() == 4 &&
-                            blue_team.len() == 4 &&
-                            event.time as isize > (last_join_time + MINIMUM_MATCHUP_LENGTH) {
-                            write_matchup_without_stats(
-                                &mut output_file, match_log.date, match_log.map_id, event.time - (last_join_time as usize), cap_diff,
-                                &red_team, &blue_team, &player_stats
-                            );
-                        }
-                        match event.team {
-                            Team::Red => {red_team.retain(|&x| x != event.player_index);},
-                            Team::Blue => {blue_team.retain(|&x| x != event.player_index);},
-                            _ => {}
-                        };
-                    },
-                    // If a player switches teams, remove them from their old team and add them to
-                    // their new team. We don't have to update the last_join_time because if you
-                    // can switch, that means the teams aren't full.
-                    Event::Switch => {
-                        match event.team {
-                            Team::Red => {
-                                red_team.retain(|&x| x != event.player_index);
-                                blue_team.push(event.player_index);
-                            },
-                            Team::Blue => {
-                                blue_team.retain(|&x| x != event.player_index);
-                                red_team.push(event.player_index);
-                            },
-                            _ => {}
-                        }
-                    }
-                    _ => {}
-                }
-
-                // If the event happened long enough after the last join time, update cap_diff if
-                // it was a cap.
-                if event.time as isize > (last_join_time + TIME_AFTER_JOIN_TO_IGNORE) {
-                    match event.event_type {
-                        Event::Capture => {
-                            cap_diff += match event.team {
-                                Team::Red => 1,
-                                Team::Blue => -1,
-                                _ => 0
-                            };
-                        },
-                        _ => {}
-                    }
-                }
-            }
-
-            // At end of match, log the last matchup if teams are full and it's lasted long enough
-            if red_team.len() == 4 &&
-                blue_team.len() == 4 &&
-                match_log.duration as isize > (last_join_time + MINIMUM_MATCHUP_LENGTH) {
-                write_matchup_without_stats(
-                    &mut output_file, match_log.date, match_log.map_id, match_log.duration - last_join_time as usize + RESPAWN_DURATION as usize, cap_diff,
-                    &red_team, &blue_team, &player_stats
-                );
+            // Log every full 4v4 lineup that lasted long enough
+            for stint in Stint::matchups(&match_log) {
+                write_matchup_without_stats(&mut output_file, &match_log, &stint);
             }
         }
     }
 }
 
-// Write matchup data, including player stats, to the output file.
-// date, map_id, duration, cap_diff, then all player names
-fn write_matchup_without_stats(
-    output_file: &mut File,
-    date: usize,
-    map_id: usize,
-    duration: usize,
-    cap_diff: isize,
-    red_team: &Vec<usize>,
-    blue_team: &Vec<usize>,
-    player_stats: &Vec<PlayerStats>
-) {
+// date, map_id, duration, cap_diff, then all player names. A stint that runs to
+// the end of the match gets credit for the respawn time it would have had.
+fn matchup_cells(match_log: &MatchLog, stint: &Stint) -> Vec<String> {
+    let duration = stint.length() + if stint.ends_match { RESPAWN_DURATION } else { 0 };
     let mut cells: Vec<String> = vec![
-        date.to_string(),
-        map_id.to_string(),
+        match_log.date.to_string(),
+        match_log.map_id.to_string(),
         duration.to_string(),
-        cap_diff.to_string()
+        stint.cap_diff.to_string()
     ];
-    let current_players: Vec<usize> = vec![red_team.clone(), blue_team.clone()].concat();
 
     // add player names
-    current_players.iter().for_each(|player| {
+    stint.players().for_each(|player| {
         cells.push(format!(
             "\"{}_{}\"",
-            player_stats[*player].name.escape_default().to_string(),
-            player_stats[*player].auth
+            match_log.players[player].name.escape_default(),
+            match_log.players[player].auth as usize
         ));
     });
+    cells
+}
+
+// Write matchup data to the output file.
+// date, map_id, duration, cap_diff, then all player names
+fn write_matchup_without_stats(output_file: &mut File, match_log: &MatchLog, stint: &Stint) {
+    let cells = matchup_cells(match_log, stint);
     output_file.write_all(
         format!(
             "\n{}",
@@ -800,39 +439,17 @@ fn write_matchup_without_stats(
 
 // Write matchup data, including player stats, to the output file.
 // date, map_id, duration, cap_diff, then all player names, then all their stats
-fn write_matchup_with_stats(
-    output_file: &mut File,
-    date: usize,
-    map_id: usize,
-    duration: usize,
-    cap_diff: isize,
-    red_team: &Vec<usize>,
-    blue_team: &Vec<usize>,
-    player_stats: &Vec<PlayerStats>,
-) {
-    let mut cells: Vec<String> = vec![
-        date.to_string(),
-        map_id.to_string(),
-        duration.to_string(),
-        cap_diff.to_string()
-    ];
-    let current_players: Vec<usize> = vec![red_team.clone(), blue_team.clone()].concat();
+fn write_matchup_with_stats(output_file: &mut File, match_log: &MatchLog, stint: &Stint) {
+    let mut cells = matchup_cells(match_log, stint);
 
-    // add player names
-    current_players.iter().for_each(|player| {
-        cells.push(format!(
-            "\"{}_{}\"",
-            player_stats[*player].name.escape_default().to_string(),
-            player_stats[*player].auth
-        ));
-    });
     // add player stats
-    current_players.iter().for_each(|player| {
-        cells.push(player_stats[*player].caps.to_string());
-        cells.push(player_stats[*player].hold.to_string());
-        cells.push(player_stats[*player].returns.to_string());
-        cells.push(player_stats[*player].ndps.to_string());
-        cells.push(player_stats[*player].pups.to_string());
+    stint.players().for_each(|player| {
+        let stats = &stint.player_stats[player];
+        cells.push(stats.caps.to_string());
+        cells.push(stats.hold.to_string());
+        cells.push(stats.returns.to_string());
+        cells.push(stats.ndps.to_string());
+        cells.push(stats.pups.to_string());
     });
     output_file.write_all(
         format!(
@@ -0,0 +1,174 @@
+use crate::events_reader::{Event, EventsReader, Team};
+use crate::log_reader::MatchLog;
+use crate::participation::Participation;
+use num_traits::FromPrimitive;
+
+// Events this soon after a roster change are left out of a stint, while the new lineup settles in
+pub const TIME_AFTER_JOIN_TO_IGNORE: usize = 10 * 60;
+// Shortest stint worth treating as a matchup
+pub const MINIMUM_MATCHUP_LENGTH: usize = 31 * 60;
+pub const RESPAWN_DURATION: usize = 3 * 60;
+pub const TEAM_SIZE: usize = 4;
+
+// A player's stats inside one stint, not counting the settling-in period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StintStats {
+    pub caps: usize,
+    // Ticks holding the flag, for holds that ended in a drop
+    pub hold: usize,
+    pub returns: usize,
+    pub ndps: usize,
+    pub pups: usize,
+}
+
+// A stretch of a match with the same players on each team
+#[derive(Debug, Clone)]
+pub struct Stint {
+    pub start: usize,
+    pub end: usize,
+    // Player indices, in the order they joined
+    pub red: Vec<usize>,
+    pub blue: Vec<usize>,
+    // Red caps minus blue caps
+    pub cap_diff: isize,
+    // Indexed like MatchLog.players. Players outside the stint have empty stats.
+    pub player_stats: Vec<StintStats>,
+    // Still running when the match ended
+    pub ends_match: bool,
+}
+
+struct StintEvent {
+    time: usize,
+    event_type: Event,
+    player_index: usize,
+    team: Team,
+}
+
+impl Stint {
+    // Every constant-roster interval of a match, in order
+    pub fn from_match_log(match_log: &MatchLog) -> Vec<Stint> {
+        Self::from_match_log_settling(match_log, TIME_AFTER_JOIN_TO_IGNORE)
+    }
+
+    // Like from_match_log, but ignoring events for `settle_time` ticks after each
+    // roster change instead of the default. Zero counts every event.
+    pub fn from_match_log_settling(match_log: &MatchLog, settle_time: usize) -> Vec<Stint> {
+        let participation = Participation::from_match_log(match_log);
+
+        // Any tick where someone joins, leaves, or switches starts a new stint
+        let mut boundaries: Vec<usize> = participation.intervals.iter()
+            .flatten()
+            .flat_map(|interval| [interval.start, interval.end])
+            .chain([0, match_log.duration])
+            .filter(|&time| time <= match_log.duration)
+            .collect();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        let events = Self::events(match_log);
+        boundaries.windows(2)
+            .map(|window| Self::between(match_log, &participation, &events, window[0], window[1], settle_time))
+            .collect()
+    }
+
+    // Full 4v4 stints long enough to count as matchups
+    pub fn matchups(match_log: &MatchLog) -> Vec<Stint> {
+        Self::from_match_log(match_log).into_iter()
+            .filter(|stint| stint.is_full() && stint.end > stint.start + MINIMUM_MATCHUP_LENGTH)
+            .collect()
+    }
+
+    pub fn length(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.red.len() == TEAM_SIZE && self.blue.len() == TEAM_SIZE
+    }
+
+    // Every player in the stint, red first
+    pub fn players(&self) -> impl Iterator<Item = usize> + '_ {
+        self.red.iter().chain(self.blue.iter()).copied()
+    }
+
+    fn events(match_log: &MatchLog) -> Vec<StintEvent> {
+        let mut events = Vec::new();
+        for (player_index, player) in match_log.players.iter().enumerate() {
+            let player_events = EventsReader::new(player.events.clone())
+                .player_events(Team::from_usize(player.team).expect("Could not parse Team enum."), match_log.duration);
+            for event in player_events {
+                match event.event_type {
+                    Event::Capture | Event::Grab | Event::Drop | Event::Return | Event::Pop
+                    | Event::Powerup | Event::DuplicatePowerup => events.push(StintEvent {
+                        time: event.time,
+                        event_type: event.event_type,
+                        player_index,
+                        team: event.team,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        events.sort_by_key(|event| event.time);
+        events
+    }
+
+    fn between(
+        match_log: &MatchLog,
+        participation: &Participation,
+        events: &[StintEvent],
+        start: usize,
+        end: usize,
+        settle_time: usize,
+    ) -> Stint {
+        let mut red: Vec<(usize, usize)> = Vec::new();
+        let mut blue: Vec<(usize, usize)> = Vec::new();
+        for (player_index, intervals) in participation.intervals.iter().enumerate() {
+            for interval in intervals.iter().filter(|interval| interval.start <= start && end <= interval.end) {
+                match interval.team {
+                    Team::Red => red.push((interval.start, player_index)),
+                    Team::Blue => blue.push((interval.start, player_index)),
+                    _ => {}
+                }
+            }
+        }
+        red.sort_unstable();
+        blue.sort_unstable();
+
+        let settled = start + settle_time;
+        let mut cap_diff: isize = 0;
+        let mut player_stats = vec![StintStats::default(); match_log.players.len()];
+        let mut hold_start: Vec<Option<usize>> = vec![None; match_log.players.len()];
+
+        for event in events.iter().filter(|event| event.time > settled && event.time <= end) {
+            let stats = &mut player_stats[event.player_index];
+            match event.event_type {
+                Event::Capture => {
+                    cap_diff += match event.team {
+                        Team::Red => 1,
+                        Team::Blue => -1,
+                        _ => 0,
+                    };
+                    stats.caps += 1;
+                }
+                Event::Grab => hold_start[event.player_index] = Some(event.time),
+                // A drop with no grab in the stint was a hold carried over from before it
+                Event::Drop => stats.hold += event.time - hold_start[event.player_index].take().unwrap_or(settled),
+                Event::Return => stats.returns += 1,
+                Event::Pop => stats.ndps += 1,
+                Event::Powerup | Event::DuplicatePowerup => stats.pups += 1,
+                _ => {}
+            }
+        }
+
+        Stint {
+            start,
+            end,
+            red: red.into_iter().map(|(_, player_index)| player_index).collect(),
+            blue: blue.into_iter().map(|(_, player_index)| player_index).collect(),
+            cap_diff,
+            player_stats,
+            ends_match: end == match_log.duration,
+        }
+    }
+}
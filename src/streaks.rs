@@ -0,0 +1,282 @@
+use crate::aliases::{AliasRegistry, DEFAULT_ALIAS_PATH};
+use crate::events_reader::Team;
+use crate::leaderboard::{record_rows, write_leaderboard, write_record_outputs, Cutoff, Leaderboard, LeaderboardEntry, Order, RecordRow};
+use crate::log_reader::{MatchIterator, MatchLog};
+use crate::records::{is_record_match, write_changes, CollectorState, PlayerStatLines, RecordCollector};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+
+const STREAKS_PER_BOARD: usize = 5;
+const TEAM_SIZE: usize = 4;
+const SECONDS_PER_DAY: usize = 24 * 60 * 60;
+const END_OF_GAME_GRACE: usize = 2 * 60; // leaving in the last 2 seconds still counts as the whole game
+const STREAK_RECORDS_STATE_PATH: &str = "analysis/streak_records_state.json";
+
+// One game in a player's or a fixed team's history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StreakGame {
+    date: usize,
+    match_id: String,
+    won: bool,
+    lost: bool,
+    capped: bool,
+    // Drops and pops (not counting caps)
+    popped: bool,
+}
+
+// A streak kept as a predicate over consecutive games, or as a run of games
+// played on the same day
+enum StreakKind {
+    Consecutive(fn(&StreakGame) -> bool),
+    SameDay,
+}
+
+struct StreakDefinition {
+    title: &'static str,
+    kind: StreakKind,
+}
+
+const PLAYER_STREAKS: &[StreakDefinition] = &[
+    StreakDefinition { title: "Win Streak", kind: StreakKind::Consecutive(|game| game.won) },
+    StreakDefinition { title: "Loss Streak", kind: StreakKind::Consecutive(|game| game.lost) },
+    StreakDefinition { title: "Consecutive Games with a Capture", kind: StreakKind::Consecutive(|game| game.capped) },
+    StreakDefinition { title: "Consecutive Games without a Drop or Pop", kind: StreakKind::Consecutive(|game| !game.popped) },
+    StreakDefinition { title: "Most Games in a Day", kind: StreakKind::SameDay },
+];
+
+const TEAM_STREAKS: &[StreakDefinition] = &[
+    StreakDefinition { title: "Win Streak", kind: StreakKind::Consecutive(|game| game.won) },
+    StreakDefinition { title: "Loss Streak", kind: StreakKind::Consecutive(|game| game.lost) },
+];
+
+// A streak on a board: who, and the first and last match of the run
+#[derive(Debug, Clone)]
+pub struct StreakEntry {
+    matches: String,
+    players: Vec<String>,
+}
+
+impl LeaderboardEntry for StreakEntry {
+    fn match_id(&self) -> &str {
+        &self.matches
+    }
+
+    fn players(&self) -> &[String] {
+        &self.players
+    }
+}
+
+impl StreakKind {
+    // Every maximal run in chronologically sorted games, as (first, last) indices
+    fn runs(&self, games: &[StreakGame]) -> Vec<(usize, usize)> {
+        let mut runs = Vec::new();
+        let mut start: Option<usize> = None;
+        for (index, game) in games.iter().enumerate() {
+            // A new day ends the previous run
+            if let (StreakKind::SameDay, Some(first)) = (self, start)
+                && games[first].date / SECONDS_PER_DAY != game.date / SECONDS_PER_DAY
+            {
+                runs.push((first, index - 1));
+                start = None;
+            }
+
+            let continues = match self {
+                StreakKind::Consecutive(predicate) => predicate(game),
+                StreakKind::SameDay => true,
+            };
+            match (start, continues) {
+                (None, true) => start = Some(index),
+                (Some(first), false) => {
+                    runs.push((first, index - 1));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(first) = start {
+            runs.push((first, games.len() - 1));
+        }
+        runs
+    }
+}
+
+fn streak_boards(
+    definitions: &[StreakDefinition],
+    histories: &BTreeMap<Vec<String>, Vec<StreakGame>>,
+) -> Vec<Leaderboard<usize, StreakEntry>> {
+    definitions.iter()
+        .map(|definition| {
+            let mut board = Leaderboard::new(Order::Highest, Cutoff::Ties(STREAKS_PER_BOARD)).skipping_zero();
+            for (players, games) in histories {
+                let mut games = games.clone();
+                games.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.match_id.cmp(&b.match_id)));
+                for (first, last) in definition.kind.runs(&games) {
+                    let matches = format!("{} to {}", games[first].match_id, games[last].match_id);
+                    board.insert(last - first + 1, StreakEntry { matches, players: players.clone() });
+                }
+            }
+            board
+        })
+        .collect()
+}
+
+// Streaks across matches, in MatchLog.date order, for players and for fixed
+// lineups of four who played a whole game together
+#[derive(Serialize, Deserialize)]
+pub struct StreakRecordsCollector {
+    ingested: BTreeSet<String>,
+    // Histories are keyed by player list: one name for players, four for teams
+    players: BTreeMap<Vec<String>, Vec<StreakGame>>,
+    teams: BTreeMap<Vec<String>, Vec<StreakGame>>,
+    #[serde(skip)]
+    aliases: AliasRegistry,
+}
+
+impl CollectorState for StreakRecordsCollector {}
+
+impl RecordCollector for StreakRecordsCollector {
+    const REPORT_NAME: &'static str = "streak_records";
+    const TITLE: &'static str = "Ranked TagPro Streak Records";
+
+    fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        Self::process_match(self, match_id, match_log);
+    }
+
+    fn generate_report(&self, output_path: &str) {
+        Self::generate_report(self, output_path);
+    }
+
+    fn record_rows(&self) -> Vec<RecordRow> {
+        Self::record_rows(self)
+    }
+}
+
+impl Default for StreakRecordsCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreakRecordsCollector {
+    pub fn new() -> Self {
+        Self {
+            ingested: BTreeSet::new(),
+            players: BTreeMap::new(),
+            teams: BTreeMap::new(),
+            aliases: AliasRegistry::default(),
+        }
+    }
+
+    // Combine alternate accounts under their main names
+    pub fn with_aliases(mut self, aliases: AliasRegistry) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn process_match(&mut self, match_id: String, match_log: &MatchLog) {
+        if !self.ingested.insert(match_id.clone()) {
+            return;
+        }
+
+        // Filter matches
+        if !is_record_match(match_log) {
+            return;
+        }
+
+        let lines = PlayerStatLines::from_match_log(match_log);
+        let participation = &lines.participation;
+        let game = |team: Team| StreakGame {
+            date: match_log.date,
+            match_id: match_id.clone(),
+            won: lines.is_win(team),
+            lost: lines.cap_diff != 0 && !lines.is_win(team),
+            capped: false,
+            popped: false,
+        };
+
+        for (player_idx, player) in match_log.players.iter().enumerate() {
+            if participation.time_played(player_idx) == 0 {
+                continue;
+            }
+            let stats = &lines.full[player_idx];
+            let player_name = self.aliases.resolve(&player.name).to_string();
+            self.players.entry(vec![player_name]).or_default().push(StreakGame {
+                capped: stats.caps > 0,
+                popped: stats.pops > 0,
+                ..game(participation.primary_team(player_idx))
+            });
+        }
+
+        // Fixed teams: the same four players on a side from start to finish
+        for team in [Team::Red, Team::Blue] {
+            let roster = participation.roster(team);
+            let whole_game = roster.iter()
+                .all(|&player_idx| participation.time_on_team(player_idx, team) + END_OF_GAME_GRACE >= match_log.duration);
+            if roster.len() != TEAM_SIZE || !whole_game {
+                continue;
+            }
+            let mut players: Vec<String> = roster.iter()
+                .map(|&player_idx| self.aliases.resolve(&match_log.players[player_idx].name).to_string())
+                .collect();
+            players.sort();
+            self.teams.entry(players).or_default().push(game(team));
+        }
+    }
+
+    pub fn generate_report(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file");
+
+        writeln!(file, "=== RANKED TAGPRO STREAK RECORDS ===\n").unwrap();
+
+        for (title, definitions, histories) in [
+            ("PLAYER STREAKS", PLAYER_STREAKS, &self.players),
+            ("TEAM STREAKS (Same Four Players, Whole Game)", TEAM_STREAKS, &self.teams),
+        ] {
+            writeln!(file, "## {}\n", title).unwrap();
+            for (definition, board) in definitions.iter().zip(streak_boards(definitions, histories)) {
+                write_leaderboard(&mut file, Some(&format!("### {}", definition.title)), &board.top(), |row| {
+                    format!("{} - {}", row.players(), row.value)
+                });
+            }
+        }
+    }
+
+    // Every board as structured rows, in report order
+    pub fn record_rows(&self) -> Vec<RecordRow> {
+        let mut rows = Vec::new();
+        for (scope, definitions, histories) in [("player", PLAYER_STREAKS, &self.players), ("team", TEAM_STREAKS, &self.teams)] {
+            for (definition, board) in definitions.iter().zip(streak_boards(definitions, histories)) {
+                rows.extend(record_rows(definition.title, scope, &board.top(), |value| value as f64));
+            }
+        }
+        rows
+    }
+}
+
+pub fn collect_streak_records(match_iterator: MatchIterator) {
+    let saved = match StreakRecordsCollector::load_state(STREAK_RECORDS_STATE_PATH) {
+        Ok(saved) => saved,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+    let previous = saved.as_ref().map(StreakRecordsCollector::record_rows);
+    let mut collector = saved.unwrap_or_else(StreakRecordsCollector::new);
+    if let Some(aliases) = AliasRegistry::load(DEFAULT_ALIAS_PATH) {
+        collector = collector.with_aliases(aliases);
+    }
+
+    for (match_id, match_log) in match_iterator {
+        collector.process_match(match_id, &match_log);
+    }
+
+    collector.save_state(STREAK_RECORDS_STATE_PATH);
+    collector.generate_report("analysis/streak_records.txt");
+    let rows = collector.record_rows();
+    write_record_outputs(&rows, StreakRecordsCollector::TITLE, "analysis/streak_records");
+    write_changes(previous, &rows, StreakRecordsCollector::TITLE, "analysis/streak_records_changes.txt");
+    println!("Streak records collected! Output written to analysis/streak_records.{{txt,json,csv,md}}");
+}
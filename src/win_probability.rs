@@ -0,0 +1,406 @@
+use crate::analysis_types::MatchFormat;
+use crate::events_reader::{Event, EventsReader, Team};
+use crate::log_reader::{MatchIterator, MatchLog};
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+
+const TIME_BUCKET: usize = 15 * 60; // 15 seconds in ticks (60 ticks per second)
+const MAX_LEAD: isize = 5;
+const DEFAULT_TIME_LIMIT_MINUTES: f32 = 8.0;
+// How many samples' worth of weight the coarser estimate gets when smoothing a cell
+const SMOOTHING_WEIGHT: f64 = 5.0;
+
+pub const DEFAULT_MODEL_PATH: &str = "analysis/win_probability.json";
+
+// Which teams currently have someone carrying a flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlagState {
+    Neither,
+    Red,
+    Blue,
+    Both,
+}
+
+impl FlagState {
+    const ALL: [FlagState; 4] = [FlagState::Neither, FlagState::Red, FlagState::Blue, FlagState::Both];
+
+    pub fn new(red_has_flag: bool, blue_has_flag: bool) -> Self {
+        match (red_has_flag, blue_has_flag) {
+            (false, false) => FlagState::Neither,
+            (true, false) => FlagState::Red,
+            (false, true) => FlagState::Blue,
+            (true, true) => FlagState::Both,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            FlagState::Neither => "neither",
+            FlagState::Red => "red",
+            FlagState::Blue => "blue",
+            FlagState::Both => "both",
+        }
+    }
+}
+
+// Score and flag situation at a point in a match. `cap_diff` is red minus blue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameState {
+    pub time: usize,
+    pub cap_diff: isize,
+    pub flags: FlagState,
+}
+
+impl GameState {
+    pub fn start() -> Self {
+        Self { time: 0, cap_diff: 0, flags: FlagState::Neither }
+    }
+}
+
+// A grab, drop, capture, return or quit, with the game state just before and after it
+#[derive(Debug, Clone)]
+pub struct TimelineEvent {
+    pub time: usize,
+    pub player_index: usize,
+    pub event_type: Event,
+    pub team: Team,
+    pub before: GameState,
+    pub after: GameState,
+}
+
+// Everything in a match that moves the score or flag state, in order, plus the
+// returns that force drops
+#[derive(Debug, Clone)]
+pub struct MatchTimeline {
+    pub events: Vec<TimelineEvent>,
+    pub duration: usize,
+    pub final_cap_diff: isize,
+}
+
+impl MatchTimeline {
+    pub fn from_match_log(match_log: &MatchLog) -> Self {
+        let mut all_events = Vec::new();
+        for (player_idx, player) in match_log.players.iter().enumerate() {
+            let team = Team::from_usize(player.team).expect("Could not parse Team enum.");
+            let player_events = EventsReader::new(player.events.clone())
+                .player_events(team, match_log.duration);
+            for event in player_events {
+                if matches!(event.event_type, Event::Grab | Event::Drop | Event::Capture | Event::Return | Event::Quit) {
+                    all_events.push((event.time, player_idx, event.event_type, event.team));
+                }
+            }
+        }
+        all_events.sort_by_key(|&(time, ..)| time);
+
+        let mut events = Vec::new();
+        let mut cap_diff: isize = 0;
+        let mut red_fc: Option<usize> = None;
+        let mut blue_fc: Option<usize> = None;
+
+        for (time, player_index, event_type, team) in all_events {
+            let before = GameState { time, cap_diff, flags: FlagState::new(red_fc.is_some(), blue_fc.is_some()) };
+            let fc = match team {
+                Team::Red => &mut red_fc,
+                Team::Blue => &mut blue_fc,
+                Team::None => continue,
+            };
+            match event_type {
+                Event::Grab => *fc = Some(player_index),
+                Event::Capture => {
+                    cap_diff += if team == Team::Red { 1 } else { -1 };
+                    *fc = None;
+                }
+                // Quits only matter if the player was carrying the flag
+                Event::Drop | Event::Quit => {
+                    if *fc != Some(player_index) {
+                        continue;
+                    }
+                    *fc = None;
+                }
+                // Returns don't change anything themselves; the carrier's drop does
+                Event::Return => {}
+                _ => continue,
+            }
+            let after = GameState { time, cap_diff, flags: FlagState::new(red_fc.is_some(), blue_fc.is_some()) };
+            events.push(TimelineEvent { time, player_index, event_type, team, before, after });
+        }
+
+        Self { events, duration: match_log.duration, final_cap_diff: cap_diff }
+    }
+
+    pub fn state_at(&self, time: usize) -> GameState {
+        let state = self.events.iter()
+            .take_while(|event| event.time <= time)
+            .last()
+            .map_or(GameState::start(), |event| event.after);
+        GameState { time, ..state }
+    }
+
+    // 1 if red won, 0 if blue won, a half for a tie
+    pub fn red_result(&self) -> f64 {
+        match self.final_cap_diff.signum() {
+            1 => 1.0,
+            -1 => 0.0,
+            _ => 0.5,
+        }
+    }
+}
+
+// P(red wins | cap diff, time remaining, flag state), binned and fitted from
+// historical matches. Time remaining is bucketed, with bucket 0 for overtime.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WinProbabilityModel {
+    pub time_limit: usize,
+    pub time_bucket: usize,
+    pub max_lead: isize,
+    // Red wins (ties count half) and samples for each cell
+    red_wins: Vec<f64>,
+    samples: Vec<f64>,
+    // Match files [start, end) the model was fitted on, if known
+    #[serde(default)]
+    pub fitted_files: Option<(usize, usize)>,
+}
+
+impl WinProbabilityModel {
+    pub fn new(time_limit: usize) -> Self {
+        let mut model = Self {
+            time_limit,
+            time_bucket: TIME_BUCKET,
+            max_lead: MAX_LEAD,
+            red_wins: Vec::new(),
+            samples: Vec::new(),
+            fitted_files: None,
+        };
+        let cells = model.lead_count() * model.bucket_count() * FlagState::ALL.len();
+        model.red_wins = vec![0.0; cells];
+        model.samples = vec![0.0; cells];
+        model
+    }
+
+    pub fn fit(match_iterator: MatchIterator, format: &MatchFormat) -> Self {
+        let time_limit = format.time_limit.unwrap_or(DEFAULT_TIME_LIMIT_MINUTES);
+        let mut model = Self::new((time_limit * 60.0 * 60.0).round() as usize);
+        for (_match_id, match_log) in match_iterator {
+            if format.accepts(&match_log) {
+                model.add_match(&MatchTimeline::from_match_log(&match_log));
+            }
+        }
+        model
+    }
+
+    // Sample the match once per time bucket and count its result in each cell
+    pub fn add_match(&mut self, timeline: &MatchTimeline) {
+        let result = timeline.red_result();
+        for time in (0..timeline.duration).step_by(self.time_bucket) {
+            let cell = self.cell_index(&timeline.state_at(time));
+            self.red_wins[cell] += result;
+            self.samples[cell] += 1.0;
+        }
+    }
+
+    // Chance red wins from this state. Sparse cells are pulled toward the
+    // estimate for the same score and time with any flag state, which is in
+    // turn pulled toward the estimate for the same score at any time.
+    pub fn red_win_probability(&self, state: &GameState) -> f64 {
+        let lead = self.lead_index(state.cap_diff);
+        let bucket = self.bucket_index(state.time);
+        let flags = FlagState::ALL.len();
+
+        let lead_cells = lead * self.bucket_count() * flags..(lead + 1) * self.bucket_count() * flags;
+        let lead_estimate = self.smoothed(lead_cells, 0.5);
+        let time_start = (lead * self.bucket_count() + bucket) * flags;
+        let time_estimate = self.smoothed(time_start..time_start + flags, lead_estimate);
+        let cell = self.cell_index(state);
+        self.smoothed(cell..cell + 1, time_estimate)
+    }
+
+    pub fn win_probability(&self, team: Team, state: &GameState) -> f64 {
+        match team {
+            Team::Red => self.red_win_probability(state),
+            Team::Blue => 1.0 - self.red_win_probability(state),
+            Team::None => 0.5,
+        }
+    }
+
+    // Chance that whichever team is ahead by `lead` wins, averaged over colors
+    pub fn leader_win_probability(&self, time: usize, lead: isize) -> f64 {
+        let lead = lead.abs();
+        let red_ahead = GameState { time, cap_diff: lead, flags: FlagState::Neither };
+        let blue_ahead = GameState { time, cap_diff: -lead, flags: FlagState::Neither };
+        (self.red_win_probability(&red_ahead) + 1.0 - self.red_win_probability(&blue_ahead)) / 2.0
+    }
+
+    // Red's win probability at the start, every time bucket, and every event
+    pub fn curve(&self, timeline: &MatchTimeline) -> Vec<(GameState, f64)> {
+        let mut states: Vec<GameState> = (0..timeline.duration)
+            .step_by(self.time_bucket)
+            .map(|time| timeline.state_at(time))
+            .collect();
+        states.extend(timeline.events.iter().map(|event| event.after));
+        states.sort_by_key(|state| state.time);
+        states.dedup();
+        states.into_iter()
+            .map(|state| (state, self.red_win_probability(&state)))
+            .collect()
+    }
+
+    // Win probability added by each player: the change in their team's chance
+    // of winning across every grab, capture and drop they made. A return gets
+    // the swing of the drop it forced, so a pop that loses the flag is charged
+    // to the carrier once rather than also as a pop.
+    pub fn player_wpa(&self, timeline: &MatchTimeline, player_count: usize) -> Vec<f64> {
+        let mut wpa = vec![0.0; player_count];
+        for event in timeline.events.iter() {
+            let swing = self.win_probability(event.team, &event.after) - self.win_probability(event.team, &event.before);
+            match event.event_type {
+                Event::Grab | Event::Capture => wpa[event.player_index] += swing,
+                Event::Drop | Event::Quit => {
+                    wpa[event.player_index] += swing;
+                    let returner = timeline.events.iter()
+                        .find(|other| other.time == event.time && other.event_type == Event::Return && other.team != event.team);
+                    if let Some(returner) = returner {
+                        wpa[returner.player_index] -= swing;
+                    }
+                }
+                _ => {}
+            }
+        }
+        wpa
+    }
+
+    pub fn write_curve_csv(&self, match_log: &MatchLog, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file.");
+        self.write_curve(&MatchTimeline::from_match_log(match_log), &mut file);
+    }
+
+    pub fn write_curve(&self, timeline: &MatchTimeline, output: &mut impl Write) {
+        writeln!(output, "time,cap_diff,flags,red_win_probability").unwrap();
+        for (state, probability) in self.curve(timeline) {
+            writeln!(output, "{},{},{},{:.4}", state.time, state.cap_diff, state.flags.label(), probability).unwrap();
+        }
+    }
+
+    // The whole table, one row per cell
+    pub fn write_table_csv(&self, output_path: &str) {
+        let mut file = File::create(output_path).expect("Could not create output file.");
+        writeln!(file, "cap_diff,time_remaining,flags,samples,red_win_probability").unwrap();
+        for cap_diff in -self.max_lead..=self.max_lead {
+            for bucket in 0..self.bucket_count() {
+                let time_remaining = bucket * self.time_bucket;
+                for flags in FlagState::ALL {
+                    let state = GameState { time: self.time_limit.saturating_sub(time_remaining), cap_diff, flags };
+                    writeln!(file, "{},{},{},{},{:.4}", cap_diff, time_remaining, flags.label(),
+                        self.samples[self.cell_index(&state)], self.red_win_probability(&state)).unwrap();
+                }
+            }
+        }
+    }
+
+    pub fn save(&self, output_path: &str) {
+        let output_file = File::create(output_path).expect("Could not create output file.");
+        serde_json::to_writer(output_file, self).expect("Could not write win probability model to file.");
+    }
+
+    pub fn load(input_path: &str) -> Option<Self> {
+        let input_file = File::open(input_path).ok()?;
+        serde_json::from_reader(input_file).ok()
+    }
+
+    // Load a model to score match files [start_index, end_index) with. It must
+    // have been fitted on other files, so no match is scored by a model that
+    // already saw its result.
+    pub fn load_for_scoring(input_path: &str, start_index: usize, end_index: usize) -> Result<Self, String> {
+        let model = Self::load(input_path).ok_or_else(|| format!("Could not read win probability model from {}", input_path))?;
+        match model.fitted_files {
+            None => Err(format!("{} doesn't record which match files it was fitted on; refit it with fit_win_probability", input_path)),
+            Some((start, end)) if start < end_index && start_index < end => Err(format!(
+                "{} was fitted on match files {} through {}, which overlap the files being scored ({} through {}); \
+                 score other files with --files <start> <end>",
+                input_path, start, end - 1, start_index, end_index - 1,
+            )),
+            Some(_) => Ok(model),
+        }
+    }
+
+    // The model named by a `--wpa-model <path>` argument, for scoring match
+    // files [start_index, end_index). None when the flag isn't given.
+    pub fn from_args(args: &[String], start_index: usize, end_index: usize) -> Result<Option<Self>, String> {
+        let Some(flag) = args.iter().position(|arg| arg == "--wpa-model") else {
+            return Ok(None);
+        };
+        let path = args.get(flag + 1).ok_or("--wpa-model needs the path to a fitted win probability model")?;
+        Self::load_for_scoring(path, start_index, end_index).map(Some)
+    }
+
+    fn smoothed(&self, cells: std::ops::Range<usize>, prior: f64) -> f64 {
+        let red_wins: f64 = self.red_wins[cells.clone()].iter().sum();
+        let samples: f64 = self.samples[cells].iter().sum();
+        (red_wins + SMOOTHING_WEIGHT * prior) / (samples + SMOOTHING_WEIGHT)
+    }
+
+    fn lead_count(&self) -> usize {
+        (2 * self.max_lead + 1) as usize
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.time_limit.div_ceil(self.time_bucket) + 1
+    }
+
+    fn lead_index(&self, cap_diff: isize) -> usize {
+        (cap_diff.clamp(-self.max_lead, self.max_lead) + self.max_lead) as usize
+    }
+
+    // Buckets count down to the end of regulation; overtime is bucket 0
+    fn bucket_index(&self, time: usize) -> usize {
+        self.time_limit.saturating_sub(time).div_ceil(self.time_bucket)
+    }
+
+    fn cell_index(&self, state: &GameState) -> usize {
+        (self.lead_index(state.cap_diff) * self.bucket_count() + self.bucket_index(state.time)) * FlagState::ALL.len()
+            + state.flags.index()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(time: usize, event_type: Event, before: GameState, after: GameState) -> TimelineEvent {
+        TimelineEvent { time, player_index: 0, event_type, team: Team::Red, before, after }
+    }
+
+    #[test]
+    fn curve_has_a_row_per_bucket_and_event() {
+        let start = GameState::start();
+        let carrying = GameState { time: 100, cap_diff: 0, flags: FlagState::Red };
+        let capped = GameState { time: 200, cap_diff: 1, flags: FlagState::Neither };
+        let timeline = MatchTimeline {
+            events: vec![
+                event(100, Event::Grab, GameState { time: 100, ..start }, carrying),
+                event(200, Event::Capture, GameState { time: 200, ..carrying }, capped),
+            ],
+            duration: 2 * TIME_BUCKET,
+            final_cap_diff: 1,
+        };
+        let mut model = WinProbabilityModel::new(2 * TIME_BUCKET);
+        model.add_match(&timeline);
+
+        let mut output = Vec::new();
+        model.write_curve(&timeline, &mut output);
+        let output = String::from_utf8(output).unwrap();
+        let rows: Vec<Vec<&str>> = output.lines().map(|line| line.split(',').collect()).collect();
+
+        assert_eq!(rows[0], ["time", "cap_diff", "flags", "red_win_probability"]);
+        let states: Vec<(&str, &str, &str)> = rows[1..].iter().map(|row| (row[0], row[1], row[2])).collect();
+        assert_eq!(states, [("0", "0", "neither"), ("100", "0", "red"), ("200", "1", "neither"), ("900", "1", "neither")]);
+        let probabilities: Vec<f64> = rows[1..].iter().map(|row| row[3].parse().unwrap()).collect();
+        // Red won the only fitted match, so every state it passed through leans red
+        assert!(probabilities.iter().all(|&probability| probability > 0.5 && probability < 1.0));
+    }
+}